@@ -0,0 +1,173 @@
+//! Async versions of the [`Table`] methods, enabled by the `async` feature.
+
+use std::{collections::HashMap, panic, time::Duration};
+
+use tokio::task;
+
+use crate::{
+    database::Database,
+    query_parser::{Condition, SelectCols, SelectOptions},
+    table::{ColOptions, Schema, Table, TableEntries, TableError},
+    types::DataType,
+};
+
+type TableResult<T> = Result<T, TableError>;
+
+/// A [`Table`] owning its names, so it can be moved into async tasks.
+///
+/// Every call runs the matching [`Table`] method on the tokio blocking pool, the way
+/// `tokio::fs` runs file operations, so the runtime threads are never blocked by the disk.
+#[derive(Debug, Clone)]
+pub struct AsyncTable {
+    pub db: String,
+    pub table_name: String,
+    /// How long to wait for the table lock, forever if `None`
+    pub lock_timeout: Option<Duration>,
+}
+
+impl AsyncTable {
+    pub fn new(db: &str, table_name: &str) -> TableResult<Self> {
+        Database::exists_or_err(db)?;
+        Ok(Self {
+            db: db.to_string(),
+            table_name: table_name.to_string(),
+            lock_timeout: None,
+        })
+    }
+
+    /// Run `f` with the table on the blocking pool.
+    async fn run<T, F>(&self, f: F) -> TableResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Table) -> TableResult<T> + Send + 'static,
+    {
+        let this = self.clone();
+        let res = task::spawn_blocking(move || {
+            let mut table = Table::new(&this.db, &this.table_name)?;
+            table.lock_timeout = this.lock_timeout;
+            f(&table)
+        })
+        .await;
+
+        match res {
+            Ok(res) => res,
+            Err(e) => panic::resume_unwind(e.into_panic()),
+        }
+    }
+
+    pub async fn create(&self, schema: Schema) -> TableResult<()> {
+        self.run(move |t| t.create(schema)).await
+    }
+
+    pub async fn drop(&self) -> TableResult<()> {
+        self.run(|t| t.drop()).await
+    }
+
+    pub async fn insert(
+        &self,
+        cols: SelectCols,
+        values: Vec<Vec<Option<String>>>,
+    ) -> TableResult<()> {
+        self.run(move |t| t.insert(cols, values)).await
+    }
+
+    pub async fn select(
+        &self,
+        cols: SelectCols,
+        condition: Option<Condition>,
+        options: SelectOptions,
+    ) -> TableResult<TableEntries> {
+        self.run(move |t| t.select(cols, condition, options)).await
+    }
+
+    pub async fn update(
+        &self,
+        set: HashMap<String, Option<String>>,
+        condition: Option<Condition>,
+    ) -> TableResult<usize> {
+        self.run(move |t| t.update(set, condition)).await
+    }
+
+    pub async fn delete(&self, condition: Condition) -> TableResult<()> {
+        self.run(move |t| t.delete(condition)).await
+    }
+
+    pub async fn alter(&self, col_name: &str, datatype: DataType, force: bool) -> TableResult<()> {
+        let col_name = col_name.to_string();
+        self.run(move |t| t.alter(&col_name, datatype, force)).await
+    }
+
+    pub async fn add_col(
+        &self,
+        col_name: &str,
+        datatype: DataType,
+        options: ColOptions,
+    ) -> TableResult<()> {
+        let col_name = col_name.to_string();
+        self.run(move |t| t.add_col(&col_name, datatype, options))
+            .await
+    }
+
+    pub async fn remove_col(&self, col_name: &str) -> TableResult<()> {
+        let col_name = col_name.to_string();
+        self.run(move |t| t.remove_col(col_name.as_str())).await
+    }
+
+    pub async fn truncate(&self, reset_seq: bool) -> TableResult<()> {
+        self.run(move |t| t.truncate(reset_seq)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        query_parser::{SelectCols, SelectOptions},
+        table::{
+            tests::{eq, row, set, TestDB},
+            Schema, TableError,
+        },
+        types::DataType,
+    };
+
+    use super::AsyncTable;
+
+    #[tokio::test]
+    async fn async_table() {
+        let db = TestDB::new("test_async_table");
+        let table = AsyncTable::new(&db.0, "users").unwrap();
+        table
+            .create(Schema::new(
+                vec!["name".into(), "age".into()],
+                vec![DataType::TEXT, DataType::INT],
+            ))
+            .await
+            .unwrap();
+
+        table
+            .insert(
+                SelectCols::All,
+                vec![row(&["jone", "20"]), row(&["jane", "30"])],
+            )
+            .await
+            .unwrap();
+        let updated = table
+            .update(set(&[("age", "31")]), Some(eq("name", "jane")))
+            .await
+            .unwrap();
+        assert_eq!(updated, 1);
+        table.delete(eq("name", "jone")).await.unwrap();
+
+        let entries = table
+            .select(SelectCols::All, None, SelectOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["age"].as_deref(), Some("31"));
+
+        table.drop().await.unwrap();
+        assert!(matches!(
+            table.truncate(false).await,
+            Err(TableError::TableNotFond(_))
+        ));
+    }
+}
@@ -1,7 +1,17 @@
-use std::{fs, io, path::Path, str::FromStr};
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io,
+    path::Path,
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use thiserror::Error;
 
-use crate::utils::get_db_path;
+use crate::{
+    lock::{FileLock, LockError},
+    utils::{get_base_dir, get_db_path, lock_file},
+};
 
 pub const DB_DIR: &str = "./sql";
 pub const CURR_DB: &str = "curr_db";
@@ -14,6 +24,8 @@ pub enum DatabaseError {
     IoError(#[from] io::Error),
     #[error("Database not found")]
     NotFound(String),
+    #[error("Lock Error")]
+    LockErr(#[from] LockError),
 }
 
 type DBResult<T> = Result<T, DatabaseError>;
@@ -21,7 +33,7 @@ type DBResult<T> = Result<T, DatabaseError>;
 pub struct Database;
 impl Database {
     pub fn new(name: &str) -> DBResult<()> {
-        let base_dir = Path::new(DB_DIR);
+        let base_dir = get_base_dir();
         let db_dir = base_dir.join(name);
         if db_dir.exists() {
             return Err(DatabaseError::DuplicatedDB(name.to_string()));
@@ -32,7 +44,7 @@ impl Database {
     }
 
     pub fn drop(name: &str) -> DBResult<()> {
-        let base_dir = Path::new(DB_DIR);
+        let base_dir = get_base_dir();
         let db_dir = base_dir.join(name);
 
         if !db_dir.exists() {
@@ -42,8 +54,17 @@ impl Database {
         Ok(())
     }
 
+    /// Drop the database if it exists and return whether it did.
+    pub fn drop_if_exists(name: &str) -> DBResult<bool> {
+        match Database::drop(name) {
+            Ok(()) => Ok(true),
+            Err(DatabaseError::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn use_db(name: &str) -> DBResult<()> {
-        let base_dir = Path::new(DB_DIR);
+        let base_dir = get_base_dir();
         Database::exists_or_err(name)?;
         let curr_db = base_dir.join(CURR_DB);
         fs::write(curr_db, name)?;
@@ -51,35 +72,154 @@ impl Database {
     }
 
     pub fn get_curr_db() -> DBResult<String> {
-        let base_dir = Path::new(DB_DIR);
+        let base_dir = get_base_dir();
         let curr_db = base_dir.join(CURR_DB);
         let db = fs::read_to_string(curr_db)?;
         Database::exists_or_err(&db)?;
         Ok(db)
     }
 
+    /// Names of the databases in [`DB_DIR`], in alphabetical order. The directory is created
+    /// if it doesn't exist yet.
     pub fn get_dbs() -> DBResult<Vec<String>> {
-        let base_dir = Path::new(DB_DIR);
-        let dbs = fs::read_dir(base_dir)?
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path().is_dir())
-            .map(|e| String::from_str(e.file_name().to_str().unwrap()).unwrap())
-            .collect::<Vec<_>>();
+        Database::dbs_in(&get_base_dir())
+    }
+
+    /// The directories of `base_dir`, other entries like the [`CURR_DB`] file are skipped.
+    fn dbs_in(base_dir: &Path) -> DBResult<Vec<String>> {
+        fs::create_dir_all(base_dir)?;
+        let mut dbs = Vec::new();
+        for entry in fs::read_dir(base_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Ok(name) = entry.file_name().into_string() {
+                    dbs.push(name);
+                }
+            }
+        }
+        dbs.sort();
 
         Ok(dbs)
     }
 
     pub fn get_db_tables(db_name: &str) -> DBResult<Vec<String>> {
+        Database::list_tables(db_name, false)
+    }
+
+    /// Names of the tables in `db`, along with its views if `include_views` is set, in
+    /// alphabetical order.
+    ///
+    /// Only names with both a `<name>.json` data file and a `<name>.schema.json` schema are
+    /// listed, see [`Database::incomplete_tables`] for the others. Views are the names with a
+    /// `<name>.view.json` definition.
+    pub fn list_tables(db: &str, include_views: bool) -> DBResult<Vec<String>> {
+        let (data, schemas) = Database::table_files(db)?;
+        let mut names = data
+            .intersection(&schemas)
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        if include_views {
+            names.extend(Database::view_files(db)?);
+        }
+        Ok(names.into_iter().collect())
+    }
+
+    /// The names of the view definitions in `db`.
+    fn view_files(db: &str) -> DBResult<BTreeSet<String>> {
+        let mut views = BTreeSet::new();
+        for entry in fs::read_dir(get_db_path(db))?.filter_map(|e| e.ok()) {
+            if let Some(name) = entry
+                .file_name()
+                .to_str()
+                .and_then(|f| f.strip_suffix(".view.json"))
+            {
+                views.insert(name.to_string());
+            }
+        }
+        Ok(views)
+    }
+
+    /// Names in `db` with a data file but no schema or a schema but no data file, in
+    /// alphabetical order.
+    pub fn incomplete_tables(db: &str) -> DBResult<Vec<String>> {
+        let (data, schemas) = Database::table_files(db)?;
+        Ok(data.symmetric_difference(&schemas).cloned().collect())
+    }
+
+    /// The names of the data files and of the schema files in `db`.
+    fn table_files(db: &str) -> DBResult<(BTreeSet<String>, BTreeSet<String>)> {
+        Database::exists_or_err(db)?;
+        let mut data = BTreeSet::new();
+        let mut schemas = BTreeSet::new();
+        for entry in fs::read_dir(get_db_path(db))?.filter_map(|e| e.ok()) {
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            if let Some(name) = file_name.strip_suffix(".schema.json") {
+                schemas.insert(name.to_string());
+            } else if let Some(name) = file_name.strip_suffix(".json") {
+                // Column indexes are `<table>.<col>.index.json` and views `<view>.view.json`
+                if !name.ends_with(".index") && !name.ends_with(".view") {
+                    data.insert(name.to_string());
+                }
+            }
+        }
+
+        Ok((data, schemas))
+    }
+
+    /// Write the files of `db_name` to a gzip compressed tar archive at `dest_path`, see
+    /// [`Database::restore`].
+    ///
+    /// Every table is locked for reading until the archive is written, so it holds the tables
+    /// as they were at one point. Lock files and unfinished writes are left out.
+    pub fn backup(db_name: &str, dest_path: &Path) -> DBResult<()> {
         Database::exists_or_err(db_name)?;
-        let db_path = get_db_path(db_name);
-        let tables = fs::read_dir(db_path)?
-            .filter_map(|e| e.ok())
-            .map(|e| String::from_str(e.file_name().to_str().unwrap()).unwrap())
-            .filter(|f| f.ends_with("schema.json"))
-            .map(|f| f.split(".").nth(0).unwrap().to_string())
-            .collect::<Vec<_>>();
+        let db_dir = get_db_path(db_name);
+        // Tables are listed in order, so concurrent backups lock them in the same order
+        let _locks = Database::list_tables(db_name, false)?
+            .iter()
+            .map(|table| FileLock::shared(&db_dir.join(lock_file(table)), None))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(tables)
+        let mut files = BTreeSet::new();
+        for entry in fs::read_dir(&db_dir)? {
+            let entry = entry?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if entry.file_type()?.is_file() && !name.ends_with(".lock") && !name.ends_with(".tmp") {
+                files.insert(name);
+            }
+        }
+
+        let encoder = GzEncoder::new(File::create(dest_path)?, Compression::default());
+        let mut archive = tar::Builder::new(encoder);
+        for name in files {
+            archive.append_path_with_name(db_dir.join(&name), &name)?;
+        }
+        archive.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    /// Create the database `dest_db_name` from an archive written by [`Database::backup`].
+    ///
+    /// Fails with [`DatabaseError::DuplicatedDB`] if the database already exists. Nothing is
+    /// left behind if the archive can't be unpacked.
+    pub fn restore(archive_path: &Path, dest_db_name: &str) -> DBResult<()> {
+        let db_dir = get_db_path(dest_db_name);
+        if db_dir.exists() {
+            return Err(DatabaseError::DuplicatedDB(dest_db_name.to_string()));
+        }
+
+        let mut archive = tar::Archive::new(GzDecoder::new(File::open(archive_path)?));
+        fs::create_dir_all(&db_dir)?;
+        if let Err(e) = archive.unpack(&db_dir) {
+            let _ = fs::remove_dir_all(&db_dir);
+            return Err(e.into());
+        }
+        Ok(())
     }
 
     pub fn exists(name: &str) -> bool {
@@ -95,3 +235,31 @@ impl Database {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::{Database, CURR_DB};
+
+    #[test]
+    fn dbs_in_base_dir() {
+        let base_dir = env::temp_dir().join(format!("sql_test_get_dbs_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base_dir);
+
+        assert!(Database::dbs_in(&base_dir).unwrap().is_empty());
+        assert!(base_dir.is_dir());
+
+        for db in ["shop", "blog", "auth"] {
+            fs::create_dir(base_dir.join(db)).unwrap();
+        }
+        fs::write(base_dir.join(CURR_DB), "shop").unwrap();
+        fs::write(base_dir.join("notes.txt"), "").unwrap();
+        assert_eq!(
+            Database::dbs_in(&base_dir).unwrap(),
+            vec!["auth", "blog", "shop"]
+        );
+
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+}
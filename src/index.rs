@@ -0,0 +1,242 @@
+use std::{collections::HashMap, fs};
+
+use crate::{
+    query_parser::{Condition, ConditionValue, Operator},
+    table::{Schema, Table, TableEntries, TableError},
+    utils::get_index_path,
+};
+
+type TableResult<T> = Result<T, TableError>;
+
+/// The positions of the rows holding each value of the indexed column, `NULL`s are left out.
+pub type IndexEntries = HashMap<String, Vec<usize>>;
+
+/// A column index, stored as `<table>.<col>.index.json` next to the table.
+///
+/// The indexes of a table are rebuilt every time its rows are written, [`Table::select`] and
+/// [`Table::count`] use them for conditions comparing an indexed column with `=`.
+pub struct Index;
+
+impl Index {
+    /// Index `col` of the table, failing with [`TableError::IndexAlreadyExists`] if it already
+    /// is.
+    pub fn create(table: &Table, col: &str) -> TableResult<()> {
+        let schema = table.read_schema()?;
+        if !schema.cols.iter().any(|c| c == col) {
+            return Err(TableError::ColNotFound(col.to_string()));
+        }
+
+        let _lock = table.lock(false)?;
+        if Index::exists(table, col) {
+            return Err(TableError::IndexAlreadyExists(col.to_string()));
+        }
+        let entries = table.load()?;
+        Index::write(table, col, &entries)
+    }
+
+    pub fn drop(table: &Table, col: &str) -> TableResult<()> {
+        let _lock = table.lock(false)?;
+        if !Index::exists(table, col) {
+            return Err(TableError::IndexNotFound(col.to_string()));
+        }
+        fs::remove_file(get_index_path(table, col))?;
+        Ok(())
+    }
+
+    pub fn exists(table: &Table, col: &str) -> bool {
+        get_index_path(table, col).exists()
+    }
+
+    pub(crate) fn build(entries: &TableEntries, col: &str) -> IndexEntries {
+        let mut index = IndexEntries::new();
+        for (pos, entry) in entries.iter().enumerate() {
+            if let Some(Some(value)) = entry.get(col) {
+                index.entry(value.clone()).or_default().push(pos);
+            }
+        }
+        index
+    }
+
+    /// Rebuild every index of the table from `entries`, the caller holds the table lock.
+    ///
+    /// An index that can't be written is removed so it never points at the wrong rows.
+    pub(crate) fn update_all(
+        table: &Table,
+        schema: &Schema,
+        entries: &TableEntries,
+    ) -> TableResult<()> {
+        for col in schema.cols.iter().filter(|col| Index::exists(table, col)) {
+            if let Err(e) = Index::write(table, col, entries) {
+                let _ = fs::remove_file(get_index_path(table, col));
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// The positions of the rows that can match `condition`, `None` if no index applies.
+    ///
+    /// Only an `=` on an indexed column, alone or on either side of an `AND`, uses an index. The
+    /// caller holds the table lock and still has to match the returned rows.
+    pub(crate) fn positions(
+        table: &Table,
+        schema: &Schema,
+        condition: &Condition,
+    ) -> TableResult<Option<Vec<usize>>> {
+        match Index::indexed_leaf(table, condition) {
+            Some((col, value)) => {
+                let content = fs::read_to_string(get_index_path(table, col))?;
+                let mut index: IndexEntries = serde_json::from_str(&content)?;
+                // Values like booleans are stored in one form but compared whatever their form
+                let value = match schema.col_type(col) {
+                    Some(dtype) if dtype.has_aliases() => dtype.normalize(value),
+                    _ => value.to_string(),
+                };
+                Ok(Some(index.remove(&value).unwrap_or_default()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn indexed_leaf<'c>(table: &Table, condition: &'c Condition) -> Option<(&'c str, &'c str)> {
+        match condition {
+            Condition::Leaf {
+                key,
+                operator: Operator::Eq,
+                value: ConditionValue::Single(value),
+            } if Index::exists(table, key) => Some((key, value)),
+            Condition::And(left, right) => {
+                Index::indexed_leaf(table, left).or_else(|| Index::indexed_leaf(table, right))
+            }
+            _ => None,
+        }
+    }
+
+    /// Write to a temporary file and move it in place, like the table rows.
+    fn write(table: &Table, col: &str, entries: &TableEntries) -> TableResult<()> {
+        let path = get_index_path(table, col);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(&Index::build(entries, col))?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use crate::{
+        query_parser::{Query, QueryParser, SelectCols, SelectOptions, TableQuery},
+        table::{
+            tests::{eq, row, set, users_table, TestDB},
+            Table, TableError,
+        },
+        utils::get_index_path,
+    };
+
+    use super::{Index, IndexEntries};
+
+    fn read_index(table: &Table, col: &str) -> IndexEntries {
+        serde_json::from_str(&fs::read_to_string(get_index_path(table, col)).unwrap()).unwrap()
+    }
+
+    fn names(table: &Table, condition: &str) -> Vec<String> {
+        let query = format!("SELECT * FROM users WHERE {}", condition);
+        let condition = match QueryParser::parse(&query).unwrap() {
+            Query::Table {
+                query: TableQuery::Select { condition, .. },
+                ..
+            } => condition,
+            _ => unreachable!(),
+        };
+        table
+            .select(SelectCols::All, condition, SelectOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry["name"].clone().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn index_follows_writes() {
+        let db = TestDB::new("test_index_writes");
+        let table = users_table(&db);
+        Index::create(&table, "age").unwrap();
+        assert_eq!(
+            read_index(&table, "age"),
+            HashMap::from([("20".to_string(), vec![0]), ("30".to_string(), vec![1, 2])])
+        );
+
+        table
+            .insert(SelectCols::All, vec![vec![Some("max".into()), None]])
+            .unwrap();
+        table.delete(eq("name", "jone")).unwrap();
+        table
+            .update(set(&[("age", "40")]), Some(eq("name", "doe")))
+            .unwrap();
+        assert_eq!(
+            read_index(&table, "age"),
+            HashMap::from([("30".to_string(), vec![0]), ("40".to_string(), vec![1])])
+        );
+
+        table.truncate(false).unwrap();
+        assert!(read_index(&table, "age").is_empty());
+    }
+
+    #[test]
+    fn select_uses_index() {
+        let db = TestDB::new("test_index_select");
+        let table = users_table(&db);
+        Index::create(&table, "age").unwrap();
+
+        assert_eq!(names(&table, "age = 30"), vec!["jane", "doe"]);
+        assert_eq!(names(&table, "age = 30 AND name = 'doe'"), vec!["doe"]);
+        assert_eq!(names(&table, "age = 31"), Vec::<String>::new());
+        assert_eq!(table.count(Some(eq("age", "30"))).unwrap(), 2);
+
+        // The index only narrows the rows down, they are still matched
+        let positions = HashMap::from([("30".to_string(), vec![0, 1, 2])]);
+        fs::write(
+            get_index_path(&table, "age"),
+            serde_json::to_string(&positions).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(names(&table, "age = 30"), vec!["jane", "doe"]);
+    }
+
+    #[test]
+    fn index_errors_and_cleanup() {
+        let db = TestDB::new("test_index_cleanup");
+        let table = users_table(&db);
+
+        assert!(matches!(
+            Index::create(&table, "email"),
+            Err(TableError::ColNotFound(_))
+        ));
+        assert!(matches!(
+            table.drop_index("age"),
+            Err(TableError::IndexNotFound(_))
+        ));
+        Index::create(&table, "age").unwrap();
+        assert!(matches!(
+            Index::create(&table, "age"),
+            Err(TableError::IndexAlreadyExists(_))
+        ));
+
+        table.rename_col("age", "years").unwrap();
+        assert!(!Index::exists(&table, "age"));
+        assert_eq!(read_index(&table, "years")["30"], vec![1, 2]);
+        table
+            .insert(SelectCols::All, vec![row(&["max", "30"])])
+            .unwrap();
+        assert_eq!(read_index(&table, "years")["30"], vec![1, 2, 3]);
+
+        table.remove_col("years").unwrap();
+        assert!(!Index::exists(&table, "years"));
+
+        Index::create(&table, "name").unwrap();
+        table.drop().unwrap();
+        assert!(!Index::exists(&table, "name"));
+    }
+}
@@ -0,0 +1,26 @@
+//! Simple relational database written in **Rust**.
+//!
+//! Every database is a directory inside [`database::DB_DIR`] and every table is stored as a
+//! `<table>.json` data file next to its `<table>.schema.json` schema.
+
+#![allow(
+    clippy::new_ret_no_self,
+    clippy::upper_case_acronyms,
+    clippy::enum_variant_names
+)]
+
+#[cfg(feature = "async")]
+pub mod async_table;
+pub mod database;
+pub mod index;
+pub mod lock;
+pub mod migrations;
+pub mod query_parser;
+pub mod query_planner;
+mod regex;
+pub mod scan;
+pub mod table;
+pub mod transaction;
+pub mod types;
+pub mod utils;
+pub mod views;
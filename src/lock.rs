@@ -0,0 +1,115 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use fs2::FileExt;
+use thiserror::Error;
+
+/// How long to sleep between two attempts while waiting for a lock with a timeout.
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Error, Debug)]
+pub enum LockError {
+    #[error("IO Error")]
+    IoError(#[from] io::Error),
+    #[error("Timed out waiting for the lock")]
+    Timeout,
+}
+
+/// An advisory lock on a file, released when dropped.
+///
+/// Only processes taking the lock are kept out, it doesn't prevent anyone from writing the
+/// locked file.
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    /// Lock `path` for writing, waiting at most `timeout` if given or until the lock is free.
+    pub fn exclusive(path: &Path, timeout: Option<Duration>) -> Result<Self, LockError> {
+        FileLock::acquire(path, timeout, false)
+    }
+
+    /// Lock `path` for reading, other readers can hold it at the same time.
+    pub fn shared(path: &Path, timeout: Option<Duration>) -> Result<Self, LockError> {
+        FileLock::acquire(path, timeout, true)
+    }
+
+    fn acquire(path: &Path, timeout: Option<Duration>, shared: bool) -> Result<Self, LockError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let Some(timeout) = timeout else {
+            match shared {
+                true => FileExt::lock_shared(&file)?,
+                false => FileExt::lock_exclusive(&file)?,
+            }
+            return Ok(Self { file });
+        };
+
+        let start = Instant::now();
+        loop {
+            let res = match shared {
+                true => FileExt::try_lock_shared(&file),
+                false => FileExt::try_lock_exclusive(&file),
+            };
+            match res {
+                Ok(()) => return Ok(Self { file }),
+                Err(e) if e.kind() != fs2::lock_contended_error().kind() => return Err(e.into()),
+                Err(_) if start.elapsed() >= timeout => return Err(LockError::Timeout),
+                Err(_) => thread::sleep(RETRY_INTERVAL),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::database::Database;
+    use crate::utils::get_db_path;
+
+    use super::{FileLock, LockError};
+
+    #[test]
+    fn exclusive_lock_blocks_others() {
+        let db = "test_file_lock";
+        let _ = Database::drop(db);
+        Database::new(db).unwrap();
+        let path = get_db_path(db).join("users.lock");
+        let timeout = Some(Duration::from_millis(50));
+
+        let first = FileLock::shared(&path, timeout).unwrap();
+        let second = FileLock::shared(&path, timeout).unwrap();
+        assert!(matches!(
+            FileLock::exclusive(&path, timeout),
+            Err(LockError::Timeout)
+        ));
+
+        drop((first, second));
+        let lock = FileLock::exclusive(&path, timeout).unwrap();
+        assert!(matches!(
+            FileLock::shared(&path, timeout),
+            Err(LockError::Timeout)
+        ));
+        drop(lock);
+        assert!(FileLock::shared(&path, timeout).is_ok());
+
+        Database::drop(db).unwrap();
+    }
+}
@@ -1,12 +1,4 @@
-mod database;
-mod query_parser;
-mod query_planner;
-mod regex;
-mod table;
-mod types;
-mod utils;
-
-use query_planner::{QueryPlanner, QueryPlannerError};
+use sql::query_planner::{QueryPlanner, QueryPlannerError};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -16,6 +8,9 @@ enum ErrorWrapper {
 }
 
 fn main() -> Result<(), ErrorWrapper> {
-    QueryPlanner::new()?;
+    match std::env::args().nth(1) {
+        Some(db) => QueryPlanner::run(&db)?,
+        None => QueryPlanner::new()?,
+    }
     Ok(())
 }
@@ -0,0 +1,239 @@
+use std::{collections::HashSet, fs, io};
+
+use thiserror::Error;
+
+use crate::{
+    table::{Table, TableError},
+    utils::get_version_path,
+};
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("Table Error")]
+    TableErr(#[from] TableError),
+    #[error("IO Error")]
+    IoErr(#[from] io::Error),
+    #[error("Invalid schema version")]
+    InvalidVersion(String),
+    #[error("Duplicated migration version")]
+    DuplicateVersion(u32),
+    #[error("No migration for the current version")]
+    MissingMigration(u32),
+    #[error("Migration {0} failed")]
+    Failed(u32, #[source] TableError),
+}
+
+type MigrationResult<T> = Result<T, MigrationError>;
+
+/// A schema change, `down` undoes what `up` does.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Migrations are applied by increasing version, 0 is the version before any migration
+    pub version: u32,
+    pub up: fn(&Table) -> Result<(), TableError>,
+    pub down: fn(&Table) -> Result<(), TableError>,
+}
+
+/// Applies [`Migration`]s to a table, keeping the version of the last one applied in
+/// `<table>.version`.
+///
+/// The version is saved after every migration, so a failing one leaves the table at the
+/// version of the migration before it.
+pub struct MigrationRunner<'a> {
+    table: Table<'a>,
+}
+
+impl<'a> MigrationRunner<'a> {
+    /// The table doesn't need to exist yet, the first migration can create it.
+    pub fn new(db: &'a str, table_name: &'a str) -> MigrationResult<Self> {
+        Ok(Self {
+            table: Table::new(db, table_name)?,
+        })
+    }
+
+    /// The version of the last migration applied, 0 if none was.
+    pub fn version(&self) -> MigrationResult<u32> {
+        let path = get_version_path(&self.table);
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let content = {
+            let _lock = self.table.lock(true)?;
+            fs::read_to_string(path)?
+        };
+        content
+            .trim()
+            .parse()
+            .map_err(|_| MigrationError::InvalidVersion(content))
+    }
+
+    /// Apply the migrations newer than the current version and return the new version.
+    pub fn run(&self, migrations: &[Migration]) -> MigrationResult<u32> {
+        let mut version = self.version()?;
+        for migration in MigrationRunner::sorted(migrations)? {
+            if migration.version <= version {
+                continue;
+            }
+            (migration.up)(&self.table)
+                .map_err(|e| MigrationError::Failed(migration.version, e))?;
+            version = migration.version;
+            self.write_version(version)?;
+        }
+
+        Ok(version)
+    }
+
+    /// Undo the migrations newer than `to_version`, newest first, and return the new version.
+    ///
+    /// Fails with [`MigrationError::MissingMigration`] before undoing anything if the current
+    /// version isn't one of `migrations`.
+    pub fn rollback(&self, migrations: &[Migration], to_version: u32) -> MigrationResult<u32> {
+        let sorted = MigrationRunner::sorted(migrations)?;
+        let mut version = self.version()?;
+        if version > to_version && !sorted.iter().any(|m| m.version == version) {
+            return Err(MigrationError::MissingMigration(version));
+        }
+
+        for (pos, migration) in sorted.iter().enumerate().rev() {
+            if migration.version <= to_version {
+                break;
+            }
+            if migration.version > version {
+                continue;
+            }
+            (migration.down)(&self.table)
+                .map_err(|e| MigrationError::Failed(migration.version, e))?;
+            version = match pos {
+                0 => 0,
+                pos => sorted[pos - 1].version,
+            };
+            self.write_version(version)?;
+        }
+
+        Ok(version)
+    }
+
+    fn sorted(migrations: &[Migration]) -> MigrationResult<Vec<Migration>> {
+        let mut seen = HashSet::new();
+        if let Some(migration) = migrations.iter().find(|m| !seen.insert(m.version)) {
+            return Err(MigrationError::DuplicateVersion(migration.version));
+        }
+
+        let mut sorted = migrations.to_vec();
+        sorted.sort_by_key(|m| m.version);
+        Ok(sorted)
+    }
+
+    /// Save the version to a temporary file then rename it, like the table sequence.
+    fn write_version(&self, version: u32) -> MigrationResult<()> {
+        let path = get_version_path(&self.table);
+        let tmp = path.with_extension("version.tmp");
+        let _lock = self.table.lock(false)?;
+        fs::write(&tmp, version.to_string())?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        query_parser::SelectCols,
+        table::{
+            tests::{row, TestDB},
+            ColOptions, Schema, Table, TableError,
+        },
+        types::DataType,
+        utils::get_version_path,
+    };
+
+    use super::{Migration, MigrationError, MigrationRunner};
+
+    fn create(table: &Table) -> Result<(), TableError> {
+        table.create(Schema::new(vec!["name".into()], vec![DataType::TEXT]))?;
+        table.insert(SelectCols::All, vec![row(&["jone"])])
+    }
+
+    fn add_age(table: &Table) -> Result<(), TableError> {
+        let options = ColOptions {
+            nullable: true,
+            ..Default::default()
+        };
+        table.add_col("age", DataType::INT, options)
+    }
+
+    fn remove_age(table: &Table) -> Result<(), TableError> {
+        table.remove_col("age")
+    }
+
+    fn fail(_: &Table) -> Result<(), TableError> {
+        Err(TableError::ColNotFound("email".into()))
+    }
+
+    fn migrations() -> Vec<Migration> {
+        vec![
+            Migration {
+                version: 5,
+                up: add_age,
+                down: remove_age,
+            },
+            Migration {
+                version: 1,
+                up: create,
+                down: |table| table.drop(),
+            },
+        ]
+    }
+
+    #[test]
+    fn run_and_rollback() {
+        let db = TestDB::new("test_migrations_run");
+        let runner = MigrationRunner::new(&db.0, "users").unwrap();
+        assert_eq!(runner.version().unwrap(), 0);
+
+        assert_eq!(runner.run(&migrations()).unwrap(), 5);
+        assert_eq!(runner.run(&migrations()).unwrap(), 5);
+        let table = Table::new(&db.0, "users").unwrap();
+        assert_eq!(table.read_schema().unwrap().cols, vec!["name", "age"]);
+
+        assert_eq!(runner.rollback(&migrations(), 3).unwrap(), 1);
+        assert_eq!(table.read_schema().unwrap().cols, vec!["name"]);
+        assert_eq!(runner.rollback(&migrations(), 0).unwrap(), 0);
+        assert!(matches!(
+            table.read_schema(),
+            Err(TableError::TableNotFond(_))
+        ));
+        assert_eq!(runner.run(&migrations()[1..]).unwrap(), 1);
+    }
+
+    #[test]
+    fn failed_migration_keeps_previous_version() {
+        let db = TestDB::new("test_migrations_failed");
+        let runner = MigrationRunner::new(&db.0, "users").unwrap();
+
+        let mut migrations = migrations();
+        migrations.push(Migration {
+            version: 3,
+            up: fail,
+            down: fail,
+        });
+        assert!(matches!(
+            runner.run(&migrations),
+            Err(MigrationError::Failed(3, TableError::ColNotFound(_)))
+        ));
+        assert_eq!(runner.version().unwrap(), 1);
+
+        migrations.push(migrations[0]);
+        assert!(matches!(
+            runner.run(&migrations),
+            Err(MigrationError::DuplicateVersion(5))
+        ));
+
+        std::fs::write(get_version_path(&Table::new(&db.0, "users").unwrap()), "7").unwrap();
+        assert!(matches!(
+            runner.rollback(&migrations[..2], 0),
+            Err(MigrationError::MissingMigration(7))
+        ));
+    }
+}
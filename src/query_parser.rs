@@ -1,10 +1,13 @@
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use thiserror::Error;
 
 use crate::{
     regex::*,
+    table::{Check, ForeignKey, UniqueConstraint},
     types::{DataType, DataTypesErr},
-    utils::{get_cols, get_comma_separated_values},
+    utils::get_comma_separated_values,
 };
 
 pub type ColName = String;
@@ -13,6 +16,7 @@ pub type ColName = String;
 pub enum DatabaseAction {
     Create,
     Drop,
+    DropIfExists,
     Use,
 }
 
@@ -21,8 +25,21 @@ pub enum TableQuery {
     Create {
         cols: Vec<String>,
         types: Vec<DataType>,
+        primary_key: Option<ColName>,
+        unique: Vec<ColName>,
+        not_null: Vec<ColName>,
+        /// Columns declared `ON UPDATE NOW`
+        auto_now: Vec<ColName>,
+        /// The `DEFAULT` value of each column
+        defaults: Vec<Option<String>>,
+        foreign_keys: Vec<ForeignKey>,
+        checks: Vec<Check>,
+        unique_constraints: Vec<UniqueConstraint>,
+        /// Keep an existing table with the same schema instead of failing
+        if_not_exists: bool,
     },
     DropTable,
+    DropTableIfExists,
     Truncate,
     AddCol {
         col_name: String,
@@ -32,26 +49,262 @@ pub enum TableQuery {
         col_name: String,
         datatype: DataType,
     },
+    RenameCol {
+        old_name: String,
+        new_name: String,
+    },
+    Rename(String),
     DropCol(ColName),
+    DropPrimaryKey,
+    /// `SHOW CREATE TABLE`
+    ShowCreate,
+    CreateIndex(ColName),
+    DropIndex(ColName),
     Select {
         cols: SelectCols,
         condition: Option<Condition>,
+        options: SelectOptions,
+    },
+    /// A `SELECT ... GROUP BY`, aggregates are `(result_col, func, source_col)` triples
+    SelectGrouped {
+        group_cols: Vec<ColName>,
+        aggregates: Vec<(ColName, AggFunc, ColName)>,
+        condition: Option<Condition>,
+        having: Option<Condition>,
     },
+    /// Unquoted `NULL` values are `None`
     Insert {
         cols: SelectCols,
-        values: Vec<Vec<String>>,
+        values: Vec<Vec<Option<String>>>,
     },
     Delete {
         condition: Condition,
     },
+    Update {
+        assignments: Vec<(ColName, Option<String>)>,
+        condition: Option<Condition>,
+    },
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum SelectCols {
     All,
-    Cols(Vec<String>),
+    Cols(Vec<SelectItem>),
+}
+
+impl SelectCols {
+    /// Parse a comma separated select list like `*` or `name AS n, CASE ... END AS bracket`.
+    pub fn parse(query: &str) -> Result<Self, QueryParserError> {
+        let query = query.trim();
+
+        if query == "*" {
+            return Ok(SelectCols::All);
+        }
+
+        let items = split_on_comma(query)
+            .into_iter()
+            .map(SelectItem::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(SelectCols::Cols(items))
+    }
+
+    /// The first alias shared with another selected column.
+    pub fn duplicate_alias(&self) -> Option<&str> {
+        let items = match self {
+            SelectCols::All => return None,
+            SelectCols::Cols(items) => items,
+        };
+
+        items
+            .iter()
+            .filter_map(|item| item.alias.as_deref())
+            .find(|alias| items.iter().filter(|i| i.name() == *alias).count() > 1)
+    }
+
+    /// The column shown under `name` in the output rows.
+    pub fn source_col<'a>(&'a self, name: &'a str) -> &'a str {
+        match self {
+            SelectCols::All => name,
+            SelectCols::Cols(items) => items
+                .iter()
+                .find(|item| item.alias.as_deref() == Some(name))
+                .and_then(|item| item.col())
+                .unwrap_or(name),
+        }
+    }
+}
+
+/// A selected column or computed value, renamed in the output rows when it has an alias.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SelectItem {
+    pub expr: SelectExpr,
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum SelectExpr {
+    Col(ColName),
+    Case(CaseExpr),
+}
+
+/// A `CASE WHEN <condition> THEN <value> ... ELSE <value> END` expression, the first arm with
+/// a matching condition gives the value and rows matching no arm get the `ELSE` value or `NULL`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct CaseExpr {
+    pub arms: Vec<(Condition, String)>,
+    pub default: Option<String>,
+}
+
+impl CaseExpr {
+    /// Parse the arms between `CASE` and `END` like `WHEN age >= 18 THEN 'adult' ELSE 'minor'`.
+    fn parse(arms: &str, default: Option<&str>) -> Result<Self, QueryParserError> {
+        let arms = split_on_keyword(arms, "when")
+            .into_iter()
+            .map(|arm| match split_on_keyword(arm, "then")[..] {
+                [condition, value] => Ok((Condition::parse(condition)?, unquote(value))),
+                _ => Err(QueryParserError::InvalidCase(arm.to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            arms,
+            default: default.map(unquote),
+        })
+    }
+}
+
+impl SelectItem {
+    pub fn new(col: &str, alias: Option<&str>) -> Self {
+        Self {
+            expr: SelectExpr::Col(col.to_string()),
+            alias: alias.map(|a| a.to_string()),
+        }
+    }
+
+    pub fn case(case: CaseExpr, alias: &str) -> Self {
+        Self {
+            expr: SelectExpr::Case(case),
+            alias: Some(alias.to_string()),
+        }
+    }
+
+    /// Parse a column like `name`, `name AS full_name` or `name full_name`, or a `CASE`
+    /// expression which needs an alias.
+    pub fn parse(query: &str) -> Result<Self, QueryParserError> {
+        let query = query.trim();
+
+        let re_case = Regex::new(RE_CASE).unwrap();
+        if let Some(caps) = re_case.captures(query) {
+            let alias = caps
+                .name("alias")
+                .ok_or_else(|| QueryParserError::MissingAlias(query.to_string()))?;
+            let case = CaseExpr::parse(&caps["arms"], caps.name("default").map(|d| d.as_str()))?;
+            return Ok(Self::case(case, alias.as_str()));
+        }
+
+        let re = Regex::new(RE_SELECT_ITEM).unwrap();
+        Ok(match re.captures(query) {
+            Some(caps) => Self::new(&caps["col"], caps.name("alias").map(|a| a.as_str())),
+            None => Self::new(query, None),
+        })
+    }
+
+    /// The selected column, `None` for a computed value.
+    pub fn col(&self) -> Option<&str> {
+        match &self.expr {
+            SelectExpr::Col(col) => Some(col),
+            SelectExpr::Case(_) => None,
+        }
+    }
+
+    /// The key of the column in the output rows.
+    pub fn name(&self) -> &str {
+        self.alias.as_deref().or(self.col()).unwrap_or_default()
+    }
+}
+
+impl From<&str> for SelectItem {
+    fn from(col: &str) -> Self {
+        Self::new(col, None)
+    }
+}
+
+impl From<String> for SelectItem {
+    fn from(col: String) -> Self {
+        Self {
+            expr: SelectExpr::Col(col),
+            alias: None,
+        }
+    }
+}
+
+/// Extra clauses applied to the rows matching a select condition.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SelectOptions {
+    /// Max number of rows to return
+    pub limit: Option<usize>,
+    /// Number of matching rows to skip before collecting
+    pub offset: Option<usize>,
+    /// Drop rows whose selected columns repeat an earlier row
+    pub distinct: bool,
+    /// Sort the rows by these columns, later clauses break ties of earlier ones
+    pub order: Option<Vec<OrderClause>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum JoinKind {
+    /// Only rows with a match on both sides
+    Inner,
+    /// Every row of the left table, unmatched ones get `NULL` right columns
+    Left,
+    /// Every row of the right table, unmatched ones get `NULL` left columns
+    Right,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+/// A single `ORDER BY` column.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OrderClause {
+    pub col: ColName,
+    pub direction: SortDirection,
+}
+
+impl OrderClause {
+    pub fn new(col: &str, direction: SortDirection) -> Self {
+        Self {
+            col: col.to_string(),
+            direction,
+        }
+    }
+
+    /// Parse a comma separated `ORDER BY` list like `age DESC, name`.
+    pub fn parse(query: &str) -> Result<Vec<Self>, QueryParserError> {
+        let re = Regex::new(RE_ORDER_CLAUSE).unwrap();
+
+        query
+            .split(',')
+            .map(|clause| {
+                let caps = re
+                    .captures(clause.trim())
+                    .ok_or_else(|| QueryParserError::InvalidOrder(clause.to_string()))?;
+                let direction = match caps.name("direction") {
+                    Some(d) if d.as_str().eq_ignore_ascii_case("desc") => SortDirection::Desc,
+                    _ => SortDirection::Asc,
+                };
+                Ok(OrderClause::new(&caps["col"], direction))
+            })
+            .collect()
+    }
 }
 
+// A parsed query is short lived, boxing the large table queries isn't worth it
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq, Eq)]
 pub enum Query {
     ShowAllDBs,
@@ -79,6 +332,18 @@ pub enum QueryParserError {
     InvalidCondition(String),
     #[error("Invalid Operator")]
     InvalidOperator(String),
+    #[error("Invalid order by clause")]
+    InvalidOrder(String),
+    #[error("Unbalanced parentheses at position {0}")]
+    UnbalancedParens(usize),
+    #[error("A subquery must select exactly one column")]
+    SubqueryCols(String),
+    #[error("Duplicate column alias `{0}`")]
+    DuplicateAlias(String),
+    #[error("Invalid CASE expression")]
+    InvalidCase(String),
+    #[error("A CASE expression needs an alias")]
+    MissingAlias(String),
     #[error("Data type error")]
     DataTypeErr(#[from] DataTypesErr),
 }
@@ -94,6 +359,10 @@ impl QueryParser {
                 "databases" => Ok(Query::ShowAllDBs),
                 "current database" => Ok(Query::ShowCurrDB),
                 "tables" => Ok(Query::ShowTables),
+                show if show.starts_with("create table ") => Ok(Query::Table {
+                    name: caps["query"]["create table ".len()..].trim().to_string(),
+                    query: TableQuery::ShowCreate,
+                }),
                 _ => Err(QueryParserError::BadQuery(query.to_string())),
             };
         }
@@ -103,10 +372,12 @@ impl QueryParser {
             let name = caps["name"].to_string();
             let action = &caps["action"];
 
-            let action = match action.to_lowercase().as_str() {
-                "create" => DatabaseAction::Create,
-                "drop" => DatabaseAction::Drop,
-                "use" => DatabaseAction::Use,
+            let if_exists = caps.name("if_exists").is_some();
+            let action = match (action.to_lowercase().as_str(), if_exists) {
+                ("create", false) => DatabaseAction::Create,
+                ("drop", false) => DatabaseAction::Drop,
+                ("drop", true) => DatabaseAction::DropIfExists,
+                ("use", false) => DatabaseAction::Use,
                 _ => return Err(QueryParserError::InvalidDBAction(action.to_string())),
             };
 
@@ -117,16 +388,90 @@ impl QueryParser {
         if let Some(caps) = re_create_table.captures(query) {
             let table_name = caps["name"].to_string();
             let re_entries = Regex::new(RE_TABLE_ENTRIES).unwrap();
+            let re_constraint = Regex::new(RE_COL_CONSTRAINT).unwrap();
             let mut types = Vec::new();
             let mut cols = Vec::new();
+            let mut primary_key = None;
+            let mut unique = Vec::new();
+            let mut not_null = Vec::new();
+            let mut auto_now = Vec::new();
+            let mut defaults = Vec::new();
+            let mut foreign_keys = Vec::new();
+            let mut checks = Vec::new();
+            let mut unique_constraints = Vec::new();
+            let re_unique = Regex::new(RE_UNIQUE_CONSTRAINT).unwrap();
+            let re_check = Regex::new(RE_CHECK_CONSTRAINT).unwrap();
             for caps in re_entries.captures_iter(&caps["entries"]) {
-                types.push(DataType::parse(&caps["col_type"])?);
-                cols.push(caps["col_name"].to_string())
+                let entry = format!("{} {}", &caps["col_name"], caps["col_type"].trim());
+                if let Some(unique) = re_unique.captures(&entry) {
+                    let cols = get_comma_separated_values(&unique["cols"]);
+                    let name = match unique.name("name") {
+                        Some(name) => name.as_str().to_string(),
+                        None => format!("{}_{}_key", table_name, cols.join("_")),
+                    };
+                    unique_constraints.push(UniqueConstraint { name, cols });
+                    continue;
+                }
+                if let Some(check) = re_check.captures(&entry) {
+                    let name = match check.name("name") {
+                        Some(name) => name.as_str().to_string(),
+                        None => format!("{}_check", table_name),
+                    };
+                    let condition = Condition::parse(&check["check"])?;
+                    checks.push(Check { name, condition });
+                    continue;
+                }
+
+                let col_name = caps["col_name"].to_string();
+                let mut col_type = caps["col_type"].trim();
+                let mut default = None;
+                while let Some(constraint) = re_constraint.captures(col_type) {
+                    let value = constraint.name("quoted").or(constraint.name("value"));
+                    if let Some(value) = value {
+                        default = Some(unquote(value.as_str()));
+                    } else if let Some(ref_table) = constraint.name("ref_table") {
+                        foreign_keys.push(ForeignKey {
+                            col: col_name.clone(),
+                            ref_table: ref_table.as_str().to_string(),
+                            ref_col: constraint["ref_col"].to_string(),
+                            cascade: constraint.name("cascade").is_some(),
+                        });
+                    } else if let Some(check) = constraint.name("check") {
+                        checks.push(Check {
+                            name: format!("{}_check", col_name),
+                            condition: Condition::parse(check.as_str())?,
+                        });
+                    } else if constraint["constraint"].eq_ignore_ascii_case("unique") {
+                        unique.push(col_name.clone());
+                    } else if constraint["constraint"].to_lowercase().starts_with("not") {
+                        not_null.push(col_name.clone());
+                    } else if constraint["constraint"].to_lowercase().starts_with("on") {
+                        auto_now.push(col_name.clone());
+                    } else {
+                        primary_key = Some(col_name.clone());
+                    }
+                    col_type = constraint.name("col_type").unwrap().as_str();
+                }
+                types.push(DataType::parse(col_type)?);
+                cols.push(col_name);
+                defaults.push(default);
             }
 
             return Ok(Query::Table {
                 name: table_name,
-                query: TableQuery::Create { cols, types },
+                query: TableQuery::Create {
+                    cols,
+                    types,
+                    primary_key,
+                    unique,
+                    not_null,
+                    auto_now,
+                    defaults,
+                    foreign_keys,
+                    checks,
+                    unique_constraints,
+                    if_not_exists: caps.name("if_not_exists").is_some(),
+                },
             });
         }
 
@@ -134,14 +479,21 @@ impl QueryParser {
 
         if let Some(caps) = re_table.captures(query) {
             let table_name = caps["name"].to_string();
-            match caps["action"].to_lowercase().as_str() {
-                "drop" => {
+            let if_exists = caps.name("if_exists").is_some();
+            match (caps["action"].to_lowercase().as_str(), if_exists) {
+                ("drop", false) => {
                     return Ok(Query::Table {
                         name: table_name,
                         query: TableQuery::DropTable,
                     })
                 }
-                "truncate" => {
+                ("drop", true) => {
+                    return Ok(Query::Table {
+                        name: table_name,
+                        query: TableQuery::DropTableIfExists,
+                    })
+                }
+                ("truncate", false) => {
                     return Ok(Query::Table {
                         name: table_name,
                         query: TableQuery::Truncate,
@@ -163,6 +515,26 @@ impl QueryParser {
             });
         }
 
+        let re_drop_pk = Regex::new(RE_DROP_PK).unwrap();
+        if let Some(caps) = re_drop_pk.captures(query) {
+            return Ok(Query::Table {
+                name: caps["table_name"].to_string(),
+                query: TableQuery::DropPrimaryKey,
+            });
+        }
+
+        let re_index = Regex::new(RE_INDEX).unwrap();
+        if let Some(caps) = re_index.captures(query) {
+            let col_name = caps["col_name"].to_string();
+            return Ok(Query::Table {
+                name: caps["table_name"].to_string(),
+                query: match caps["action"].to_lowercase().as_str() {
+                    "create" => TableQuery::CreateIndex(col_name),
+                    _ => TableQuery::DropIndex(col_name),
+                },
+            });
+        }
+
         let re_alter_col = Regex::new(RE_ALTER_COL).unwrap();
         if let Some(caps) = re_alter_col.captures(query) {
             return Ok(Query::Table {
@@ -174,6 +546,25 @@ impl QueryParser {
             });
         }
 
+        let re_rename_table = Regex::new(RE_RENAME_TABLE).unwrap();
+        if let Some(caps) = re_rename_table.captures(query) {
+            return Ok(Query::Table {
+                name: caps["table_name"].to_string(),
+                query: TableQuery::Rename(caps["new_name"].to_string()),
+            });
+        }
+
+        let re_rename_col = Regex::new(RE_RENAME_COL).unwrap();
+        if let Some(caps) = re_rename_col.captures(query) {
+            return Ok(Query::Table {
+                name: caps["table_name"].to_string(),
+                query: TableQuery::RenameCol {
+                    old_name: caps["old_name"].to_string(),
+                    new_name: caps["new_name"].to_string(),
+                },
+            });
+        }
+
         let re_add_col = Regex::new(RE_ADD_COL).unwrap();
         if let Some(caps) = re_add_col.captures(query) {
             return Ok(Query::Table {
@@ -185,9 +576,55 @@ impl QueryParser {
             });
         }
 
+        let re_grouped = Regex::new(RE_SELECT_GROUPED).unwrap();
+        if let Some(caps) = re_grouped.captures(query) {
+            let group_cols = get_comma_separated_values(&caps["group_cols"]);
+            let re_agg = Regex::new(RE_AGGREGATE).unwrap();
+
+            let mut aggregates = Vec::new();
+            for col in caps["cols"].split(',').map(|c| c.trim()) {
+                match re_agg.captures(col) {
+                    Some(agg) => {
+                        let func = AggFunc::parse(&agg["func"])?;
+                        let name = match agg.name("alias") {
+                            Some(alias) => alias.as_str().to_string(),
+                            None => func.alias(&agg["col"]),
+                        };
+                        aggregates.push((name, func, agg["col"].to_string()));
+                    }
+                    // Plain columns must be grouped to have a single value per group
+                    None if group_cols.iter().any(|g| g == col) => {}
+                    None => return Err(QueryParserError::InvalidTableAction(col.to_string())),
+                }
+            }
+
+            let condition = match caps.name("condition") {
+                None => None,
+                Some(c) => Some(Condition::parse(c.as_str())?),
+            };
+            let having = match caps.name("having") {
+                None => None,
+                Some(h) => Some(Condition::parse(h.as_str())?),
+            };
+
+            return Ok(Query::Table {
+                name: caps["table_name"].to_string(),
+                query: TableQuery::SelectGrouped {
+                    group_cols,
+                    aggregates,
+                    condition,
+                    having,
+                },
+            });
+        }
+
         let re_select = Regex::new(RE_SELECT).unwrap();
         if let Some(caps) = re_select.captures(query) {
             let condition = caps.name("condition").map(|_| &caps["condition"]);
+            let cols = SelectCols::parse(&caps["cols"])?;
+            if let Some(alias) = cols.duplicate_alias() {
+                return Err(QueryParserError::DuplicateAlias(alias.to_string()));
+            }
 
             return Ok(Query::Table {
                 name: caps["table_name"].to_string(),
@@ -196,7 +633,22 @@ impl QueryParser {
                         None => None,
                         Some(c) => Some(Condition::parse(c)?),
                     },
-                    cols: get_cols(&caps["cols"]),
+                    cols,
+                    options: SelectOptions {
+                        limit: caps
+                            .name("limit")
+                            .map(|l| parse_count(l.as_str()))
+                            .transpose()?,
+                        offset: caps
+                            .name("offset")
+                            .map(|o| parse_count(o.as_str()))
+                            .transpose()?,
+                        distinct: caps.name("distinct").is_some(),
+                        order: match caps.name("order") {
+                            None => None,
+                            Some(o) => Some(OrderClause::parse(o.as_str())?),
+                        },
+                    },
                 },
             });
         }
@@ -204,14 +656,27 @@ impl QueryParser {
         let re_insert = Regex::new(RE_INSERT).unwrap();
         if let Some(caps) = re_insert.captures(query) {
             let cols = match caps.name("cols") {
-                Some(_) => SelectCols::Cols(get_comma_separated_values(&caps["cols"])),
+                Some(_) => SelectCols::Cols(
+                    get_comma_separated_values(&caps["cols"])
+                        .into_iter()
+                        .map(SelectItem::from)
+                        .collect(),
+                ),
                 None => SelectCols::All,
             };
 
             let re_values = Regex::new(RE_INSERT_VALUES_VALUES).unwrap();
             let values = re_values
                 .captures_iter(&caps["values"])
-                .map(|caps| get_comma_separated_values(&caps["row"]))
+                .map(|caps| {
+                    get_comma_separated_values(&caps["row"])
+                        .into_iter()
+                        .map(|value| match value.eq_ignore_ascii_case("null") {
+                            true => None,
+                            false => Some(value),
+                        })
+                        .collect()
+                })
                 .collect::<Vec<Vec<_>>>();
 
             return Ok(Query::Table {
@@ -220,6 +685,40 @@ impl QueryParser {
             });
         }
 
+        let re_update = Regex::new(RE_UPDATE).unwrap();
+        if let Some(caps) = re_update.captures(query) {
+            let re_null = Regex::new(RE_NULL_ASSIGNMENT).unwrap();
+            let mut assignments = Vec::new();
+            for assignment in caps["assignments"].split(',') {
+                if let Some(null) = re_null.captures(assignment.trim()) {
+                    assignments.push((null["key"].to_string(), None));
+                    continue;
+                }
+
+                match Condition::parse_leaf(assignment)? {
+                    Condition::Leaf {
+                        key,
+                        value: ConditionValue::Single(value),
+                        operator: Operator::Eq,
+                    } => assignments.push((key, Some(value))),
+                    _ => return Err(QueryParserError::BadQuery(assignment.trim().to_string())),
+                }
+            }
+
+            let condition = caps.name("condition").map(|_| &caps["condition"]);
+
+            return Ok(Query::Table {
+                name: caps["table_name"].to_string(),
+                query: TableQuery::Update {
+                    assignments,
+                    condition: match condition {
+                        None => None,
+                        Some(c) => Some(Condition::parse(c)?),
+                    },
+                },
+            });
+        }
+
         let re_delete = Regex::new(RE_DELETE_FROM_TABLE).unwrap();
         if let Some(caps) = re_delete.captures(query) {
             let condition = Condition::parse(&caps["condition"])?;
@@ -233,7 +732,7 @@ impl QueryParser {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum Operator {
     Eq,
     NotEq,
@@ -241,128 +740,871 @@ pub enum Operator {
     Lt,
     GtEq,
     LtEq,
+    Like,
+    NotLike,
+    /// `LIKE` ignoring the case
+    ILike,
+    NotILike,
+    /// `=` ignoring the case, written `a = 'b' COLLATE NOCASE`
+    IEq,
+    INotEq,
+    /// Matches the value as a regular expression
+    Regexp,
+    NotRegexp,
+    /// `=` on the value at a path of a JSON column, written `json_extract(col, '$.a.b') = 'x'`
+    JsonPathEq(String),
+    In,
+    NotIn,
+    Between,
+    NotBetween,
+    IsNull,
+    IsNotNull,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct Condition {
-    pub key: String,
-    pub value: String,
-    pub operator: Operator,
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operator = match self {
+            Operator::Eq => "=",
+            Operator::NotEq => "!=",
+            Operator::Gt => ">",
+            Operator::Lt => "<",
+            Operator::GtEq => ">=",
+            Operator::LtEq => "<=",
+            Operator::Like => "LIKE",
+            Operator::NotLike => "NOT LIKE",
+            Operator::ILike => "ILIKE",
+            Operator::NotILike => "NOT ILIKE",
+            // The `COLLATE NOCASE` follows the value
+            Operator::IEq => "=",
+            Operator::INotEq => "!=",
+            Operator::Regexp => "REGEXP",
+            Operator::NotRegexp => "NOT REGEXP",
+            // The path is written around the key
+            Operator::JsonPathEq(_) => "=",
+            Operator::In => "IN",
+            Operator::NotIn => "NOT IN",
+            Operator::Between => "BETWEEN",
+            Operator::NotBetween => "NOT BETWEEN",
+            Operator::IsNull => "IS NULL",
+            Operator::IsNotNull => "IS NOT NULL",
+        };
+        f.write_str(operator)
+    }
 }
 
-impl Condition {
-    fn parse(query: &str) -> Result<Condition, QueryParserError> {
-        let re = Regex::new(RE_KEY_VALUE).unwrap();
-
-        match re.captures(query) {
-            Some(caps) => {
-                let operator = match &caps["operator"] {
-                    "=" => Operator::Eq,
-                    "!=" => Operator::NotEq,
-                    ">" => Operator::Gt,
-                    ">=" => Operator::GtEq,
-                    "<" => Operator::Lt,
-                    "<=" => Operator::LtEq,
-                    _ => {
-                        return Err(QueryParserError::InvalidOperator(
-                            caps["operator"].to_string(),
-                        ))
-                    }
-                };
+/// An aggregate function computed over a group of rows.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AggFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
 
-                Ok(Condition {
-                    key: caps["key"].to_string(),
-                    value: caps["value"].to_string(),
-                    operator,
-                })
-            }
-            None => Err(QueryParserError::InvalidCondition(query.to_string())),
+impl AggFunc {
+    pub fn parse(func: &str) -> Result<Self, QueryParserError> {
+        match func.to_lowercase().as_str() {
+            "count" => Ok(AggFunc::Count),
+            "sum" => Ok(AggFunc::Sum),
+            "avg" => Ok(AggFunc::Avg),
+            "min" => Ok(AggFunc::Min),
+            "max" => Ok(AggFunc::Max),
+            _ => Err(QueryParserError::InvalidTableAction(func.to_string())),
         }
     }
+
+    /// The name of the output column holding this aggregate, e.g. `COUNT(id)`.
+    pub fn alias(&self, col: &str) -> String {
+        format!("{}({})", format!("{:?}", self).to_uppercase(), col)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        query_parser::{Condition, DatabaseAction, Operator, Query, SelectCols, TableQuery},
-        types::DataType,
-    };
+/// The right hand side of a condition.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum ConditionValue {
+    Single(String),
+    /// A list of values used by `IN` and `NOT IN`
+    Set(Vec<String>),
+    /// The inclusive `(low, high)` bounds used by `BETWEEN` and `NOT BETWEEN`
+    Range(String, String),
+    /// No operand, used by `IS NULL` and `IS NOT NULL`
+    Empty,
+    /// The values of a single column select used by `IN (SELECT ...)`, it's replaced by the
+    /// resulting [`ConditionValue::Set`] before any row is matched
+    Subquery {
+        table: String,
+        col: ColName,
+        condition: Option<Box<Condition>>,
+    },
+    /// Another column of the same row, from an unquoted value like `end_date > start_date`.
+    /// It's compared as a literal when the table has no such column, so `name = jone` still
+    /// matches the text `jone`
+    Column(ColName),
+}
 
-    use super::{QueryParser, QueryParserError};
+impl From<&str> for ConditionValue {
+    fn from(value: &str) -> Self {
+        ConditionValue::Single(value.to_string())
+    }
+}
 
-    #[test]
-    fn create_database() {
-        let all_caps = QueryParser::parse("CREATE DATABASE demo").unwrap();
-        let all_lowercase = QueryParser::parse("create database demo").unwrap();
+impl From<String> for ConditionValue {
+    fn from(value: String) -> Self {
+        ConditionValue::Single(value)
+    }
+}
 
-        assert_eq!(
-            all_caps,
-            Query::Database {
-                name: "demo".to_string(),
-                action: DatabaseAction::Create
+impl From<Vec<String>> for ConditionValue {
+    fn from(values: Vec<String>) -> Self {
+        ConditionValue::Set(values)
+    }
+}
+
+/// A `WHERE` condition.
+///
+/// Compound conditions are stored as a tree where `NOT` binds tighter than `AND` and `AND`
+/// tighter than `OR`, so `a = 1 OR NOT b = 2 AND c = 3` is parsed as
+/// `a = 1 OR ((NOT b = 2) AND c = 3)`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum Condition {
+    Leaf {
+        key: String,
+        value: ConditionValue,
+        operator: Operator,
+    },
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    pub fn leaf<K: Into<String>, V: Into<ConditionValue>>(
+        key: K,
+        operator: Operator,
+        value: V,
+    ) -> Self {
+        Condition::Leaf {
+            key: key.into(),
+            value: value.into(),
+            operator,
+        }
+    }
+
+    /// `left AND right`
+    pub fn and(left: Condition, right: Condition) -> Self {
+        Condition::And(Box::new(left), Box::new(right))
+    }
+
+    /// `left OR right`
+    pub fn or(left: Condition, right: Condition) -> Self {
+        Condition::Or(Box::new(left), Box::new(right))
+    }
+
+    /// `NOT condition`
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(condition: Condition) -> Self {
+        Condition::Not(Box::new(condition))
+    }
+
+    /// All the columns referenced by this condition.
+    pub fn keys(&self) -> Vec<&String> {
+        match self {
+            Condition::Leaf { key, .. } => vec![key],
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                let mut keys = left.keys();
+                keys.extend(right.keys());
+                keys
             }
-        );
+            Condition::Not(inner) => inner.keys(),
+        }
+    }
 
-        assert_eq!(
-            all_lowercase,
-            Query::Database {
-                name: "demo".to_string(),
-                action: DatabaseAction::Create
+    /// Replace the column `old` by `new` in the keys and column values.
+    pub fn rename_col(&mut self, old: &str, new: &str) {
+        match self {
+            Condition::Leaf { key, value, .. } => {
+                if key == old {
+                    *key = new.to_string();
+                }
+                if let ConditionValue::Column(col) = value {
+                    if col == old {
+                        *col = new.to_string();
+                    }
+                }
             }
-        );
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                left.rename_col(old, new);
+                right.rename_col(old, new);
+            }
+            Condition::Not(inner) => inner.rename_col(old, new),
+        }
     }
 
-    #[test]
-    fn drop_database() {
-        let query = QueryParser::parse("DROP DATABASE demo").unwrap();
+    /// The condition as SQL, wrapped in parentheses when `group` is set.
+    fn grouped(&self, group: bool) -> String {
+        match group {
+            true => format!("({})", self),
+            false => self.to_string(),
+        }
+    }
 
-        assert_eq!(
-            query,
-            Query::Database {
-                name: "demo".to_string(),
-                action: DatabaseAction::Drop
-            }
-        );
+    fn parse(query: &str) -> Result<Condition, QueryParserError> {
+        check_parens(query)?;
+        Condition::parse_or(query)
     }
 
-    #[test]
-    fn use_database() {
-        let query = QueryParser::parse("USE DATABASE demo").unwrap();
+    fn parse_or(query: &str) -> Result<Condition, QueryParserError> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Err(QueryParserError::InvalidCondition(query.to_string()));
+        }
 
-        assert_eq!(
-            query,
-            Query::Database {
-                name: "demo".to_string(),
-                action: DatabaseAction::Use
+        let mut or_parts = split_on_keyword(query, "or").into_iter();
+        let mut condition = Condition::parse_and(or_parts.next().unwrap())?;
+        for part in or_parts {
+            condition = Condition::or(condition, Condition::parse_and(part)?);
+        }
+
+        Ok(condition)
+    }
+
+    fn parse_and(query: &str) -> Result<Condition, QueryParserError> {
+        let re_between = Regex::new(RE_BETWEEN_LOW).unwrap();
+        let mut leaves: Vec<String> = Vec::new();
+        for part in split_on_keyword(query, "and") {
+            match leaves.last_mut() {
+                // The `AND` of `BETWEEN <low> AND <high>` belongs to the range itself
+                Some(leaf) if re_between.is_match(leaf) => {
+                    leaf.push_str(" AND ");
+                    leaf.push_str(part);
+                }
+                _ => leaves.push(part.to_string()),
             }
-        );
+        }
+
+        let mut leaves = leaves.iter();
+        let mut condition = Condition::parse_not(leaves.next().unwrap())?;
+        for leaf in leaves {
+            condition = Condition::and(condition, Condition::parse_not(leaf)?);
+        }
+
+        Ok(condition)
     }
 
-    #[test]
-    fn create_table_one_line_query() {
-        let query = QueryParser::parse("CREATE TABLE user(id int, name varchar, age int)").unwrap();
-        if let Query::Table {
-            name,
-            query: TableQuery::Create { cols, types },
-        } = query
-        {
-            assert_eq!(name, "user".to_string());
-            assert_eq!(
-                cols,
-                vec!["id".to_string(), "name".to_string(), "age".to_string()]
-            );
-            assert_eq!(
-                types,
-                vec![DataType::INT, DataType::VARCHAR(255), DataType::INT]
-            );
-        } else {
-            panic!("Unexpected query");
+    fn parse_not(query: &str) -> Result<Condition, QueryParserError> {
+        let query = query.trim();
+        if let Some(inner) = strip_group(query) {
+            return Condition::parse_or(inner);
+        }
+
+        let re_not = Regex::new(RE_NOT_CONDITION).unwrap();
+        match re_not.captures(query) {
+            Some(caps) => Ok(Condition::not(Condition::parse_not(&caps["condition"])?)),
+            None => Condition::parse_leaf(query),
         }
     }
 
-    #[test]
-    fn create_table_multi_line_query() {
-        let query = QueryParser::parse(
+    fn parse_leaf(query: &str) -> Result<Condition, QueryParserError> {
+        let query = query.trim();
+
+        let re_json = Regex::new(RE_JSON_CONDITION).unwrap();
+        if let Some(caps) = re_json.captures(query) {
+            let value = caps.name("quoted").or(caps.name("value")).unwrap();
+            return Ok(Condition::leaf(
+                &caps["key"],
+                Operator::JsonPathEq(caps["path"].to_string()),
+                unquote(value.as_str()),
+            ));
+        }
+
+        let re_null = Regex::new(RE_NULL_CONDITION).unwrap();
+        if let Some(caps) = re_null.captures(query) {
+            let operator = match caps.name("not") {
+                Some(_) => Operator::IsNotNull,
+                None => Operator::IsNull,
+            };
+
+            return Ok(Condition::leaf(
+                &caps["key"],
+                operator,
+                ConditionValue::Empty,
+            ));
+        }
+
+        let re_subquery = Regex::new(RE_IN_SUBQUERY).unwrap();
+        if let Some(caps) = re_subquery.captures(query) {
+            let operator = match caps["operator"].to_lowercase().starts_with("not") {
+                true => Operator::NotIn,
+                false => Operator::In,
+            };
+            let col = match SelectCols::parse(&caps["cols"])? {
+                SelectCols::Cols(cols) if cols.len() == 1 && cols[0].col().is_some() => {
+                    cols[0].col().unwrap().to_string()
+                }
+                _ => return Err(QueryParserError::SubqueryCols(caps["cols"].to_string())),
+            };
+            let condition = match caps.name("condition") {
+                None => None,
+                Some(c) => Some(Box::new(Condition::parse_or(c.as_str())?)),
+            };
+
+            return Ok(Condition::leaf(
+                &caps["key"],
+                operator,
+                ConditionValue::Subquery {
+                    table: caps["table_name"].to_string(),
+                    col,
+                    condition,
+                },
+            ));
+        }
+
+        let re_in = Regex::new(RE_IN_CONDITION).unwrap();
+        if let Some(caps) = re_in.captures(query) {
+            let operator = match caps["operator"].to_lowercase().starts_with("not") {
+                true => Operator::NotIn,
+                false => Operator::In,
+            };
+
+            let values = split_on_comma(&caps["values"])
+                .into_iter()
+                .map(unquote)
+                .filter(|v| !v.is_empty())
+                .collect::<Vec<_>>();
+            return Ok(Condition::leaf(&caps["key"], operator, values));
+        }
+
+        let re_between = Regex::new(RE_BETWEEN_CONDITION).unwrap();
+        if let Some(caps) = re_between.captures(query) {
+            let operator = match caps["operator"].to_lowercase().starts_with("not") {
+                true => Operator::NotBetween,
+                false => Operator::Between,
+            };
+
+            return Ok(Condition::leaf(
+                &caps["key"],
+                operator,
+                ConditionValue::Range(unquote(&caps["low"]), unquote(&caps["high"])),
+            ));
+        }
+
+        let re = Regex::new(RE_KEY_VALUE).unwrap();
+
+        match re.captures(query) {
+            Some(caps) => {
+                let operator = caps["operator"].to_lowercase();
+                let operator = match operator.split_whitespace().collect::<Vec<_>>()[..] {
+                    ["="] => Operator::Eq,
+                    ["!="] => Operator::NotEq,
+                    [">"] => Operator::Gt,
+                    [">="] => Operator::GtEq,
+                    ["<"] => Operator::Lt,
+                    ["<="] => Operator::LtEq,
+                    ["like"] => Operator::Like,
+                    ["not", "like"] => Operator::NotLike,
+                    ["ilike"] => Operator::ILike,
+                    ["not", "ilike"] => Operator::NotILike,
+                    ["regexp"] => Operator::Regexp,
+                    ["not", "regexp"] => Operator::NotRegexp,
+                    _ => {
+                        return Err(QueryParserError::InvalidOperator(
+                            caps["operator"].to_string(),
+                        ))
+                    }
+                };
+                let operator = match (operator, caps.name("nocase")) {
+                    (operator, None) => operator,
+                    (Operator::Eq, Some(_)) => Operator::IEq,
+                    (Operator::NotEq, Some(_)) => Operator::INotEq,
+                    (_, Some(nocase)) => {
+                        return Err(QueryParserError::InvalidOperator(
+                            nocase.as_str().trim().to_string(),
+                        ))
+                    }
+                };
+
+                let value = match (caps.name("quoted"), caps.name("value")) {
+                    (Some(quoted), _) => ConditionValue::from(unquote(quoted.as_str())),
+                    (None, Some(value)) if is_col_ref(value.as_str()) => {
+                        ConditionValue::Column(value.as_str().to_string())
+                    }
+                    (None, value) => ConditionValue::from(value.unwrap().as_str()),
+                };
+                Ok(Condition::leaf(&caps["key"], operator, value))
+            }
+            None => Err(QueryParserError::InvalidCondition(query.to_string())),
+        }
+    }
+}
+
+/// Renders SQL that parses back to the same condition, only the parentheses needed to keep its
+/// shape are added.
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Leaf {
+                key,
+                value,
+                operator,
+            } => {
+                let nocase = match operator {
+                    Operator::IEq | Operator::INotEq => " COLLATE NOCASE",
+                    _ => "",
+                };
+                let key = match operator {
+                    Operator::JsonPathEq(path) => format!("json_extract({}, '{}')", key, path),
+                    _ => key.to_string(),
+                };
+                match value {
+                    ConditionValue::Single(value) => {
+                        write!(f, "{} {} {}{}", key, operator, quote(value), nocase)
+                    }
+                    ConditionValue::Column(col) => {
+                        write!(f, "{} {} {}{}", key, operator, col, nocase)
+                    }
+                    ConditionValue::Set(values) => {
+                        let values = values.iter().map(|v| quote(v)).collect::<Vec<_>>();
+                        write!(f, "{} {} ({})", key, operator, values.join(", "))
+                    }
+                    ConditionValue::Range(low, high) => {
+                        write!(f, "{} {} {} AND {}", key, operator, quote(low), quote(high))
+                    }
+                    ConditionValue::Empty => write!(f, "{} {}", key, operator),
+                    ConditionValue::Subquery {
+                        table,
+                        col,
+                        condition,
+                    } => {
+                        write!(f, "{} {} (SELECT {} FROM {}", key, operator, col, table)?;
+                        if let Some(condition) = condition {
+                            write!(f, " WHERE {}", condition)?;
+                        }
+                        f.write_str(")")
+                    }
+                }
+            }
+            Condition::Or(left, right) => {
+                let right = right.grouped(matches!(**right, Condition::Or(..)));
+                write!(f, "{} OR {}", left, right)
+            }
+            Condition::And(left, right) => {
+                let left = left.grouped(matches!(**left, Condition::Or(..)));
+                let right =
+                    right.grouped(matches!(**right, Condition::Or(..) | Condition::And(..)));
+                write!(f, "{} AND {}", left, right)
+            }
+            Condition::Not(inner) => {
+                let inner =
+                    inner.grouped(matches!(**inner, Condition::Or(..) | Condition::And(..)));
+                write!(f, "NOT {}", inner)
+            }
+        }
+    }
+}
+
+/// Quote a value so it's parsed back as a literal, numbers are left as they are.
+fn quote(value: &str) -> String {
+    match value.parse::<f64>().is_ok() && !value.contains(char::is_alphabetic) {
+        true => value.to_string(),
+        false => format!("'{}'", value.replace('\'', "''")),
+    }
+}
+
+/// Parse a `LIMIT` or `OFFSET` count, failing rather than panicking if it's too big for a
+/// `usize`.
+fn parse_count(count: &str) -> Result<usize, QueryParserError> {
+    count
+        .parse()
+        .map_err(|_| QueryParserError::BadQuery(count.to_string()))
+}
+
+/// Split the query on commas outside quoted values and parentheses.
+fn split_on_comma(query: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    for (idx, _) in query.match_indices(',') {
+        let (quoted, depth) = scan_nesting(&query[..idx]);
+        if quoted || depth > 0 {
+            continue;
+        }
+        parts.push(&query[start..idx]);
+        start = idx + 1;
+    }
+    parts.push(&query[start..]);
+
+    parts
+}
+
+/// Trim a value and strip its surrounding quotes if any, `''` inside single quotes stands for
+/// a literal `'`.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return inner.replace("''", "'");
+    }
+    if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return inner.to_string();
+    }
+    value.to_string()
+}
+
+/// Whether an unquoted value names a column rather than a literal like `12` or `true`.
+fn is_col_ref(value: &str) -> bool {
+    let re = Regex::new(RE_COL_REF).unwrap();
+    re.is_match(value)
+        && !["true", "false", "null"]
+            .iter()
+            .any(|keyword| value.eq_ignore_ascii_case(keyword))
+}
+
+/// Split the query on a keyword like `AND`, ignoring any keyword inside a quoted value or
+/// parentheses.
+fn split_on_keyword<'q>(query: &'q str, keyword: &str) -> Vec<&'q str> {
+    let re = Regex::new(&format!(r"(?i)\s+{}\s+", keyword)).unwrap();
+    let mut parts = Vec::new();
+    let mut start = 0;
+
+    for m in re.find_iter(query) {
+        let (quoted, depth) = scan_nesting(&query[..m.start()]);
+        if quoted || depth > 0 {
+            continue;
+        }
+
+        parts.push(&query[start..m.start()]);
+        start = m.end();
+    }
+
+    parts.push(&query[start..]);
+    parts
+}
+
+/// Whether the end of the text is inside a quote, and how many parentheses are still open.
+fn scan_nesting(text: &str) -> (bool, usize) {
+    let mut quote = None;
+    let mut depth = 0usize;
+    for c in text.chars() {
+        match quote {
+            Some(q) if q == c => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '(' => depth += 1,
+            None if c == ')' => depth = depth.saturating_sub(1),
+            None => {}
+        }
+    }
+
+    (quote.is_some(), depth)
+}
+
+/// Check that every parenthesis outside a quoted value is closed, the error holds the
+/// (character) position of the first unmatched one.
+fn check_parens(text: &str) -> Result<(), QueryParserError> {
+    let mut quote = None;
+    let mut open = Vec::new();
+    for (pos, c) in text.chars().enumerate() {
+        match quote {
+            Some(q) if q == c => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '(' => open.push(pos),
+            None if c == ')' => {
+                open.pop().ok_or(QueryParserError::UnbalancedParens(pos))?;
+            }
+            None => {}
+        }
+    }
+
+    match open.first() {
+        Some(&pos) => Err(QueryParserError::UnbalancedParens(pos)),
+        None => Ok(()),
+    }
+}
+
+/// The text inside the parentheses if they wrap the whole query, like `(a = 1 OR b = 2)`.
+fn strip_group(query: &str) -> Option<&str> {
+    let inner = query.strip_prefix('(')?.strip_suffix(')')?;
+    // `(a = 1) AND (b = 2)` starts and ends with parentheses which aren't a pair
+    let (quoted, _) = scan_nesting(inner);
+    let closes_early = inner
+        .char_indices()
+        .filter(|&(_, c)| c == ')')
+        .any(|(idx, _)| {
+            let (quoted, depth) = scan_nesting(&inner[..idx]);
+            !quoted && depth == 0
+        });
+
+    match quoted || closes_early {
+        true => None,
+        false => Some(inner),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        query_parser::{
+            AggFunc, CaseExpr, Condition, ConditionValue, DatabaseAction, Operator, OrderClause,
+            Query, SelectCols, SelectItem, SelectOptions, SortDirection, TableQuery,
+        },
+        table::{Check, ForeignKey, UniqueConstraint},
+        types::{DataType, BLOB_SIZE},
+    };
+
+    use super::{QueryParser, QueryParserError};
+
+    #[test]
+    fn create_database() {
+        let all_caps = QueryParser::parse("CREATE DATABASE demo").unwrap();
+        let all_lowercase = QueryParser::parse("create database demo").unwrap();
+
+        assert_eq!(
+            all_caps,
+            Query::Database {
+                name: "demo".to_string(),
+                action: DatabaseAction::Create
+            }
+        );
+
+        assert_eq!(
+            all_lowercase,
+            Query::Database {
+                name: "demo".to_string(),
+                action: DatabaseAction::Create
+            }
+        );
+    }
+
+    #[test]
+    fn drop_database() {
+        let query = QueryParser::parse("DROP DATABASE demo").unwrap();
+
+        assert_eq!(
+            query,
+            Query::Database {
+                name: "demo".to_string(),
+                action: DatabaseAction::Drop
+            }
+        );
+    }
+
+    #[test]
+    fn drop_database_if_exists() {
+        let query = QueryParser::parse("DROP DATABASE IF EXISTS demo").unwrap();
+
+        assert_eq!(
+            query,
+            Query::Database {
+                name: "demo".to_string(),
+                action: DatabaseAction::DropIfExists
+            }
+        );
+        let res = QueryParser::parse("USE DATABASE IF EXISTS demo");
+        assert!(matches!(res, Err(QueryParserError::InvalidDBAction(_))));
+    }
+
+    #[test]
+    fn use_database() {
+        let query = QueryParser::parse("USE DATABASE demo").unwrap();
+
+        assert_eq!(
+            query,
+            Query::Database {
+                name: "demo".to_string(),
+                action: DatabaseAction::Use
+            }
+        );
+    }
+
+    #[test]
+    fn create_table_one_line_query() {
+        let query = QueryParser::parse("CREATE TABLE user(id int, name varchar, age int)").unwrap();
+        if let Query::Table {
+            name,
+            query: TableQuery::Create { cols, types, .. },
+        } = query
+        {
+            assert_eq!(name, "user".to_string());
+            assert_eq!(
+                cols,
+                vec!["id".to_string(), "name".to_string(), "age".to_string()]
+            );
+            assert_eq!(
+                types,
+                vec![DataType::INT, DataType::VARCHAR(255), DataType::INT]
+            );
+        } else {
+            panic!("Unexpected query");
+        }
+    }
+
+    #[test]
+    fn create_table_if_not_exists() {
+        let query = QueryParser::parse("CREATE TABLE IF NOT EXISTS user(id int)").unwrap();
+        if let Query::Table {
+            name,
+            query:
+                TableQuery::Create {
+                    cols,
+                    if_not_exists,
+                    ..
+                },
+        } = query
+        {
+            assert_eq!(name, "user");
+            assert_eq!(cols, vec!["id"]);
+            assert!(if_not_exists);
+        } else {
+            panic!("Unexpected query");
+        }
+    }
+
+    #[test]
+    fn create_table_with_primary_key() {
+        let query = QueryParser::parse(
+            "CREATE TABLE user(name varchar(20) primary key, role enum('a', 'b') default 'b', email text UNIQUE, team int REFERENCES team(id) ON DELETE CASCADE)",
+        )
+        .unwrap();
+        if let Query::Table {
+            query:
+                TableQuery::Create {
+                    cols,
+                    types,
+                    primary_key,
+                    unique,
+                    defaults,
+                    foreign_keys,
+                    ..
+                },
+            ..
+        } = query
+        {
+            assert_eq!(cols, vec!["name", "role", "email", "team"]);
+            assert_eq!(
+                types,
+                vec![
+                    DataType::VARCHAR(20),
+                    DataType::ENUM(vec!["a".to_string(), "b".to_string()]),
+                    DataType::TEXT,
+                    DataType::INT
+                ]
+            );
+            assert_eq!(primary_key, Some("name".to_string()));
+            assert_eq!(unique, vec!["email"]);
+            assert_eq!(defaults, vec![None, Some("b".to_string()), None, None]);
+            assert_eq!(
+                foreign_keys,
+                vec![ForeignKey {
+                    col: "team".into(),
+                    ref_table: "team".into(),
+                    ref_col: "id".into(),
+                    cascade: true,
+                }]
+            );
+        } else {
+            panic!("Unexpected query");
+        }
+    }
+
+    #[test]
+    fn create_table_with_range() {
+        let query =
+            QueryParser::parse("CREATE TABLE user(name text, age range(0, 150) UNIQUE);").unwrap();
+        if let Query::Table {
+            query: TableQuery::Create { cols, types, .. },
+            ..
+        } = query
+        {
+            assert_eq!(cols, vec!["name", "age"]);
+            assert_eq!(
+                types,
+                vec![DataType::TEXT, DataType::RANGE(Some(0), Some(150))]
+            );
+        } else {
+            panic!("Unexpected query");
+        }
+    }
+
+    #[test]
+    fn create_table_with_checks() {
+        let query = QueryParser::parse(
+            "CREATE TABLE user(age int CHECK (age >= 0) UNIQUE, status text CHECK (status IN ('active', 'banned')));",
+        )
+        .unwrap();
+        if let Query::Table {
+            query:
+                TableQuery::Create {
+                    cols,
+                    unique,
+                    checks,
+                    ..
+                },
+            ..
+        } = query
+        {
+            assert_eq!(cols, vec!["age", "status"]);
+            assert_eq!(unique, vec!["age"]);
+            assert_eq!(
+                checks,
+                vec![
+                    Check {
+                        name: "age_check".into(),
+                        condition: Condition::leaf("age", Operator::GtEq, "0"),
+                    },
+                    Check {
+                        name: "status_check".into(),
+                        condition: Condition::leaf(
+                            "status",
+                            Operator::In,
+                            vec!["active".to_string(), "banned".to_string()]
+                        ),
+                    }
+                ]
+            );
+        } else {
+            panic!("Unexpected query");
+        }
+    }
+
+    #[test]
+    fn create_table_with_unique_constraints() {
+        let query = QueryParser::parse(
+            "CREATE TABLE visit (user_id INT, date TEXT, UNIQUE (user_id, date), CONSTRAINT one_code UNIQUE (code));",
+        )
+        .unwrap();
+        if let Query::Table {
+            query:
+                TableQuery::Create {
+                    cols,
+                    unique_constraints,
+                    ..
+                },
+            ..
+        } = query
+        {
+            assert_eq!(cols, vec!["user_id", "date"]);
+            assert_eq!(
+                unique_constraints,
+                vec![
+                    UniqueConstraint {
+                        name: "visit_user_id_date_key".into(),
+                        cols: vec!["user_id".into(), "date".into()],
+                    },
+                    UniqueConstraint {
+                        name: "one_code".into(),
+                        cols: vec!["code".into()],
+                    }
+                ]
+            );
+        } else {
+            panic!("Unexpected query");
+        }
+    }
+
+    #[test]
+    fn create_table_multi_line_query() {
+        let query = QueryParser::parse(
             r#"CREATE TABLE blog (
                 id INT,
                 title VARCHAR(255),
@@ -372,7 +1614,7 @@ mod tests {
         .unwrap();
         if let Query::Table {
             name,
-            query: TableQuery::Create { cols, types },
+            query: TableQuery::Create { cols, types, .. },
         } = query
         {
             assert_eq!(name, "blog".to_string());
@@ -403,6 +1645,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn drop_table_if_exists() {
+        let query = QueryParser::parse("DROP TABLE IF EXISTS demo;").unwrap();
+        assert_eq!(
+            query,
+            Query::Table {
+                name: "demo".to_string(),
+                query: TableQuery::DropTableIfExists,
+            }
+        );
+    }
+
     #[test]
     fn truncate_table() {
         let query = QueryParser::parse(r#"TRUNCATE TABLE demo"#).unwrap();
@@ -433,6 +1687,155 @@ mod tests {
         }
     }
 
+    #[test]
+    fn drop_primary_key() {
+        let query = QueryParser::parse("ALTER TABLE demo DROP PRIMARY KEY;").unwrap();
+
+        assert_eq!(
+            query,
+            Query::Table {
+                name: "demo".into(),
+                query: TableQuery::DropPrimaryKey,
+            }
+        );
+    }
+
+    #[test]
+    fn create_table_with_not_null_and_table_check() {
+        let query = QueryParser::parse(
+            "CREATE TABLE slots(starts int NOT NULL, ends int not null UNIQUE, note text, edited timestamp ON UPDATE NOW, CHECK (starts < ends), CONSTRAINT short CHECK (note != 'long'));",
+        )
+        .unwrap();
+        if let Query::Table {
+            query:
+                TableQuery::Create {
+                    not_null,
+                    auto_now,
+                    unique,
+                    checks,
+                    ..
+                },
+            ..
+        } = query
+        {
+            assert_eq!(not_null, vec!["starts", "ends"]);
+            assert_eq!(auto_now, vec!["edited"]);
+            assert_eq!(unique, vec!["ends"]);
+            let names = checks.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+            assert_eq!(names, vec!["slots_check", "short"]);
+            assert_eq!(checks[0].condition.to_string(), "starts < ends");
+        } else {
+            panic!("Unexpected query");
+        }
+    }
+
+    #[test]
+    fn create_table_with_function_defaults() {
+        let query = QueryParser::parse(
+            "CREATE TABLE logs(msg text, created datetime DEFAULT NOW(), edited datetime default now() on update now())",
+        )
+        .unwrap();
+        if let Query::Table {
+            query:
+                TableQuery::Create {
+                    types,
+                    defaults,
+                    auto_now,
+                    ..
+                },
+            ..
+        } = query
+        {
+            assert_eq!(types[1..], [DataType::DATETIME, DataType::DATETIME]);
+            assert_eq!(defaults[1..], [Some("NOW()".into()), Some("now()".into())]);
+            assert_eq!(auto_now, vec!["edited"]);
+        } else {
+            panic!("Unexpected query");
+        }
+    }
+
+    #[test]
+    fn create_table_with_blob() {
+        let query =
+            QueryParser::parse("CREATE TABLE files(name text, thumb blob(1024), data BLOB)")
+                .unwrap();
+        if let Query::Table {
+            query: TableQuery::Create { types, .. },
+            ..
+        } = query
+        {
+            assert_eq!(
+                types,
+                [
+                    DataType::TEXT,
+                    DataType::BLOB(1024),
+                    DataType::BLOB(BLOB_SIZE)
+                ]
+            );
+        } else {
+            panic!("Unexpected query");
+        }
+    }
+
+    #[test]
+    fn show_create_table() {
+        assert_eq!(
+            QueryParser::parse("SHOW CREATE TABLE users;").unwrap(),
+            Query::Table {
+                name: "users".into(),
+                query: TableQuery::ShowCreate,
+            }
+        );
+    }
+
+    #[test]
+    fn create_and_drop_index() {
+        assert_eq!(
+            QueryParser::parse("CREATE INDEX ON users (email);").unwrap(),
+            Query::Table {
+                name: "users".into(),
+                query: TableQuery::CreateIndex("email".into()),
+            }
+        );
+        assert_eq!(
+            QueryParser::parse("drop index on users(email)").unwrap(),
+            Query::Table {
+                name: "users".into(),
+                query: TableQuery::DropIndex("email".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn rename_table() {
+        let query = QueryParser::parse("ALTER TABLE demo RENAME TO sample;").unwrap();
+
+        assert_eq!(
+            query,
+            Query::Table {
+                name: "demo".to_string(),
+                query: TableQuery::Rename("sample".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn rename_col() {
+        let query =
+            QueryParser::parse("ALTER TABLE demo RENAME COLUMN name TO full_name;").unwrap();
+
+        assert_eq!(
+            query,
+            Query::Table {
+                name: "demo".to_string(),
+                query: TableQuery::RenameCol {
+                    old_name: "name".to_string(),
+                    new_name: "full_name".to_string(),
+                },
+            }
+        );
+    }
+
     #[test]
     fn alter_col() {
         let query = QueryParser::parse("ALTER TABLE demo ALTER COLUMN id int").unwrap();
@@ -490,7 +1893,9 @@ mod tests {
 
         if let Query::Table {
             name,
-            query: TableQuery::Select { cols, condition },
+            query: TableQuery::Select {
+                cols, condition, ..
+            },
         } = query
         {
             assert_eq!(name, "user".to_string());
@@ -498,54 +1903,263 @@ mod tests {
             assert!(condition.is_some());
             assert_eq!(
                 condition.unwrap(),
-                Condition {
-                    key: "age".into(),
-                    value: "12".into(),
-                    operator: Operator::GtEq
+                Condition::leaf("age", Operator::GtEq, "12")
+            );
+        } else {
+            panic!("Unexpected query")
+        }
+    }
+
+    #[test]
+    fn parse_select_statment_with_all_cols_and_condition() {
+        let query = QueryParser::parse("SELECT * FROM user WHERE age=12").unwrap();
+
+        if let Query::Table {
+            name,
+            query: TableQuery::Select {
+                cols, condition, ..
+            },
+        } = query
+        {
+            assert_eq!(name, "user".to_string());
+            assert_eq!(cols, SelectCols::All);
+            assert!(condition.is_some());
+            assert_eq!(
+                condition.unwrap(),
+                Condition::leaf("age", Operator::Eq, "12")
+            );
+        } else {
+            panic!("Unexpected query")
+        }
+    }
+
+    #[test]
+    fn parse_select_statment() {
+        let query = QueryParser::parse("SELECT id,name FROM user").unwrap();
+
+        if let Query::Table {
+            name,
+            query: TableQuery::Select {
+                cols, condition, ..
+            },
+        } = query
+        {
+            assert_eq!(name, "user".to_string());
+            assert_eq!(cols, SelectCols::Cols(vec!["id".into(), "name".into()]));
+            assert!(condition.is_none());
+        } else {
+            panic!("Unexpected query")
+        }
+    }
+
+    #[test]
+    fn parse_select_with_overflowing_limit_or_offset() {
+        let too_big = "99999999999999999999999";
+        for query in [
+            format!("SELECT * FROM t LIMIT {}", too_big),
+            format!("SELECT * FROM t LIMIT 1 OFFSET {}", too_big),
+        ] {
+            assert_eq!(
+                QueryParser::parse(&query),
+                Err(QueryParserError::BadQuery(too_big.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn parse_select_statment_with_limit_and_offset() {
+        let query =
+            QueryParser::parse("SELECT * FROM user WHERE age > 12 LIMIT 10 OFFSET 20;").unwrap();
+
+        if let Query::Table {
+            name,
+            query:
+                TableQuery::Select {
+                    cols,
+                    condition,
+                    options,
+                },
+        } = query
+        {
+            assert_eq!(name, "user".to_string());
+            assert_eq!(cols, SelectCols::All);
+            assert_eq!(condition, Some(Condition::leaf("age", Operator::Gt, "12")));
+            assert_eq!(
+                options,
+                SelectOptions {
+                    limit: Some(10),
+                    offset: Some(20),
+                    ..Default::default()
+                }
+            );
+        } else {
+            panic!("Unexpected query")
+        }
+    }
+
+    #[test]
+    fn parse_select_aliases() {
+        let query =
+            QueryParser::parse("SELECT name AS full_name, age years, id FROM user").unwrap();
+
+        if let Query::Table {
+            query: TableQuery::Select { cols, .. },
+            ..
+        } = query
+        {
+            assert_eq!(
+                cols,
+                SelectCols::Cols(vec![
+                    SelectItem::new("name", Some("full_name")),
+                    SelectItem::new("age", Some("years")),
+                    SelectItem::new("id", None),
+                ])
+            );
+        } else {
+            panic!("Unexpected query")
+        }
+
+        let res = QueryParser::parse("SELECT name AS n, age AS n FROM user");
+        assert_eq!(res, Err(QueryParserError::DuplicateAlias("n".into())));
+        let res = QueryParser::parse("SELECT name, age AS name FROM user");
+        assert_eq!(res, Err(QueryParserError::DuplicateAlias("name".into())));
+    }
+
+    #[test]
+    fn parse_select_case() {
+        let query = QueryParser::parse(
+            "SELECT name, CASE WHEN age >= 18 THEN 'adult' WHEN age IN (13, 14) THEN teen ELSE 'minor' END AS bracket FROM user",
+        )
+        .unwrap();
+
+        if let Query::Table {
+            query: TableQuery::Select { cols, .. },
+            ..
+        } = query
+        {
+            let case = CaseExpr {
+                arms: vec![
+                    (Condition::leaf("age", Operator::GtEq, "18"), "adult".into()),
+                    (
+                        Condition::leaf("age", Operator::In, vec!["13".into(), "14".into()]),
+                        "teen".into(),
+                    ),
+                ],
+                default: Some("minor".into()),
+            };
+            assert_eq!(
+                cols,
+                SelectCols::Cols(vec!["name".into(), SelectItem::case(case, "bracket")])
+            );
+        } else {
+            panic!("Unexpected query")
+        }
+
+        let res = QueryParser::parse("SELECT CASE WHEN age > 1 THEN 'a' END FROM user");
+        assert!(matches!(res, Err(QueryParserError::MissingAlias(_))));
+        let res = QueryParser::parse("SELECT CASE WHEN age > 1 'a' END AS x FROM user");
+        assert!(matches!(res, Err(QueryParserError::InvalidCase(_))));
+    }
+
+    #[test]
+    fn parse_select_order_by() {
+        let query = QueryParser::parse(
+            "SELECT * FROM user WHERE age > 12 ORDER BY age DESC, name LIMIT 5;",
+        )
+        .unwrap();
+
+        if let Query::Table {
+            query: TableQuery::Select {
+                condition, options, ..
+            },
+            ..
+        } = query
+        {
+            assert_eq!(condition, Some(Condition::leaf("age", Operator::Gt, "12")));
+            assert_eq!(
+                options,
+                SelectOptions {
+                    limit: Some(5),
+                    order: Some(vec![
+                        OrderClause::new("age", SortDirection::Desc),
+                        OrderClause::new("name", SortDirection::Asc),
+                    ]),
+                    ..Default::default()
                 }
             );
         } else {
             panic!("Unexpected query")
         }
+
+        let res = QueryParser::parse("SELECT * FROM user ORDER BY age up");
+        assert!(matches!(res, Err(QueryParserError::InvalidOrder(_))));
+    }
+
+    #[test]
+    fn parse_select_grouped() {
+        let query = QueryParser::parse(
+            "SELECT country, city, COUNT(*), AVG(age) AS avg_age FROM users WHERE age > 18 \
+             GROUP BY country, city HAVING avg_age > 30;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query,
+            Query::Table {
+                name: "users".to_string(),
+                query: TableQuery::SelectGrouped {
+                    group_cols: vec!["country".to_string(), "city".to_string()],
+                    aggregates: vec![
+                        ("COUNT(*)".to_string(), AggFunc::Count, "*".to_string()),
+                        ("avg_age".to_string(), AggFunc::Avg, "age".to_string()),
+                    ],
+                    condition: Some(Condition::leaf("age", Operator::Gt, "18")),
+                    having: Some(Condition::leaf("avg_age", Operator::Gt, "30")),
+                },
+            }
+        );
+
+        // Every plain column must be grouped
+        let res = QueryParser::parse("SELECT name, COUNT(*) FROM users GROUP BY country");
+        assert!(matches!(res, Err(QueryParserError::InvalidTableAction(col)) if col == "name"));
     }
 
     #[test]
-    fn parse_select_statment_with_all_cols_and_condition() {
-        let query = QueryParser::parse("SELECT * FROM user WHERE age=12").unwrap();
+    fn parse_select_distinct() {
+        let query = QueryParser::parse("SELECT DISTINCT country FROM user;").unwrap();
 
         if let Query::Table {
-            name,
-            query: TableQuery::Select { cols, condition },
+            query: TableQuery::Select { cols, options, .. },
+            ..
         } = query
         {
-            assert_eq!(name, "user".to_string());
-            assert_eq!(cols, SelectCols::All);
-            assert!(condition.is_some());
-            assert_eq!(
-                condition.unwrap(),
-                Condition {
-                    key: "age".into(),
-                    value: "12".into(),
-                    operator: Operator::Eq
-                }
-            );
+            assert_eq!(cols, SelectCols::Cols(vec!["country".into()]));
+            assert!(options.distinct);
         } else {
             panic!("Unexpected query")
         }
     }
 
     #[test]
-    fn parse_select_statment() {
-        let query = QueryParser::parse("SELECT id,name FROM user").unwrap();
+    fn parse_select_statment_with_limit_only() {
+        let query = QueryParser::parse("SELECT id FROM user LIMIT 5").unwrap();
 
         if let Query::Table {
-            name,
-            query: TableQuery::Select { cols, condition },
+            query: TableQuery::Select {
+                condition, options, ..
+            },
+            ..
         } = query
         {
-            assert_eq!(name, "user".to_string());
-            assert_eq!(cols, SelectCols::Cols(vec!["id".into(), "name".into()]));
             assert!(condition.is_none());
+            assert_eq!(
+                options,
+                SelectOptions {
+                    limit: Some(5),
+                    offset: None,
+                    ..Default::default()
+                }
+            );
         } else {
             panic!("Unexpected query")
         }
@@ -564,7 +2178,7 @@ mod tests {
             assert_eq!(cols, SelectCols::All);
             assert_eq!(
                 values,
-                vec![vec!["value1".to_string(), "value2".to_string()]]
+                vec![vec![Some("value1".to_string()), Some("value2".to_string())]]
             );
         } else {
             panic!("Unexpected query")
@@ -580,9 +2194,9 @@ mod tests {
         .unwrap();
 
         let expected_values = vec![
-            vec!["val1".to_string(), "val2".to_string()],
-            vec!["val3".to_string(), "val4".to_string()],
-            vec!["val5".to_string(), "val6".to_string()],
+            vec![Some("val1".to_string()), Some("val2".to_string())],
+            vec![Some("val3".to_string()), Some("val4".to_string())],
+            vec![Some("val5".to_string()), Some("val6".to_string())],
         ];
 
         if let Query::Table {
@@ -607,9 +2221,9 @@ mod tests {
         .unwrap();
 
         let expected_values = vec![
-            vec!["1".to_string(), "2".to_string()],
-            vec!["3".to_string(), "4".to_string()],
-            vec!["5".to_string(), "6".to_string()],
+            vec![Some("1".to_string()), Some("2".to_string())],
+            vec![Some("3".to_string()), Some("4".to_string())],
+            vec![Some("5".to_string()), Some("6".to_string())],
         ];
 
         if let Query::Table {
@@ -618,11 +2232,49 @@ mod tests {
         } = query
         {
             assert_eq!(name, "table_name".to_string());
+            assert_eq!(cols, SelectCols::Cols(vec!["a".into(), "b".into()]));
+            assert_eq!(values, expected_values);
+        } else {
+            panic!("Unexpected query")
+        }
+    }
+
+    #[test]
+    fn unquoted_bool_literals() {
+        let query = QueryParser::parse("INSERT INTO flags VALUES ('a', TRUE), ('b', false);");
+        if let Ok(Query::Table {
+            query: TableQuery::Insert { values, .. },
+            ..
+        }) = query
+        {
+            assert_eq!(values[0][1].as_deref(), Some("TRUE"));
+            assert_eq!(values[1][1].as_deref(), Some("false"));
+        } else {
+            panic!("Unexpected query")
+        }
+
+        let con = Condition::parse("active = TRUE").unwrap();
+        assert_eq!(con, Condition::leaf("active", Operator::Eq, "TRUE"));
+    }
+
+    #[test]
+    fn insert_statment_with_null() {
+        let query = QueryParser::parse("INSERT INTO user VALUES (1, NULL, 'NULL', null);").unwrap();
+
+        if let Query::Table {
+            query: TableQuery::Insert { values, .. },
+            ..
+        } = query
+        {
             assert_eq!(
-                cols,
-                SelectCols::Cols(vec!["a".to_string(), "b".to_string()])
+                values,
+                vec![vec![
+                    Some("1".to_string()),
+                    None,
+                    Some("'NULL'".to_string()),
+                    None
+                ]]
             );
-            assert_eq!(values, expected_values);
         } else {
             panic!("Unexpected query")
         }
@@ -631,7 +2283,7 @@ mod tests {
     #[test]
     fn delete_from_table() {
         let query = QueryParser::parse(
-            "DELETE FROM table_name WHERE name = jone;
+            "DELETE FROM table_name WHERE name = 'jone';
         ",
         )
         .unwrap();
@@ -642,14 +2294,84 @@ mod tests {
         } = query
         {
             assert_eq!(name, "table_name".to_string());
+            assert_eq!(condition, Condition::leaf("name", Operator::Eq, "jone"));
+        } else {
+            panic!("Unexpected query")
+        }
+    }
+
+    #[test]
+    fn update_table() {
+        let query = QueryParser::parse(
+            "UPDATE user
+            SET name = 'jone', age = 21
+            WHERE id = 1;",
+        )
+        .unwrap();
+
+        if let Query::Table {
+            name,
+            query:
+                TableQuery::Update {
+                    assignments,
+                    condition,
+                },
+        } = query
+        {
+            assert_eq!(name, "user".to_string());
             assert_eq!(
-                condition,
-                Condition {
-                    key: "name".into(),
-                    value: "jone".into(),
-                    operator: Operator::Eq
-                }
+                assignments,
+                vec![
+                    ("name".to_string(), Some("jone".to_string())),
+                    ("age".to_string(), Some("21".to_string()))
+                ]
+            );
+            assert_eq!(condition, Some(Condition::leaf("id", Operator::Eq, "1")));
+        } else {
+            panic!("Unexpected query")
+        }
+    }
+
+    #[test]
+    fn update_table_with_null() {
+        let query = QueryParser::parse("UPDATE user SET email = NULL, note = 'null'").unwrap();
+
+        if let Query::Table {
+            query: TableQuery::Update { assignments, .. },
+            ..
+        } = query
+        {
+            assert_eq!(
+                assignments,
+                vec![
+                    ("email".to_string(), None),
+                    ("note".to_string(), Some("null".to_string()))
+                ]
+            );
+        } else {
+            panic!("Unexpected query")
+        }
+    }
+
+    #[test]
+    fn update_table_without_condition() {
+        let query = QueryParser::parse("UPDATE user SET active = false").unwrap();
+
+        if let Query::Table {
+            name,
+            query:
+                TableQuery::Update {
+                    assignments,
+                    condition,
+                },
+        } = query
+        {
+            assert_eq!(name, "user".to_string());
+            assert_eq!(
+                assignments,
+                vec![("active".to_string(), Some("false".to_string()))]
             );
+            assert!(condition.is_none());
         } else {
             panic!("Unexpected query")
         }
@@ -669,28 +2391,405 @@ mod tests {
     #[test]
     fn parse_eq_condition() {
         let con = Condition::parse("name = jone").unwrap();
-
         assert_eq!(
             con,
-            Condition {
-                key: "name".into(),
-                value: "jone".into(),
-                operator: Operator::Eq
-            }
-        )
+            Condition::leaf("name", Operator::Eq, ConditionValue::Column("jone".into()))
+        );
+
+        let con = Condition::parse("name = 'jone'").unwrap();
+        assert_eq!(con, Condition::leaf("name", Operator::Eq, "jone"));
+
+        let con = Condition::parse("active = false").unwrap();
+        assert_eq!(con, Condition::leaf("active", Operator::Eq, "false"))
     }
 
     #[test]
     fn parse_less_than_or_equal_condition() {
         let con = Condition::parse("age <= 21").unwrap();
+        assert_eq!(con, Condition::leaf("age", Operator::LtEq, "21"))
+    }
+
+    #[test]
+    fn parse_and_condition() {
+        let con = Condition::parse("age > 18 AND country = 'US'").unwrap();
         assert_eq!(
             con,
-            Condition {
-                key: "age".into(),
-                value: "21".into(),
-                operator: Operator::LtEq
-            }
+            Condition::and(
+                Condition::leaf("age", Operator::Gt, "18"),
+                Condition::leaf("country", Operator::Eq, "US")
+            )
+        )
+    }
+
+    #[test]
+    fn parse_or_condition() {
+        let con = Condition::parse("status = 'active' or status = 'trial'").unwrap();
+        assert_eq!(
+            con,
+            Condition::or(
+                Condition::leaf("status", Operator::Eq, "active"),
+                Condition::leaf("status", Operator::Eq, "trial")
+            )
+        )
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let con = Condition::parse("a = 1 OR b = 2 AND c = 3 OR d = 4").unwrap();
+        assert_eq!(
+            con,
+            Condition::or(
+                Condition::or(
+                    Condition::leaf("a", Operator::Eq, "1"),
+                    Condition::and(
+                        Condition::leaf("b", Operator::Eq, "2"),
+                        Condition::leaf("c", Operator::Eq, "3")
+                    )
+                ),
+                Condition::leaf("d", Operator::Eq, "4")
+            )
+        )
+    }
+
+    #[test]
+    fn display_condition() {
+        for sql in [
+            "a = 1 OR b = 'x y' AND c = d",
+            "(a = 1 OR b = 2) AND NOT (c IS NULL OR d NOT LIKE 'j%')",
+            "a = 1 AND (b = 2 AND c IN ('x', 'y'))",
+            "a NOT BETWEEN 1 AND 5 OR (b = 'x' OR c IN (SELECT id FROM t WHERE n = 2))",
+        ] {
+            let con = Condition::parse(sql).unwrap();
+            assert_eq!(con.to_string(), sql);
+            assert_eq!(Condition::parse(&con.to_string()).unwrap(), con);
+        }
+    }
+
+    #[test]
+    fn ignore_keywords_inside_quotes() {
+        let con = Condition::parse("name = 'tom and jerry'").unwrap();
+        assert_eq!(con, Condition::leaf("name", Operator::Eq, "tom and jerry"))
+    }
+
+    #[test]
+    fn parse_select_statment_with_compound_condition() {
+        let query =
+            QueryParser::parse("SELECT * FROM user WHERE age >= 12 AND name != jone;").unwrap();
+
+        if let Query::Table {
+            query: TableQuery::Select { condition, .. },
+            ..
+        } = query
+        {
+            assert_eq!(
+                condition,
+                Some(Condition::And(
+                    Box::new(Condition::leaf("age", Operator::GtEq, "12")),
+                    Box::new(Condition::leaf(
+                        "name",
+                        Operator::NotEq,
+                        ConditionValue::Column("jone".into())
+                    ))
+                ))
+            );
+        } else {
+            panic!("Unexpected query")
+        }
+    }
+
+    #[test]
+    fn parse_like_condition() {
+        let con = Condition::parse("email LIKE '%@example.com'").unwrap();
+        assert_eq!(
+            con,
+            Condition::leaf("email", Operator::Like, "%@example.com")
+        );
+
+        let con = Condition::parse("name not like user_%").unwrap();
+        assert_eq!(con, Condition::leaf("name", Operator::NotLike, "user_%"));
+
+        let con = Condition::parse("email ILIKE '%@EXAMPLE.com'").unwrap();
+        assert_eq!(
+            con,
+            Condition::leaf("email", Operator::ILike, "%@EXAMPLE.com")
+        );
+        let con = Condition::parse("name NOT ILIKE 'j%'").unwrap();
+        assert_eq!(con, Condition::leaf("name", Operator::NotILike, "j%"));
+    }
+
+    #[test]
+    fn parse_json_path_condition() {
+        let con = Condition::parse("json_extract(data, '$.user.tags[0]') = 'red'").unwrap();
+        assert_eq!(
+            con,
+            Condition::leaf("data", Operator::JsonPathEq("$.user.tags[0]".into()), "red")
+        );
+        assert_eq!(
+            con.to_string(),
+            "json_extract(data, '$.user.tags[0]') = 'red'"
+        );
+
+        let con = Condition::parse("JSON_EXTRACT( data , '$.n' ) = 3 AND id > 1").unwrap();
+        assert_eq!(
+            con,
+            Condition::and(
+                Condition::leaf("data", Operator::JsonPathEq("$.n".into()), "3"),
+                Condition::leaf("id", Operator::Gt, "1")
+            )
+        );
+        assert_eq!(con.to_string(), "json_extract(data, '$.n') = 3 AND id > 1");
+
+        // Paths start at the document
+        assert!(Condition::parse("json_extract(data, 'user') = 'x'").is_err());
+    }
+
+    #[test]
+    fn parse_regexp_condition() {
+        let con = Condition::parse(r"name REGEXP '^j\w+e$'").unwrap();
+        assert_eq!(con, Condition::leaf("name", Operator::Regexp, r"^j\w+e$"));
+        assert_eq!(con.to_string(), r"name REGEXP '^j\w+e$'");
+
+        let con = Condition::parse("name not regexp '[0-9]'").unwrap();
+        assert_eq!(con, Condition::leaf("name", Operator::NotRegexp, "[0-9]"));
+        assert_eq!(con.to_string(), "name NOT REGEXP '[0-9]'");
+    }
+
+    #[test]
+    fn parse_nocase_condition() {
+        let con = Condition::parse("email = 'Jone@Example.com' COLLATE NOCASE").unwrap();
+        assert_eq!(
+            con,
+            Condition::leaf("email", Operator::IEq, "Jone@Example.com")
+        );
+        assert_eq!(con.to_string(), "email = 'Jone@Example.com' COLLATE NOCASE");
+
+        let con = Condition::parse("code != 'us' collate nocase AND a = b COLLATE NOCASE").unwrap();
+        assert_eq!(
+            con.to_string(),
+            "code != 'us' COLLATE NOCASE AND a = b COLLATE NOCASE"
+        );
+        assert!(matches!(
+            Condition::parse("age > 'a' COLLATE NOCASE"),
+            Err(QueryParserError::InvalidOperator(_))
+        ));
+    }
+
+    #[test]
+    fn parse_in_condition() {
+        let con = Condition::parse("status IN ('active', \"pending\", trial)").unwrap();
+        assert_eq!(
+            con,
+            Condition::leaf(
+                "status",
+                Operator::In,
+                vec![
+                    "active".to_string(),
+                    "pending".to_string(),
+                    "trial".to_string()
+                ]
+            )
+        );
+
+        let con = Condition::parse("id not in (1,2) AND age > 3").unwrap();
+        assert_eq!(
+            con,
+            Condition::And(
+                Box::new(Condition::leaf(
+                    "id",
+                    Operator::NotIn,
+                    vec!["1".to_string(), "2".to_string()]
+                )),
+                Box::new(Condition::leaf("age", Operator::Gt, "3"))
+            )
+        );
+    }
+
+    #[test]
+    fn parse_in_subquery_condition() {
+        let query = QueryParser::parse(
+            "DELETE FROM orders WHERE user_id IN (SELECT id FROM users WHERE active = 'false' AND (age > 3)) OR total = 0;",
         )
+        .unwrap();
+
+        assert_eq!(
+            query,
+            Query::Table {
+                name: "orders".to_string(),
+                query: TableQuery::Delete {
+                    condition: Condition::Or(
+                        Box::new(Condition::leaf(
+                            "user_id",
+                            Operator::In,
+                            ConditionValue::Subquery {
+                                table: "users".to_string(),
+                                col: "id".to_string(),
+                                condition: Some(Box::new(Condition::And(
+                                    Box::new(Condition::leaf("active", Operator::Eq, "false")),
+                                    Box::new(Condition::leaf("age", Operator::Gt, "3"))
+                                ))),
+                            }
+                        )),
+                        Box::new(Condition::leaf("total", Operator::Eq, "0"))
+                    )
+                },
+            }
+        );
+
+        let con = Condition::parse("id not in (select user_id from orders)").unwrap();
+        assert_eq!(
+            con,
+            Condition::leaf(
+                "id",
+                Operator::NotIn,
+                ConditionValue::Subquery {
+                    table: "orders".to_string(),
+                    col: "user_id".to_string(),
+                    condition: None,
+                }
+            )
+        );
+
+        let res = Condition::parse("id IN (SELECT id, name FROM users)");
+        assert!(matches!(res, Err(QueryParserError::SubqueryCols(cols)) if cols == "id, name"));
+        let res = Condition::parse("id IN (SELECT * FROM users)");
+        assert!(matches!(res, Err(QueryParserError::SubqueryCols(_))));
+    }
+
+    #[test]
+    fn parse_empty_in_condition() {
+        let con = Condition::parse("id IN ()").unwrap();
+        assert_eq!(
+            con,
+            Condition::leaf("id", Operator::In, Vec::<String>::new())
+        );
+    }
+
+    #[test]
+    fn parse_not_condition() {
+        let con = Condition::parse("a = 1 OR NOT b = 2 AND c = 3").unwrap();
+        assert_eq!(
+            con,
+            Condition::or(
+                Condition::leaf("a", Operator::Eq, "1"),
+                Condition::and(
+                    Condition::not(Condition::leaf("b", Operator::Eq, "2")),
+                    Condition::leaf("c", Operator::Eq, "3")
+                )
+            )
+        );
+
+        // `NOT` as part of an operator isn't a negation
+        let con = Condition::parse("not not name not like 'j%'").unwrap();
+        assert_eq!(
+            con,
+            Condition::not(Condition::not(Condition::leaf(
+                "name",
+                Operator::NotLike,
+                "j%"
+            )))
+        );
+    }
+
+    #[test]
+    fn parse_grouped_condition() {
+        let a = || Condition::leaf("a", Operator::Eq, "1");
+        let b = || Condition::leaf("b", Operator::Eq, "2");
+        let c = || Condition::leaf("c", Operator::Gt, "3");
+
+        let con = Condition::parse("(a = 1 OR b = 2) AND c > 3").unwrap();
+        assert_eq!(con, Condition::and(Condition::or(a(), b()), c()));
+
+        let con = Condition::parse("NOT (a = 1 OR b = 2) OR c > 3").unwrap();
+        assert_eq!(
+            con,
+            Condition::or(Condition::not(Condition::or(a(), b())), c())
+        );
+
+        let con = Condition::parse("a = 1 AND ((b = 2 OR (c > 3)) AND NOT (a = 1))").unwrap();
+        assert_eq!(
+            con,
+            Condition::and(
+                a(),
+                Condition::and(Condition::or(b(), c()), Condition::not(a()))
+            )
+        );
+
+        // Redundant parentheses and parentheses inside values
+        assert_eq!(Condition::parse("((a = 1))").unwrap(), a());
+        assert_eq!(
+            Condition::parse("(a = 1) AND (b = 2)").unwrap(),
+            Condition::and(a(), b())
+        );
+        assert_eq!(
+            Condition::parse("(name = 'x (y' OR id IN (1, 2))").unwrap(),
+            Condition::or(
+                Condition::leaf("name", Operator::Eq, "x (y"),
+                Condition::leaf("id", Operator::In, vec!["1".to_string(), "2".to_string()])
+            )
+        );
+    }
+
+    #[test]
+    fn parse_unbalanced_parens() {
+        let res = Condition::parse("(a = 1 OR b = 2 AND c > 3");
+        assert!(matches!(res, Err(QueryParserError::UnbalancedParens(0))));
+
+        let res = Condition::parse("a = 1) OR (b = 2");
+        assert!(matches!(res, Err(QueryParserError::UnbalancedParens(5))));
+
+        let res = QueryParser::parse("SELECT * FROM user WHERE (a = 1 OR (b = 2);");
+        assert!(matches!(res, Err(QueryParserError::UnbalancedParens(0))));
+    }
+
+    #[test]
+    fn parse_null_condition() {
+        let con = Condition::parse("email IS NULL").unwrap();
+        assert_eq!(
+            con,
+            Condition::leaf("email", Operator::IsNull, ConditionValue::Empty)
+        );
+
+        let con = Condition::parse("email is not null OR age > 3").unwrap();
+        assert_eq!(
+            con,
+            Condition::Or(
+                Box::new(Condition::leaf(
+                    "email",
+                    Operator::IsNotNull,
+                    ConditionValue::Empty
+                )),
+                Box::new(Condition::leaf("age", Operator::Gt, "3"))
+            )
+        );
+    }
+
+    #[test]
+    fn parse_between_condition() {
+        let con = Condition::parse("price BETWEEN 10 AND 50").unwrap();
+        assert_eq!(
+            con,
+            Condition::leaf(
+                "price",
+                Operator::Between,
+                ConditionValue::Range("10".into(), "50".into())
+            )
+        );
+
+        let con = Condition::parse("name not between 'a b' and 'm' AND age > 3 OR id = 1").unwrap();
+        assert_eq!(
+            con,
+            Condition::Or(
+                Box::new(Condition::And(
+                    Box::new(Condition::leaf(
+                        "name",
+                        Operator::NotBetween,
+                        ConditionValue::Range("a b".into(), "m".into())
+                    )),
+                    Box::new(Condition::leaf("age", Operator::Gt, "3"))
+                )),
+                Box::new(Condition::leaf("id", Operator::Eq, "1"))
+            )
+        );
     }
 
     #[test]
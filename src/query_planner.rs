@@ -1,7 +1,10 @@
 use crate::{
     database::{Database, DatabaseError},
     query_parser::{DatabaseAction, Query, QueryParser, QueryParserError, TableQuery},
-    table::{Table, TableError},
+    table::{
+        table_except, table_intersect, table_union, ColOptions, Schema, Table, TableEntries,
+        TableError,
+    },
     utils::display_entries,
 };
 use inquire::{validator::Validation, InquireError, Text};
@@ -19,9 +22,65 @@ pub enum QueryPlannerError {
     TableError(#[from] TableError),
 }
 
+/// What a successfully executed query produced.
+#[derive(Debug, PartialEq)]
+pub enum QueryResult {
+    Done,
+    Rows(TableEntries),
+    Updated(usize),
+    Names(Vec<String>),
+    /// The tables of the current database and the names missing their data or schema file.
+    Tables {
+        names: Vec<String>,
+        incomplete: Vec<String>,
+    },
+    Message(String),
+}
+
+impl QueryResult {
+    /// The rows of a select, `None` for any other result.
+    pub fn into_rows(self) -> Option<TableEntries> {
+        match self {
+            QueryResult::Rows(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// The rows of both results without duplicates, see [`table_union`]. Like
+    /// [`QueryResult::intersect`] and [`QueryResult::except`] it's `None` unless both results
+    /// are rows.
+    pub fn union(self, other: QueryResult) -> Option<QueryResult> {
+        let (a, b) = (self.into_rows()?, other.into_rows()?);
+        Some(QueryResult::Rows(table_union(a, b, true)))
+    }
+
+    /// The rows found in both results, see [`table_intersect`].
+    pub fn intersect(self, other: QueryResult) -> Option<QueryResult> {
+        let (a, b) = (self.into_rows()?, other.into_rows()?);
+        Some(QueryResult::Rows(table_intersect(a, b)))
+    }
+
+    /// The rows of this result that aren't in `other`, see [`table_except`].
+    pub fn except(self, other: QueryResult) -> Option<QueryResult> {
+        let (a, b) = (self.into_rows()?, other.into_rows()?);
+        Some(QueryResult::Rows(table_except(a, b)))
+    }
+}
+
 pub struct QueryPlanner;
 impl QueryPlanner {
     pub fn new() -> Result<(), QueryPlannerError> {
+        QueryPlanner::repl()
+    }
+
+    /// Switch to `db_name` and start the prompt.
+    pub fn run(db_name: &str) -> Result<(), QueryPlannerError> {
+        Database::use_db(db_name)?;
+        QueryPlanner::repl()
+    }
+
+    /// Read and execute queries until `exit` / `quit` or the prompt is interrupted.
+    fn repl() -> Result<(), QueryPlannerError> {
         let keywords = include_str!("../mysql5.0_keywords.txt")
             .split("\n")
             .map(|k| k.trim().to_string())
@@ -47,7 +106,7 @@ impl QueryPlanner {
                 .filter(|keyword| keyword.starts_with(&q.to_uppercase()))
                 .take(4)
                 .map(|k| {
-                    let mut as_string = (&input_tokens[0..num_of_tokens - 1]).to_vec().join(" ");
+                    let mut as_string = input_tokens[0..num_of_tokens - 1].to_vec().join(" ");
                     as_string.push(' ');
                     as_string.push_str(&k);
 
@@ -69,67 +128,335 @@ impl QueryPlanner {
                     }
                 })
                 .prompt();
-            if let Err(e) = query {
-                eprintln!("{:?}", e);
-                continue;
+            let query = match query {
+                Ok(query) => query,
+                Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => {
+                    return Ok(())
+                }
+                Err(e @ InquireError::NotTTY) => return Err(e.into()),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    continue;
+                }
+            };
+
+            if matches!(
+                query.trim().trim_end_matches(';').to_lowercase().as_str(),
+                "exit" | "quit"
+            ) {
+                return Ok(());
             }
 
-            if let Err(e) = QueryPlanner::execute_query(&query.unwrap()) {
-                eprintln!("{:?}", e);
+            if let Err(e) = QueryPlanner::execute_query(&query) {
+                eprintln!("{}", error_message(&e));
             }
         }
     }
 
     fn execute_query(raw_query: &str) -> Result<(), QueryPlannerError> {
+        match QueryPlanner::execute(raw_query)? {
+            QueryResult::Done => {}
+            QueryResult::Rows(entries) if entries.is_empty() => println!("\tNo entries"),
+            QueryResult::Rows(entries) => display_entries(entries),
+            QueryResult::Updated(updated) => println!("{} entries updated", updated),
+            QueryResult::Names(names) => names.iter().for_each(|n| println!("{}", n)),
+            QueryResult::Tables { names, incomplete } => {
+                names.iter().for_each(|n| println!("{}", n));
+                for name in incomplete {
+                    eprintln!("Warning: `{}` is missing its data or schema file", name);
+                }
+            }
+            QueryResult::Message(msg) => println!("{}", msg),
+        }
+
+        Ok(())
+    }
+
+    /// Parse and run a single statement against the current database.
+    pub fn execute(raw_query: &str) -> Result<QueryResult, QueryPlannerError> {
         let query = QueryParser::parse(raw_query.trim())?;
-        match query {
+        let result = match query {
             Query::Database { name, action } => match action {
-                DatabaseAction::Create => Database::new(&name)?,
-                DatabaseAction::Drop => Database::drop(&name)?,
-                DatabaseAction::Use => Database::use_db(&name)?,
+                DatabaseAction::Create => Database::new(&name).map(|_| QueryResult::Done)?,
+                DatabaseAction::Drop => Database::drop(&name).map(|_| QueryResult::Done)?,
+                DatabaseAction::DropIfExists => match Database::drop_if_exists(&name)? {
+                    true => QueryResult::Done,
+                    false => {
+                        QueryResult::Message(format!("Database `{}` doesn't exist, skipping", name))
+                    }
+                },
+                DatabaseAction::Use => Database::use_db(&name).map(|_| QueryResult::Done)?,
             },
             Query::Table { name, query } => {
                 let curr_db = Database::get_curr_db()?;
                 let table = Table::new(&curr_db, &name)?;
                 match query {
-                    TableQuery::Create { cols, types } => table.create(cols, types)?,
-                    TableQuery::DropTable => table.drop()?,
-                    TableQuery::Truncate => table.truncate()?,
-                    TableQuery::DropCol(col) => table.remove_col(&col)?,
-                    TableQuery::AlterCol { col_name, datatype } => {
-                        table.alter(&col_name, datatype)?
+                    query @ TableQuery::Create { .. } => {
+                        let (schema, if_not_exists) = create_schema(query);
+                        match if_not_exists {
+                            true => table.create_if_not_exists(schema)?,
+                            false => table.create(schema)?,
+                        }
+                        QueryResult::Done
                     }
-                    TableQuery::AddCol { col_name, datatype } => {
-                        table.add_col(&col_name, datatype)?
+                    TableQuery::DropTable => table.drop().map(|_| QueryResult::Done)?,
+                    TableQuery::DropTableIfExists => match table.drop_if_exists()? {
+                        true => QueryResult::Done,
+                        false => QueryResult::Message(format!(
+                            "Table `{}` doesn't exist, skipping",
+                            name
+                        )),
+                    },
+                    TableQuery::Truncate => table.truncate(false).map(|_| QueryResult::Done)?,
+                    TableQuery::DropCol(col) => {
+                        table.remove_col(&col).map(|_| QueryResult::Done)?
                     }
-                    TableQuery::Select { cols, condition } => {
-                        let entries = table.select(cols, condition)?;
-
-                        if entries.is_empty() {
-                            println!("\tNo entries")
-                        } else {
-                            display_entries(entries);
-                        }
+                    TableQuery::DropPrimaryKey => {
+                        table.drop_primary_key().map(|_| QueryResult::Done)?
+                    }
+                    TableQuery::ShowCreate => QueryResult::Message(table.show_create()?),
+                    TableQuery::CreateIndex(col) => {
+                        table.create_index(&col).map(|_| QueryResult::Done)?
+                    }
+                    TableQuery::DropIndex(col) => {
+                        table.drop_index(&col).map(|_| QueryResult::Done)?
+                    }
+                    TableQuery::AlterCol { col_name, datatype } => table
+                        .alter(&col_name, datatype, false)
+                        .map(|_| QueryResult::Done)?,
+                    TableQuery::Rename(new_name) => {
+                        let mut table = Table::new(&curr_db, &name)?;
+                        table.rename(&new_name).map(|_| QueryResult::Done)?
+                    }
+                    TableQuery::RenameCol { old_name, new_name } => table
+                        .rename_col(&old_name, &new_name)
+                        .map(|_| QueryResult::Done)?,
+                    TableQuery::AddCol { col_name, datatype } => table
+                        .add_col(
+                            &col_name,
+                            datatype,
+                            ColOptions {
+                                nullable: true,
+                                ..Default::default()
+                            },
+                        )
+                        .map(|_| QueryResult::Done)?,
+                    TableQuery::Select {
+                        cols,
+                        condition,
+                        options,
+                    } => QueryResult::Rows(table.select(cols, condition, options)?),
+                    TableQuery::SelectGrouped {
+                        group_cols,
+                        aggregates,
+                        condition,
+                        having,
+                    } => QueryResult::Rows(
+                        table.group_by(group_cols, aggregates, condition, having)?,
+                    ),
+                    TableQuery::Insert { cols, values } => {
+                        table.insert(cols, values)?;
+                        QueryResult::Message(format!(
+                            "[{}@{}] {} entries",
+                            name,
+                            table.db,
+                            table.count(None)?
+                        ))
                     }
-                    TableQuery::Insert { cols, values } => table.insert(cols, values)?,
-                    TableQuery::Delete { condition } => table.delete(condition)?,
+                    TableQuery::Delete { condition } => {
+                        table.delete(condition).map(|_| QueryResult::Done)?
+                    }
+                    TableQuery::Update {
+                        assignments,
+                        condition,
+                    } => QueryResult::Updated(
+                        table.update(assignments.into_iter().collect(), condition)?,
+                    ),
                 }
             }
-            Query::ShowAllDBs => Database::get_dbs()?.iter().for_each(|db| {
-                println!("{}", db);
-            }),
+            Query::ShowAllDBs => QueryResult::Names(Database::get_dbs()?),
             Query::ShowCurrDB => {
-                let curr_db = Database::get_curr_db()?;
-                println!("Current DB: {}", curr_db);
+                QueryResult::Message(format!("Current DB: {}", Database::get_curr_db()?))
             }
             Query::ShowTables => {
-                let curr_db = Database::get_curr_db()?;
-                Database::get_db_tables(&curr_db)?.iter().for_each(|t| {
-                    println!("{}", t);
-                })
+                let db = Database::get_curr_db()?;
+                QueryResult::Tables {
+                    names: Database::list_tables(&db, false)?,
+                    incomplete: Database::incomplete_tables(&db)?,
+                }
             }
         };
 
-        Ok(())
+        Ok(result)
+    }
+}
+
+/// The schema of a `CREATE TABLE` query and whether it has `IF NOT EXISTS`.
+fn create_schema(query: TableQuery) -> (Schema, bool) {
+    let TableQuery::Create {
+        cols,
+        types,
+        primary_key,
+        unique,
+        not_null,
+        auto_now,
+        defaults,
+        foreign_keys,
+        checks,
+        unique_constraints,
+        if_not_exists,
+    } = query
+    else {
+        unreachable!("not a CREATE TABLE query")
+    };
+
+    let nullable = cols.iter().map(|col| !not_null.contains(col)).collect();
+    let schema = Schema {
+        nullable,
+        primary_key,
+        unique,
+        defaults,
+        auto_now,
+        foreign_keys,
+        checks,
+        unique_constraints,
+        ..Schema::new(cols, types)
+    };
+    (schema, if_not_exists)
+}
+
+/// The error followed by its sources, e.g. `Table Error: Column not found`.
+fn error_message(e: &dyn std::error::Error) -> String {
+    let mut msg = e.to_string();
+    let mut source = e.source();
+    while let Some(e) = source {
+        msg.push_str(&format!(": {}", e));
+        source = e.source();
+    }
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_schema, error_message, QueryPlanner, QueryPlannerError, QueryResult};
+    use crate::{
+        database::DatabaseError,
+        query_parser::{Query, QueryParser, SelectCols},
+        table::{
+            tests::{eq, keyed_table, users_table, TestDB},
+            Table,
+        },
+    };
+
+    fn create_events<'a>(db: &'a TestDB, query: &str) -> Table<'a> {
+        let Query::Table { name, query } = QueryParser::parse(query).unwrap() else {
+            panic!("not a table query");
+        };
+        assert_eq!(name, "events");
+        let table = Table::new(&db.0, "events").unwrap();
+        table.create(create_schema(query).0).unwrap();
+        table
+    }
+
+    #[test]
+    fn execute_database_queries() {
+        let name = "test_execute_database_queries";
+        let _ = QueryPlanner::execute(&format!("DROP DATABASE IF EXISTS {};", name));
+
+        let res = QueryPlanner::execute(&format!("CREATE DATABASE {};", name)).unwrap();
+        assert_eq!(res, QueryResult::Done);
+        match QueryPlanner::execute("SHOW DATABASES;").unwrap() {
+            QueryResult::Names(dbs) => assert!(dbs.iter().any(|db| db == name)),
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        QueryPlanner::execute(&format!("DROP DATABASE {};", name)).unwrap();
+        assert!(matches!(
+            QueryPlanner::execute(&format!("DROP DATABASE {};", name)),
+            Err(QueryPlannerError::DatabaseError(DatabaseError::NotFound(_)))
+        ));
+        assert!(matches!(
+            QueryPlanner::execute(&format!("DROP DATABASE IF EXISTS {};", name)),
+            Ok(QueryResult::Message(_))
+        ));
+    }
+
+    #[test]
+    fn show_create_round_trip() {
+        let db = TestDB::new("test_show_create");
+        let other_db = TestDB::new("test_show_create_copy");
+        keyed_table(&db);
+        keyed_table(&other_db);
+        let table = create_events(
+            &db,
+            "CREATE TABLE events (
+                id INT PRIMARY KEY,
+                title VARCHAR(20) NOT NULL UNIQUE,
+                kind ENUM('talk', 'demo') DEFAULT 'talk',
+                owner INT REFERENCES keyed(id) ON DELETE CASCADE,
+                score DECIMAL(2) CHECK (score >= 0 AND (score <= 10 OR kind = 'demo')),
+                starts INT,
+                ends INT,
+                updated TIMESTAMP DEFAULT NOW ON UPDATE NOW,
+                host VARCHAR(20) DEFAULT 'O''Brien' CHECK (host != 'it''s'),
+                CONSTRAINT one_per_slot UNIQUE (starts, ends),
+                CONSTRAINT valid_slot CHECK (NOT (starts > ends) AND title NOT IN ('x', 'y''s'))
+            );",
+        );
+
+        let schema = table.read_schema().unwrap();
+        let host = schema.cols.iter().position(|col| col == "host").unwrap();
+        assert_eq!(schema.defaults[host].as_deref(), Some("O'Brien"));
+
+        let sql = table.show_create().unwrap();
+        assert!(sql.contains("DEFAULT 'O''Brien'"));
+        assert!(sql.starts_with("CREATE TABLE events (\n    id INT PRIMARY KEY NOT NULL,\n"));
+        let copy = create_events(&other_db, &sql);
+        assert_eq!(copy.read_schema().unwrap(), table.read_schema().unwrap());
+        assert_eq!(copy.show_create().unwrap(), sql);
+    }
+
+    #[test]
+    fn combine_select_results() {
+        let db = TestDB::new("test_combine_results");
+        let table = users_table(&db);
+        let select = |condition| {
+            let cols = SelectCols::Cols(vec!["name".into()]);
+            QueryResult::Rows(
+                table
+                    .select(cols, Some(condition), Default::default())
+                    .unwrap(),
+            )
+        };
+        let names = |result: Option<QueryResult>| {
+            let mut names = result
+                .and_then(QueryResult::into_rows)
+                .unwrap()
+                .into_iter()
+                .map(|e| e["name"].clone().unwrap())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            names(select(eq("age", "30")).intersect(select(eq("name", "jane")))),
+            vec!["jane"]
+        );
+        assert_eq!(
+            names(select(eq("age", "30")).except(select(eq("name", "jane")))),
+            vec!["doe"]
+        );
+        assert_eq!(
+            names(select(eq("age", "20")).union(select(eq("name", "jone")))),
+            vec!["jone"]
+        );
+        assert_eq!(QueryResult::Done.except(select(eq("age", "20"))), None);
+    }
+
+    #[test]
+    fn error_message_includes_sources() {
+        let err = QueryPlanner::execute("SELECT FROM;").unwrap_err();
+        assert!(error_message(&err).starts_with("Error while parsing the query: "));
     }
 }
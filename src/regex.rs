@@ -9,40 +9,107 @@
 /// both the action(create, drop, use) and the DB name will be extracted.
 ///
 /// See a interactive example [here](https://regex101.com/r/Co6RIt/1)
-pub const RE_DB: &str = r"(?im)(?P<action>[^\s;]+) database (?P<name>[^;]+)";
+pub const RE_DB: &str =
+    r"(?im)(?P<action>[^\s;]+) database (?P<if_exists>if exists )?(?P<name>[^;]+)";
 /// A regex to extract table name and table entries.
-pub const RE_CREATE_TABLE: &str = r"(?im)create table (?P<name>[^\(\s]+)(\s|)(?P<entries>[^;]+)";
+pub const RE_CREATE_TABLE: &str = r"(?im)create table (?P<if_not_exists>if not exists )?(?P<name>[^\(\s]+)(\s|)(?P<entries>[^;]+)";
 /// A regex to extract columns name and its types. intractive example [here](https://regex101.com/r/s6rTCW/1)
-pub const RE_TABLE_ENTRIES: &str = r"(?im)(?P<col_name>[^\s,\(]+) (?P<col_type>[^,\n;\)]+)";
+///
+/// Types may hold parentheses with commas like `ENUM('a', 'b')` and be followed by constraints
+/// like `VARCHAR(20) PRIMARY KEY` or `TEXT CHECK (status IN ('a', 'b'))`.
+pub const RE_TABLE_ENTRIES: &str =
+    r"(?im)(?P<col_name>[^\s,\(]+) (?P<col_type>(?:[^,\n;\(\)]|\((?:[^\(\)]|\([^\)]*\))*\))+)";
+/// A regex to match a trailing column constraint like `INT PRIMARY KEY`, `TEXT UNIQUE`, `TEXT NOT NULL`,
+/// `TEXT DEFAULT 'active'`, `TIMESTAMP ON UPDATE NOW`, `DATETIME DEFAULT NOW()`, `INT CHECK (age >= 0)` or `INT REFERENCES users(id) ON DELETE CASCADE`.
+pub const RE_COL_CONSTRAINT: &str = r"(?i)^(?P<col_type>.+?)\s+(?P<constraint>primary\s+key|unique|default\s+((?P<quoted>'(?:[^']|'')*')|(?P<value>[^\s']+))|not\s+null|on\s+update\s+now(\s*\(\s*\))?|check\s*\((?P<check>.+)\)|references\s+(?P<ref_table>[^\s\(]+)\s*\(\s*(?P<ref_col>[^\s\)]+)\s*\)(?P<cascade>\s+on\s+delete\s+cascade)?)$";
+/// A regex to match a table constraint like `UNIQUE (user_id, date)` or
+/// `CONSTRAINT one_per_day UNIQUE (user_id, date)`.
+pub const RE_UNIQUE_CONSTRAINT: &str =
+    r"(?i)^(constraint\s+(?P<name>[^\s]+)\s+)?unique\s*\((?P<cols>[^\)]+)\)$";
+/// A regex to match a table check like `CHECK (start < end)` or `CONSTRAINT valid_range CHECK (start < end)`.
+pub const RE_CHECK_CONSTRAINT: &str =
+    r"(?is)^(constraint\s+(?P<name>[^\s]+)\s+)?check\s*\((?P<check>.+)\)$";
 /// A regex to match `drop` or `truncate` table query. Example [here](https://regex101.com/r/9z6nW4/1)
-pub const RE_TABLE: &str = r"(?im)(?P<action>drop|truncate) table (?P<name>[^;]+)";
+pub const RE_TABLE: &str =
+    r"(?im)(?P<action>drop|truncate) table (?P<if_exists>if exists )?(?P<name>[^;]+)";
 /// A regex to match drop column query. [Example](https://regex101.com/r/fM8Csp/1)
 pub const RE_DROP_COL: &str =
     r"(?im)ALTER TABLE (?P<table_name>[^\s\n]+) drop column (?P<col_name>[^\s\n;]+)";
+/// A regex to match drop primary key query like `ALTER TABLE user DROP PRIMARY KEY`.
+pub const RE_DROP_PK: &str = r"(?im)ALTER TABLE (?P<table_name>[^\s\n]+) drop primary key\s*;?\s*$";
+/// A regex to match index queries like `CREATE INDEX ON user (email)` or `DROP INDEX ON user (email)`.
+pub const RE_INDEX: &str = r"(?im)^\s*(?P<action>create|drop) index on (?P<table_name>[^\s\(]+)\s*\(\s*(?P<col_name>[^\s\)]+)\s*\)\s*;?\s*$";
+/// A regex to match rename table query like `ALTER TABLE user RENAME TO member`.
+pub const RE_RENAME_TABLE: &str =
+    r"(?im)ALTER TABLE (?P<table_name>[^\s\n]+) rename to (?P<new_name>[^\s\n;]+)";
+/// A regex to match rename column query like `ALTER TABLE user RENAME COLUMN name TO full_name`.
+pub const RE_RENAME_COL: &str = r"(?im)ALTER TABLE (?P<table_name>[^\s\n]+) rename column (?P<old_name>[^\s\n;]+) to (?P<new_name>[^\s\n;]+)";
 /// A regex to match alter  column query. [Example](https://regex101.com/r/KAcjsB/1)
 pub const RE_ALTER_COL: &str = r"(?im)ALTER TABLE (?P<table_name>[^\s\n]+) alter column (?P<col_name>[^\s\n;]+) (?P<datatype>[^\n;]+)";
 /// A regex to match add column query. [Example](https://regex101.com/r/UoGvGV/1)
 pub const RE_ADD_COL: &str =
     r"(?im)ALTER TABLE (?P<table_name>[^\s\n]+) add (?P<col_name>[^\s\n]+) (?P<datatype>[^\n;]+)";
 /// A regex to match basic select queries with conditions. [Example](https://regex101.com/r/FhdTBh/1)
-pub const RE_SELECT: &str =
-    r"(?im)select (?P<cols>.+) from (?P<table_name>[^\s;\n]+)( where (?P<condition>[^\n;]+))?";
+///
+/// The condition may be followed by `LIMIT <n>` and `OFFSET <n>`.
+pub const RE_SELECT: &str = r"(?i)^\s*select (?P<distinct>distinct )?(?P<cols>.+) from (?P<table_name>[^\s;\n]+)( where (?P<condition>[^\n;]+?))?( order by (?P<order>[^\n;]+?))?( limit (?P<limit>[0-9]+))?( offset (?P<offset>[0-9]+))?\s*;?\s*$";
 /// A regex to match complex insert queries. [Example](https://regex101.com/r/uAZ6Uo/1)
 pub const RE_INSERT: &str =
     r"(?im)INSERT INTO (?P<table_name>[^\s\n;]+)(?P<cols>.+)? values\s?(?P<values>\(.+\))";
 /// A regex to match comma separated values. [Example](https://regex101.com/r/OiSrOW/1)
 pub const RE_COMMA_SEPARATED_VALUES: &str = r"(?im)(?P<value>[^,\(\)\s]+)";
+/// A regex to match grouped select queries like `SELECT country, COUNT(*) FROM users GROUP BY country HAVING COUNT(*) > 1`.
+pub const RE_SELECT_GROUPED: &str = r"(?i)^\s*select (?P<cols>.+) from (?P<table_name>[^\s;\n]+)( where (?P<condition>[^\n;]+?))? group by (?P<group_cols>[^\n;]+?)( having (?P<having>[^\n;]+?))?\s*;?\s*$";
+/// A regex to match an aggregate column like `COUNT(*)` or `AVG(age) AS avg_age`.
+pub const RE_AGGREGATE: &str =
+    r"(?i)^(?P<func>count|sum|avg|min|max)\s*\(\s*(?P<col>[^\s\)]+)\s*\)(\s+as\s+(?P<alias>\S+))?$";
+/// A regex to match a selected column with an optional alias like `name AS full_name` or `name n`.
+pub const RE_SELECT_ITEM: &str = r"(?i)^(?P<col>[^\s,]+)(\s+(as\s+)?(?P<alias>[^\s,]+))?$";
+/// A regex to match a `CASE` expression like `CASE WHEN age >= 18 THEN 'adult' ELSE 'minor' END AS bracket`.
+pub const RE_CASE: &str = r"(?is)^case\s+when\s+(?P<arms>.+?)(\s+else\s+(?P<default>.+?))?\s+end(\s+(as\s+)?(?P<alias>[^\s,]+))?$";
+/// A regex to match a single `ORDER BY` item like `age DESC` or `name`.
+pub const RE_ORDER_CLAUSE: &str = r"(?i)^(?P<col>[^\s,]+)(\s+(?P<direction>asc|desc))?$";
 /// A regex to match insert query values like `(val1, val2), (val1, val2) (val1, val2);[`. [Example](https://regex101.com/r/mJUv6g/1)
 pub const RE_INSERT_VALUES_VALUES: &str = r"(?im)(?P<row>\([^\);]+\))";
 /// A regex to match delete from table queries. [Example](https://regex101.com/r/RQEPGa/1)
 pub const RE_DELETE_FROM_TABLE: &str =
     r"(?im)delete from (?P<table_name>[^\s]+) where (?P<condition>[^\n;]+)";
+/// A regex to match update queries like `UPDATE user SET name = jone, age = 21 WHERE id = 1;`.
+pub const RE_UPDATE: &str = r"(?i)update (?P<table_name>[^\s;]+)\s+set\s+(?P<assignments>[^;]+?)(\s+where\s+(?P<condition>[^;]+?))?\s*;?\s*$";
 /// A regex to match 'SHOW' queries like `SHOW DATABASES` or `SHOW TABLES`. [Example](https://regex101.com/r/bbs4lA/1)
 pub const RE_SHOW_QUERY: &str = r"(?im)SHOW (?P<query>[^\n;]+)";
 /// A regex to extract key values like `lname = "Doe"` or `is_married = false`. [Example](https://regex101.com/r/GeblFE/1)
-pub const RE_KEY_VALUE: &str = r#"(?im)^(?P<key>[^=\s]+)(\s*(?P<operator>[^\s\n;'"0-9]+)\s*)('?"?)(?P<value>[^\s\n=";']+)('?"?)"#;
+///
+/// Quoted values are captured as `quoted` and may contain whitespace, e.g. `name = 'Jone Doe'`.
+/// A trailing `COLLATE NOCASE` makes `=` and `!=` ignore the case.
+pub const RE_KEY_VALUE: &str = r#"(?im)^(?P<key>[^=\s]+)(\s*(?P<operator>not\s+(i?like|regexp)|[^\s\n;'"0-9]+)\s*)((?P<quoted>'(?:[^'\n]|'')*'|"[^"\n]*")|(?P<value>[^\s\n=";']+))(?P<nocase>\s+collate\s+nocase\b)?"#;
+/// A regex to match an unquoted condition value naming a column like `start_date` or `users.id`.
+pub const RE_COL_REF: &str = r"^[A-Za-z_][A-Za-z0-9_]*(\.[A-Za-z_][A-Za-z0-9_]*)?$";
+/// A regex to match subquery conditions like `user_id IN (SELECT id FROM users WHERE active = 'false')`.
+pub const RE_IN_SUBQUERY: &str = r"(?is)^(?P<key>[^\s]+)\s+(?P<operator>not\s+in|in)\s*\(\s*select\s+(?P<cols>.+?)\s+from\s+(?P<table_name>[^\s\)]+)(\s+where\s+(?P<condition>.+?))?\s*\)$";
+/// A regex to match set membership conditions like `status IN ('active', 'trial')` or `id NOT IN (1, 2)`.
+pub const RE_IN_CONDITION: &str =
+    r"(?im)^(?P<key>[^\s]+)\s+(?P<operator>not\s+in|in)\s*\((?P<values>[^\)]*)\)$";
+/// A regex to match range conditions like `price BETWEEN 10 AND 50` or `name NOT BETWEEN 'a' AND 'm'`.
+pub const RE_BETWEEN_CONDITION: &str = r#"(?im)^(?P<key>[^\s]+)\s+(?P<operator>not\s+between|between)\s+(?P<low>'(?:[^']|'')*'|"[^"]*"|[^\s'"]+)\s+and\s+(?P<high>'(?:[^']|'')*'|"[^"]*"|[^\s'"]+)$"#;
+/// A regex to match a JSON path condition like `json_extract(data, '$.user.name') = 'jone'`.
+pub const RE_JSON_CONDITION: &str = r#"(?is)^json_extract\s*\(\s*(?P<key>[^\s,]+)\s*,\s*'(?P<path>\$[^']*)'\s*\)\s*=\s*((?P<quoted>'(?:[^'\n]|'')*')|(?P<value>[^\s'"]+))$"#;
+/// A regex to match a negated condition like `NOT age > 18`.
+pub const RE_NOT_CONDITION: &str = r"(?is)^not\s+(?P<condition>.+)$";
+/// A regex to match an unquoted `NULL` assignment like `email = NULL`.
+pub const RE_NULL_ASSIGNMENT: &str = r"(?i)^(?P<key>[^=\s]+)\s*=\s*null$";
+/// A regex to match null checks like `email IS NULL` or `email IS NOT NULL`.
+pub const RE_NULL_CONDITION: &str = r"(?im)^(?P<key>[^\s]+)\s+is\s+(?P<not>not\s+)?null$";
+/// A regex to match the start of a range condition which still misses its `AND <high>` part.
+pub const RE_BETWEEN_LOW: &str = r#"(?i)\sbetween\s+('(?:[^']|'')*'|"[^"]*"|[^\s'"]+)$"#;
 /// A regex to extract `VARCHAR` size like `VARCHAR(255)`. [Example](https://regex101.com/r/aQHauk/1)
 pub const RE_VARCHAR: &str = r#"(?im)VARCHAR\(?(?P<size>[0-9]+)?\)?"#;
+/// A regex to match binary columns like `BLOB` or `BLOB(1024)`, the size is in bytes.
+pub const RE_BLOB: &str = r#"(?i)^BLOB\s*(\(\s*(?P<size>[0-9]+)\s*\))?$"#;
+/// A regex to match bounded integers like `RANGE(0, 150)` or `RANGE(0, )`, either bound can be left out.
+pub const RE_RANGE: &str = r#"(?i)^RANGE\s*\(\s*(?P<min>-?[0-9]+)?\s*,\s*(?P<max>-?[0-9]+)?\s*\)$"#;
+/// A regex to match decimals like `DECIMAL(2)`, the number of decimal places is required.
+pub const RE_DECIMAL: &str = r#"(?i)^DECIMAL\s*\(\s*(?P<places>[0-9]+)\s*\)$"#;
 /// A regex to match enums. [Example](https://regex101.com/r/RuRnxp/1)
 pub const RE_ENUM: &str = r#"(?im)ENUM\((?P<values>.+)\)"#;
 /// A regex to extract enum values. [Example](https://regex101.com/r/2O8ZbK/1)
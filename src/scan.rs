@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+};
+
+use crate::{
+    query_parser::{Condition, SelectCols},
+    table::{Schema, Table, TableError},
+};
+
+type TableResult<T> = Result<T, TableError>;
+
+/// Lazy iterator over the matching rows of a table, created by [`Table::scan`].
+///
+/// Only the row being read is kept in memory, the data file stays open until the iterator is
+/// dropped. Writes replace the data file instead of changing it in place, so a scan keeps
+/// reading the rows as they were when it started.
+pub struct TableScan {
+    reader: BufReader<File>,
+    schema: Schema,
+    condition: Option<Condition>,
+    cols: SelectCols,
+    started: bool,
+    done: bool,
+}
+
+impl TableScan {
+    pub(crate) fn new(
+        file: File,
+        schema: Schema,
+        condition: Option<Condition>,
+        cols: SelectCols,
+    ) -> Self {
+        Self {
+            reader: BufReader::new(file),
+            schema,
+            condition,
+            cols,
+            started: false,
+            done: false,
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        Ok(match self.reader.read(&mut byte)? {
+            0 => None,
+            _ => Some(byte[0]),
+        })
+    }
+
+    fn expect_byte(&mut self) -> io::Result<u8> {
+        self.next_byte()?
+            .ok_or_else(|| invalid_data("unexpected end of table file"))
+    }
+
+    /// The raw JSON of the next array element, `None` once the closing `]` is reached.
+    fn next_element(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let first = loop {
+            match self.expect_byte()? {
+                byte if byte.is_ascii_whitespace() => continue,
+                b'[' if !self.started => self.started = true,
+                b',' if self.started => continue,
+                b']' if self.started => return Ok(None),
+                byte if self.started => break byte,
+                _ => return Err(invalid_data("expected an array of rows")),
+            }
+        };
+
+        let scalar = !matches!(first, b'{' | b'[' | b'"');
+        let mut element = vec![first];
+        let mut depth = matches!(first, b'{' | b'[') as usize;
+        let mut in_string = first == b'"';
+        let mut escaped = false;
+        while depth > 0 || in_string {
+            let byte = self.expect_byte()?;
+            element.push(byte);
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+            } else {
+                match byte {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => depth -= 1,
+                    _ => {}
+                }
+            }
+        }
+        // Scalars have no closing delimiter, they run until the next separator
+        if scalar {
+            while let Some(&byte) = self.reader.fill_buf()?.first() {
+                if byte == b',' || byte == b']' || byte.is_ascii_whitespace() {
+                    break;
+                }
+                element.push(byte);
+                self.reader.consume(1);
+            }
+        }
+
+        Ok(Some(element))
+    }
+}
+
+impl Iterator for TableScan {
+    type Item = TableResult<HashMap<String, Option<String>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let element = match self.next_element() {
+                Ok(Some(element)) => element,
+                Ok(None) => break,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err.into()));
+                }
+            };
+            // A bad row is reported on its own, the rows after it can still be read
+            let entry = match serde_json::from_slice(&element) {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(err.into())),
+            };
+            if Table::match_query(&self.schema, &self.condition, &entry) {
+                return Some(Ok(Table::project(&self.schema, &self.cols, entry)));
+            }
+        }
+        self.done = true;
+        None
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use crate::{
+        query_parser::{Condition, Operator, SelectCols},
+        table::{
+            tests::{eq, row, users_table, TestDB},
+            Table, TableError,
+        },
+        utils::get_table_path,
+    };
+
+    fn names(table: &Table, condition: Option<Condition>) -> Vec<Option<String>> {
+        table
+            .scan(condition, SelectCols::Cols(vec!["name".into()]))
+            .unwrap()
+            .map(|entry| entry.unwrap()["name"].clone())
+            .collect()
+    }
+
+    #[test]
+    fn scan_filters_and_projects_rows() {
+        let db = TestDB::new("test_scan_rows");
+        let table = users_table(&db);
+
+        assert_eq!(
+            names(&table, Some(eq("age", "30"))),
+            vec![Some("jane".to_string()), Some("doe".to_string())]
+        );
+        let rows = table
+            .scan(
+                Some(Condition::leaf("age", Operator::Lt, "25")),
+                SelectCols::All,
+            )
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![HashMap::from([
+                ("name".to_string(), Some("jone".to_string())),
+                ("age".to_string(), Some("20".to_string())),
+            ])]
+        );
+    }
+
+    #[test]
+    fn scan_keeps_reading_after_bad_row() {
+        let db = TestDB::new("test_scan_bad_row");
+        let table = users_table(&db);
+        fs::write(
+            get_table_path(&table),
+            r#"[{"name":"a \"}\" b","age":"1"}, 42, {"name":null,"age":"2"}]"#,
+        )
+        .unwrap();
+
+        let mut scan = table.scan(None, SelectCols::All).unwrap();
+        assert_eq!(
+            scan.next().unwrap().unwrap()["name"],
+            Some(r#"a "}" b"#.to_string())
+        );
+        assert!(matches!(
+            scan.next(),
+            Some(Err(TableError::SerializationErr(_)))
+        ));
+        assert_eq!(scan.next().unwrap().unwrap()["name"], None);
+        assert!(scan.next().is_none());
+
+        fs::write(get_table_path(&table), r#"[{"name":"a","age":"1"}"#).unwrap();
+        let mut scan = table.scan(None, SelectCols::All).unwrap();
+        assert!(scan.next().unwrap().is_ok());
+        assert!(matches!(scan.next(), Some(Err(TableError::IoErr(_)))));
+        assert!(scan.next().is_none());
+    }
+
+    #[test]
+    fn scan_reads_rows_from_when_it_started() {
+        let db = TestDB::new("test_scan_snapshot");
+        let table = users_table(&db);
+
+        let scan = table
+            .scan(None, SelectCols::Cols(vec!["name".into()]))
+            .unwrap();
+        table
+            .insert(SelectCols::All, vec![row(&["max", "40"])])
+            .unwrap();
+        assert_eq!(scan.count(), 3);
+        assert_eq!(names(&table, None).len(), 4);
+    }
+}
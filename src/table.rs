@@ -1,20 +1,44 @@
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{IgnoredAny, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 use serde_json::json;
-use std::{collections::HashMap, fs, io};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
 use thiserror::Error;
 
 use crate::{
     database::{Database, DatabaseError},
-    query_parser::{Condition, Operator, SelectCols},
-    types::{DataType, DataTypesErr},
-    utils::{get_db_path, get_schema_path, get_table_path},
+    index::Index,
+    lock::{FileLock, LockError},
+    query_parser::{
+        AggFunc, Condition, ConditionValue, JoinKind, Operator, OrderClause, SelectCols,
+        SelectExpr, SelectOptions, SortDirection,
+    },
+    scan::TableScan,
+    types::{CoercionError, DataType, DataTypesErr, Value, NOW},
+    utils::{
+        base64_decoded_len, cached_regex, get_db_path, get_index_path, get_lock_path,
+        get_schema_path, get_seq_path, get_table_path, get_version_path, get_view_path,
+        get_wal_path, json_extract, like_match, regex_match,
+    },
 };
 
-pub type TableEntries = Vec<HashMap<String, String>>;
+/// The table rows, a missing value (`None`) is stored as `null`.
+pub type TableEntries = Vec<HashMap<String, Option<String>>>;
 
 pub struct Table<'a> {
     pub db: &'a str,
     pub table_name: &'a str,
+    /// How long to wait for the table lock, forever if `None`
+    pub lock_timeout: Option<Duration>,
 }
 
 #[derive(Debug, Error)]
@@ -37,18 +61,231 @@ pub enum TableError {
     TypeErr(#[from] DataTypesErr),
     #[error("Column already exist")]
     ColAlreadyExist(String),
+    #[error("Column `{0}` can't be null (row {1})")]
+    NullViolation(String, usize),
+    #[error("Duplicate primary key")]
+    DuplicateKey(String),
+    #[error("Column is auto incremented")]
+    AutoIncrement(String),
+    #[error("Union tables don't have the same columns")]
+    UnionMismatch(String),
+    #[error("Duplicate value in unique column")]
+    UniqueViolation(String, String),
+    #[error("Duplicate column alias `{0}`")]
+    DuplicateAlias(String),
+    #[error("Referenced row doesn't exist")]
+    ForeignKeyViolation(String),
+    #[error("Row is referenced by another table")]
+    ReferencedRow(String),
+    #[error("Table already exists")]
+    TableAlreadyExists(String),
+    #[error("Existing table has a different schema")]
+    SchemaMismatch(String),
+    #[error("Row {0} has value `{1}` that doesn't fit the column type")]
+    InvalidRow(usize, String),
+    #[error("Invalid CSV")]
+    CsvErr(#[from] csv::Error),
+    #[error("Table has no primary key")]
+    NoPrimaryKey(String),
+    #[error("Column is the primary key")]
+    PrimaryKeyCol(String),
+    #[error("Timed out waiting for the table lock")]
+    LockTimeout(String),
+    #[error("Invalid check constraint")]
+    InvalidCheck(String),
+    #[error("Check constraint `{0}` failed (row {1})")]
+    CheckViolation(String, usize),
+    #[error("Check constraint not found")]
+    CheckNotFound(String),
+    #[error("Constraint already exists")]
+    DuplicateConstraint(String),
+    #[error("Constraint not found")]
+    ConstraintNotFound(String),
+    #[error("Column `{0}` is used by constraint `{1}`")]
+    ColInConstraint(String, String),
+    #[error("Index already exists")]
+    IndexAlreadyExists(String),
+    #[error("Index not found")]
+    IndexNotFound(String),
+    #[error("Column `{0}` isn't a boolean")]
+    NotBoolean(String),
+    #[error("Column `{0}` isn't a DATE, DATETIME or TIMESTAMP")]
+    NotTime(String),
+    #[error("Invalid regular expression")]
+    InvalidRegex(String),
+    #[error("Column `{0}` is limited to {1} characters, got {2}")]
+    TooLong(String, usize, usize),
+    #[error("Column `{0}` is limited to {1} bytes, got {2}")]
+    TooLarge(String, usize, usize),
+    #[error("Column `{0}` is a BLOB and can't be compared with `{1}`")]
+    NotOrdered(String, String),
+    #[error("Invalid value for column `{0}`")]
+    CoercionErr(String, #[source] CoercionError),
+    #[error("SUM of column `{0}` overflows a 64-bit integer")]
+    SumOverflow(String),
+}
+
+impl TableError {
+    /// Point a [`TableError::NullViolation`] at the row it happened in.
+    fn at_row(self, row: usize) -> Self {
+        match self {
+            TableError::NullViolation(col, _) => TableError::NullViolation(col, row),
+            e => e,
+        }
+    }
 }
 
 type TableResult<T> = Result<T, TableError>;
 
+/// Settings of a column added to an existing table.
+#[derive(Debug, Default, Clone)]
+pub struct ColOptions {
+    pub nullable: bool,
+    pub unique: bool,
+    /// Value given to the existing rows, required for a `NOT NULL` column of a non empty table
+    pub default: Option<String>,
+    /// Skip checking the existing rows against the constraints, for migrations
+    pub force: bool,
+    /// A check on the column, named `<col_name>_check`
+    pub check: Option<Condition>,
+    /// Set the `DATE`, `DATETIME` or `TIMESTAMP` column to `NOW` on every update that doesn't
+    /// set it
+    pub auto_now: bool,
+}
+
+/// Rows of `table_name` to delete because their `col` references a deleted row.
+#[derive(Debug, Clone)]
+pub(crate) struct Cascade {
+    table_name: String,
+    col: String,
+    values: Vec<String>,
+}
+
+/// A CSV row skipped by [`Table::import_csv`].
+#[derive(Debug)]
+pub struct TableWarning {
+    /// Index of the record in the file, not counting the header
+    pub row: usize,
+    pub error: TableError,
+}
+
+/// What [`Table::insert_returning`] generated for each inserted row.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InsertResult {
+    /// The values of the `SERIAL` columns and of the `UUID` columns set to `GEN_RANDOM_UUID`,
+    /// by column name, one map per row in insert order
+    pub generated: Vec<HashMap<String, String>>,
+}
+
+/// A column as described by [`Table::describe`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub datatype: DataType,
+    pub nullable: bool,
+    pub primary_key: bool,
+    /// Whether the column alone must be unique, multi-column constraints are in
+    /// `unique_constraints`
+    pub unique: bool,
+    pub default: Option<String>,
+    pub foreign_key: Option<ForeignKey>,
+    /// Names of the table checks using the column
+    pub checks: Vec<String>,
+    /// Names of the unique constraints including the column
+    pub unique_constraints: Vec<String>,
+    /// Whether the column has an [`Index`]
+    pub indexed: bool,
+    /// Whether updates set the column to `NOW`
+    pub auto_now: bool,
+}
+
+/// Number of rows [`Table::import_csv`] inserts at once.
+const CSV_BATCH_SIZE: usize = 1000;
+
 impl<'a> Table<'a> {
     pub fn new(db: &'a str, table_name: &'a str) -> TableResult<Self> {
         Database::exists_or_err(db)?;
-        Ok(Self { db, table_name })
+        Ok(Self {
+            db,
+            table_name,
+            lock_timeout: None,
+        })
+    }
+
+    /// Give up with [`TableError::LockTimeout`] after waiting `timeout` for the table lock.
+    pub fn with_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    /// Lock the table against other processes until the returned lock is dropped, shared
+    /// for reading and exclusive for writing.
+    pub(crate) fn lock(&self, shared: bool) -> TableResult<FileLock> {
+        let path = get_lock_path(self);
+        let lock = match shared {
+            true => FileLock::shared(&path, self.lock_timeout),
+            false => FileLock::exclusive(&path, self.lock_timeout),
+        };
+        lock.map_err(|e| match e {
+            LockError::IoError(e) => TableError::IoErr(e),
+            LockError::Timeout => TableError::LockTimeout(self.table_name.to_string()),
+        })
+    }
+
+    /// Whether a table or a view already uses this table's name.
+    fn name_taken(&self) -> bool {
+        get_schema_path(self).exists()
+            || get_table_path(self).exists()
+            || get_view_path(self.db, self.table_name).exists()
     }
 
-    pub fn create(&self, cols: Vec<String>, types: Vec<DataType>) -> TableResult<()> {
-        let schema = json!({ "cols": cols, "types": types });
+    /// Create the table, failing with [`TableError::TableAlreadyExists`] rather than wiping an
+    /// existing one or shadowing a view.
+    pub fn create(&self, schema: Schema) -> TableResult<()> {
+        if self.name_taken() {
+            return Err(TableError::TableAlreadyExists(self.table_name.to_string()));
+        }
+        if schema.cols.len() != schema.types.len() {
+            return Err(TableError::NumberMismatch(format!(
+                "cols = {}, types = {}",
+                schema.cols.len(),
+                schema.types.len()
+            )));
+        }
+        for col in schema.primary_key.iter().chain(&schema.unique) {
+            self.col_exist_or_err(&schema, col)?;
+        }
+        for dtype in &schema.types {
+            dtype.validate()?;
+        }
+        for fk in &schema.foreign_keys {
+            self.col_exist_or_err(&schema, &fk.col)?;
+            let ref_cols = match fk.ref_table == self.table_name {
+                true => schema.cols.clone(),
+                false => Table::new(self.db, &fk.ref_table)?.read_schema()?.cols,
+            };
+            if !ref_cols.contains(&fk.ref_col) {
+                return Err(TableError::ColNotFound(format!(
+                    "{}.{}",
+                    fk.ref_table, fk.ref_col
+                )));
+            }
+        }
+        let schema = schema.normalized();
+        schema.validate_unique_constraints()?;
+        schema.validate_auto_now()?;
+        let mut names = HashSet::new();
+        for check in &schema.checks {
+            if !names.insert(&check.name) {
+                return Err(TableError::InvalidCheck(check.name.clone()));
+            }
+            schema.validate_check(check)?;
+        }
+        for (col, default) in schema.cols.iter().zip(&schema.defaults) {
+            if default.is_some() {
+                schema.validate(col, default.as_ref())?;
+            }
+        }
         let schema = serde_json::to_string_pretty(&schema)?;
 
         Database::exists_or_err(self.db)?;
@@ -61,31 +298,105 @@ impl<'a> Table<'a> {
         Ok(())
     }
 
-    pub fn insert(&self, cols: SelectCols, values: Vec<Vec<String>>) -> TableResult<()> {
-        Database::exists_or_err(self.db)?;
+    /// Create the table unless it exists, an existing table must have the same columns and types.
+    pub fn create_if_not_exists(&self, schema: Schema) -> TableResult<()> {
+        if !self.exist() {
+            return self.create(schema);
+        }
+
+        let existing = self.read_schema()?;
+        if existing.cols != schema.cols || existing.types != schema.types {
+            return Err(TableError::SchemaMismatch(format!(
+                "`{}` has columns {:?} of types {:?}",
+                self.table_name, existing.cols, existing.types
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Insert new rows, schema columns missing from `cols` are set to `None`.
+    ///
+    /// `SERIAL` columns can't be given a value, they're left out of [`SelectCols::All`] and take
+    /// the next value of the table sequence instead.
+    pub fn insert(&self, cols: SelectCols, values: Vec<Vec<Option<String>>>) -> TableResult<()> {
+        self.insert_returning(cols, values).map(|_| ())
+    }
+
+    /// Same as [`Table::insert`], returning the values generated for each row like `SERIAL`
+    /// ids and `GEN_RANDOM_UUID` defaults.
+    pub fn insert_returning(
+        &self,
+        cols: SelectCols,
+        values: Vec<Vec<Option<String>>>,
+    ) -> TableResult<InsertResult> {
+        self.exists_or_err()?;
+        self.recover()?;
+
+        // Held until the rows are written so no other process writes in between
+        let _lock = self.lock(false)?;
+        let schema = self.load_schema()?;
+        let mut all_entries = self.load()?;
+        let old_seq = self.load_seq()?;
+        let mut seq = old_seq;
+        let res = self.insert_entries(&schema, &mut all_entries, &mut seq, cols, values);
+        // The sequence is saved anyway, a failed insert leaves a gap rather than reusing ids
+        if seq != old_seq {
+            self.store_seq(seq)?;
+        }
+        let result = res?;
+
+        self.store(&all_entries)?;
+        Ok(result)
+    }
+
+    /// Validate the new rows and append them to `all_entries`, taking the `SERIAL` values from
+    /// `seq`. `all_entries` is left as it was if any row is rejected.
+    pub(crate) fn insert_entries(
+        &self,
+        schema: &Schema,
+        all_entries: &mut TableEntries,
+        seq: &mut i64,
+        cols: SelectCols,
+        values: Vec<Vec<Option<String>>>,
+    ) -> TableResult<InsertResult> {
+        let serial_cols = schema
+            .cols
+            .iter()
+            .zip(&schema.types)
+            .filter(|(_, dtype)| **dtype == DataType::SERIAL)
+            .map(|(col, _)| col.clone())
+            .collect::<Vec<_>>();
 
-        let schema = self.read_schema()?;
         let cols = match cols {
-            SelectCols::Cols(cols) => cols,
-            SelectCols::All => schema.cols.clone(),
+            SelectCols::Cols(cols) => cols
+                .iter()
+                .map(|item| match item.col() {
+                    Some(col) => Ok(col.to_string()),
+                    None => Err(TableError::ColNotFound(item.name().to_string())),
+                })
+                .collect::<TableResult<_>>()?,
+            // Rows holding every column try to set the serial ones
+            SelectCols::All if values.iter().any(|row| row.len() == schema.cols.len()) => {
+                schema.cols.clone()
+            }
+            SelectCols::All => schema
+                .cols
+                .iter()
+                .filter(|col| !serial_cols.contains(col))
+                .cloned()
+                .collect(),
         };
 
-        let mut col_type_map = HashMap::new();
         for col in &cols {
-            let col_pos = match schema.cols.iter().position(|c| c == col) {
-                Some(pos) => pos,
-                None => return Err(TableError::ColNotFound(col.to_string())),
-            };
-
-            let dtype = match schema.types.get(col_pos) {
-                Some(dtype) => dtype,
-                None => return Err(TableError::ColTypeNotFound(col.to_string())),
-            };
-
-            col_type_map.insert(col, dtype);
+            if serial_cols.contains(col) {
+                return Err(TableError::AutoIncrement(col.clone()));
+            }
+            self.get_col_type(schema, col)?;
         }
 
         let mut new_entries = Vec::new();
+        let mut generated = Vec::new();
         for (idx, row) in values.iter().enumerate() {
             if row.len() != cols.len() {
                 return Err(TableError::NumberMismatch(format!(
@@ -98,246 +409,5753 @@ impl<'a> Table<'a> {
             }
 
             let mut map = HashMap::new();
-            for (col, val) in cols.iter().zip(row) {
-                col_type_map[col].is_valid(val)?;
-                map.insert(col.clone(), val.clone());
+            let mut row_generated = HashMap::new();
+            for (schema_pos, col) in schema.cols.iter().enumerate() {
+                let val = match cols.iter().position(|c| c == col) {
+                    Some(pos) => schema.coerce_value(col, row[pos].clone())?,
+                    None => schema.defaults[schema_pos].clone(),
+                };
+
+                if !serial_cols.contains(col) {
+                    schema
+                        .validate(col, val.as_ref())
+                        .map_err(|e| e.at_row(idx))?;
+                }
+                let is_generated = val
+                    .as_ref()
+                    .is_some_and(|v| schema.types[schema_pos].is_generated(v));
+                let val = schema.normalize(col, val);
+                if let (true, Some(val)) = (is_generated, &val) {
+                    row_generated.insert(col.clone(), val.clone());
+                }
+                map.insert(col.clone(), val);
             }
 
             new_entries.push(map);
+            generated.push(row_generated);
+        }
+
+        for (entry, row_generated) in new_entries.iter_mut().zip(generated.iter_mut()) {
+            for col in &serial_cols {
+                *seq += 1;
+                entry.insert(col.clone(), Some(seq.to_string()));
+                row_generated.insert(col.clone(), seq.to_string());
+            }
+        }
+        for (idx, entry) in new_entries.iter().enumerate() {
+            schema.check_row(entry, idx)?;
         }
 
-        let mut all_entries = self.read()?;
+        let old_len = all_entries.len();
         all_entries.extend(new_entries);
-        println!(
-            "[{}@{}] {:?} entries",
-            self.table_name,
-            self.db,
-            all_entries.len()
-        );
-        self.write(&all_entries)?;
-        Ok(())
+        let res = schema
+            .check_constraints(all_entries)
+            .and_then(|_| self.check_foreign_keys(schema, &all_entries[old_len..], all_entries));
+        if res.is_err() {
+            all_entries.truncate(old_len);
+        }
+        res.map(|_| InsertResult { generated })
     }
 
+    /// Select the rows matching the condition.
+    ///
+    /// Rows are sorted by `options.order` using the column types, `NULL` sorts before any value.
+    /// With `options.distinct` only the first of the rows with the same projected values is kept.
+    /// Then `options.offset` rows are skipped and at most `options.limit` rows are returned.
+    ///
+    /// Columns with an alias are renamed in the returned rows, `options.order` can use either name.
     pub fn select(
         &self,
         cols: SelectCols,
         condition: Option<Condition>,
+        options: SelectOptions,
     ) -> TableResult<TableEntries> {
-        let all_entries = self.read()?;
+        if let Some(alias) = cols.duplicate_alias() {
+            return Err(TableError::DuplicateAlias(alias.to_string()));
+        }
+        let schema = self.read_schema()?;
+        let condition = self.resolve(&schema, condition)?;
+        let order_clauses = options
+            .order
+            .iter()
+            .flatten()
+            .map(|clause| OrderClause::new(cols.source_col(&clause.col), clause.direction))
+            .collect::<Vec<_>>();
+        let order = order_clauses
+            .iter()
+            .map(|clause| Ok((clause, self.get_col_type(&schema, &clause.col)?)))
+            .collect::<TableResult<Vec<_>>>()?;
+        // Unsorted rows are final once matched, so the scan can stop after `offset + limit`
+        let wanted = match (&options.order, options.distinct, options.limit) {
+            (None, false, Some(limit)) => limit.saturating_add(options.offset.unwrap_or(0)),
+            _ => usize::MAX,
+        };
+
+        let mut matched = Vec::new();
+        self.visit_matching_rows(&condition, |entry| {
+            if matched.len() < wanted && Table::match_query(&schema, &condition, &entry) {
+                matched.push(entry);
+            }
+            matched.len() < wanted
+        })?;
+        // `sort_by` is stable so rows with equal keys keep their insertion order
+        if !order.is_empty() {
+            matched.sort_by(|a, b| Table::order_entries(&order, a, b));
+        }
 
-        let entries = all_entries
+        let mut seen = HashSet::new();
+        let entries = matched
             .into_iter()
-            .filter(|e| Table::match_query(&condition, e))
-            .map(|entry| match &cols {
-                SelectCols::All => entry,
-                SelectCols::Cols(selectd_cols) => {
-                    let mut map = HashMap::new();
-                    selectd_cols.into_iter().for_each(|col| {
-                        map.insert(col.clone(), entry.get(col.trim()).unwrap().clone());
-                    });
-                    map
-                }
-            })
+            .map(|entry| Table::project(&schema, &cols, entry))
+            .filter(|entry| !options.distinct || seen.insert(Table::entry_key(entry)))
+            .skip(options.offset.unwrap_or(0))
+            .take(options.limit.unwrap_or(usize::MAX))
             .collect::<Vec<HashMap<_, _>>>();
 
         Ok(entries)
     }
 
-    pub fn delete(&self, condition: Condition) -> TableResult<()> {
-        let all_entries = self.read()?;
-        let condition = Some(condition);
+    /// Like [`Table::select`], but the values are converted to the type of their column with
+    /// [`DataType::to_value`]. `CASE` expressions and the values of unknown columns are text.
+    pub fn select_values(
+        &self,
+        cols: SelectCols,
+        condition: Option<Condition>,
+        options: SelectOptions,
+    ) -> TableResult<Vec<HashMap<String, Value>>> {
+        let schema = self.read_schema()?;
+        let types = match &cols {
+            SelectCols::All => schema
+                .cols
+                .iter()
+                .zip(&schema.types)
+                .map(|(col, dtype)| (col.clone(), dtype))
+                .collect::<HashMap<_, _>>(),
+            SelectCols::Cols(items) => items
+                .iter()
+                .filter_map(|item| match &item.expr {
+                    SelectExpr::Col(col) => {
+                        Some((item.name().to_string(), schema.col_type(col.trim())?))
+                    }
+                    SelectExpr::Case(_) => None,
+                })
+                .collect(),
+        };
 
-        let entries = all_entries
+        let entries = self
+            .select(cols.clone(), condition, options)?
             .into_iter()
-            .filter(|e| !Table::match_query(&condition, e))
-            .collect::<Vec<HashMap<_, _>>>();
+            .map(|entry| {
+                entry
+                    .into_iter()
+                    .map(|(col, raw)| {
+                        let value = match types.get(&col) {
+                            Some(dtype) => dtype.to_value(raw.as_deref()),
+                            None => DataType::TEXT.to_value(raw.as_deref()),
+                        };
+                        (col, value)
+                    })
+                    .collect()
+            })
+            .collect();
 
-        self.write(&entries)?;
-        Ok(())
+        Ok(entries)
     }
 
-    pub fn alter(&self, col_name: &str, datatype: DataType) -> TableResult<()> {
-        // Todo: Update the actual table
-        // Update schema
-        self.exists_or_err()?;
-        let mut schema = self.read_schema()?;
-        let p = schema.cols.iter().position(|c| c == &col_name.to_string());
-
-        match p {
-            None => Err(TableError::ColNotFound(col_name.into())),
-            Some(pos) => match schema.types.get(pos) {
-                None => Err(TableError::ColTypeNotFound(col_name.into())),
-                Some(_) => {
-                    schema.types[pos] = datatype;
-                    self.write_schema(schema)?;
-
-                    Ok(())
-                }
-            },
+    /// Like [`Table::select`] without the options, but the rows are read one at a time as the
+    /// returned iterator advances instead of all at once.
+    ///
+    /// A row that can't be read is returned as an error and the scan goes on with the next one.
+    pub fn scan(&self, condition: Option<Condition>, cols: SelectCols) -> TableResult<TableScan> {
+        if let Some(alias) = cols.duplicate_alias() {
+            return Err(TableError::DuplicateAlias(alias.to_string()));
         }
+        let schema = self.read_schema()?;
+        let condition = self.resolve(&schema, condition)?;
+        self.recover()?;
+        let file = {
+            let _lock = self.lock(true)?;
+            File::open(get_table_path(self))?
+        };
+        Ok(TableScan::new(file, schema, condition, cols))
     }
 
-    pub fn drop(&self) -> TableResult<()> {
-        self.exists_or_err()?;
-
-        let schema = get_schema_path(self);
-        let table = get_table_path(self);
+    /// Select `cols` from this table and `other` and concatenate the rows, full duplicate rows
+    /// are dropped unless `all` is set and the first one wins.
+    ///
+    /// Both tables must have every selected column, with [`SelectCols::All`] they must have the
+    /// same columns.
+    pub fn union(
+        &self,
+        other: &Table,
+        cols: SelectCols,
+        cond_a: Option<Condition>,
+        cond_b: Option<Condition>,
+        all: bool,
+    ) -> TableResult<TableEntries> {
+        let schema_a = self.read_schema()?;
+        let schema_b = other.read_schema()?;
+        let wanted = match &cols {
+            SelectCols::All => schema_a
+                .cols
+                .iter()
+                .chain(&schema_b.cols)
+                .map(|c| c.as_str())
+                .collect::<Vec<_>>(),
+            // Computed values only use the columns of their own table
+            SelectCols::Cols(cols) => cols.iter().filter_map(|item| item.col()).collect(),
+        };
+        let has_col = |schema: &Schema, col: &str| schema.cols.iter().any(|c| c == col);
+        let mut missing = wanted
+            .into_iter()
+            .filter(|c| !(has_col(&schema_a, c) && has_col(&schema_b, c)))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            missing.sort();
+            missing.dedup();
+            return Err(TableError::UnionMismatch(format!(
+                "`{}` and `{}` don't both have columns {}",
+                self.table_name,
+                other.table_name,
+                missing.join(", ")
+            )));
+        }
 
-        fs::remove_file(schema)?;
-        fs::remove_file(table)?;
+        let options = || SelectOptions {
+            distinct: !all,
+            ..Default::default()
+        };
+        let entries = self.select(cols.clone(), cond_a, options())?;
+        let other_entries = other.select(cols, cond_b, options())?;
 
-        Ok(())
+        Ok(table_union(entries, other_entries, !all))
     }
 
-    pub fn truncate(&self) -> Result<(), TableError> {
-        self.write(&vec![])?;
-        Ok(())
+    /// Select `cols` from this table and `other_cols` from `other`, which can be in another
+    /// database, and combine the rows like `UNION`, dropping full duplicate rows.
+    ///
+    /// Both selects must return the same columns, whatever their order, or it fails with
+    /// [`TableError::SchemaMismatch`].
+    pub fn union_with(
+        &self,
+        other: &Table,
+        cols: SelectCols,
+        other_cols: SelectCols,
+    ) -> TableResult<TableEntries> {
+        let names = |table: &Table, cols: &SelectCols| -> TableResult<Vec<String>> {
+            let mut names = match cols {
+                SelectCols::All => table.read_schema()?.cols,
+                SelectCols::Cols(items) => items.iter().map(|i| i.name().to_string()).collect(),
+            };
+            names.sort();
+            Ok(names)
+        };
+        let (names_a, names_b) = (names(self, &cols)?, names(other, &other_cols)?);
+        if names_a != names_b {
+            return Err(TableError::SchemaMismatch(format!(
+                "`{}` selects {} but `{}` selects {}",
+                self.table_name,
+                names_a.join(", "),
+                other.table_name,
+                names_b.join(", ")
+            )));
+        }
+
+        let entries = self.select(cols, None, SelectOptions::default())?;
+        let other_entries = other.select(other_cols, None, SelectOptions::default())?;
+        Ok(table_union(entries, other_entries, true))
     }
 
-    pub fn add_col(&self, col_name: &str, datatype: DataType) -> TableResult<()> {
-        // todo: Every column should be unique
-        // TODO: Add the new column to the data with the default value of this type
-        let mut schema = self.read_schema()?;
+    /// The row whose primary key is `pk_value`, the scan stops at the first match.
+    pub fn get(&self, pk_value: &str) -> TableResult<Option<HashMap<String, Option<String>>>> {
+        let schema = self.read_schema()?;
+        let pk = schema
+            .primary_key
+            .as_ref()
+            .ok_or_else(|| TableError::NoPrimaryKey(self.table_name.to_string()))?;
+        let dtype = self.get_col_type(&schema, pk)?;
 
-        if self.col_exist(&schema, col_name) {
-            Err(TableError::ColAlreadyExist(col_name.into()))
-        } else if schema.cols.len() != schema.types.len() {
-            Err(TableError::NumberMismatch(format!(
-                "cols = {}, types = {}",
-                schema.cols.len(),
-                schema.types.len()
-            )))
-        } else {
-            schema.cols.push(col_name.into());
-            schema.types.push(datatype.clone());
+        let mut found = None;
+        self.visit_rows(|entry| {
+            let matches = entry
+                .get(pk)
+                .and_then(|v| v.as_deref())
+                .is_some_and(|v| dtype.compare(v, pk_value) == Some(Ordering::Equal));
+            if matches {
+                found = Some(entry);
+            }
+            !matches
+        })?;
 
-            let all_entries = self.read()?;
-            let new_entries = all_entries
-                .into_iter()
-                .map(|mut entry| {
-                    entry.insert(col_name.into(), datatype.default());
+        Ok(found)
+    }
 
-                    entry
-                })
-                .collect::<Vec<HashMap<_, _>>>();
+    /// Count the rows matching the condition.
+    pub fn count(&self, condition: Option<Condition>) -> TableResult<usize> {
+        let schema = self.read_schema()?;
+        let condition = self.resolve(&schema, condition)?;
 
-            self.write(&new_entries)?;
-            self.write_schema(schema)?;
-            Ok(())
-        }
+        let mut count = 0;
+        self.visit_matching_rows(&condition, |entry| {
+            if Table::match_query(&schema, &condition, &entry) {
+                count += 1;
+            }
+            true
+        })?;
+
+        Ok(count)
     }
 
-    pub fn remove_col<T: Into<String> + Copy>(&self, col_name: T) -> TableResult<()> {
-        // Todo: Col should be removed from the table
-        let mut schema = self.read_schema()?;
-        let pos = schema.cols.iter().position(|c| c == &col_name.into());
+    /// The columns of the table with their type and constraints, in schema order.
+    ///
+    /// Fails with [`TableError::TableNotFond`] if the table doesn't exist.
+    pub fn describe(&self) -> TableResult<Vec<ColumnInfo>> {
+        let schema = self.read_schema()?;
+        let info = schema
+            .cols
+            .iter()
+            .enumerate()
+            .map(|(pos, col)| ColumnInfo {
+                name: col.clone(),
+                datatype: schema.types[pos].clone(),
+                nullable: schema.nullable[pos],
+                primary_key: schema.primary_key.as_ref() == Some(col),
+                unique: schema.unique.contains(col),
+                default: schema.defaults[pos].clone(),
+                foreign_key: schema
+                    .foreign_keys
+                    .iter()
+                    .find(|fk| &fk.col == col)
+                    .cloned(),
+                checks: schema
+                    .checks
+                    .iter()
+                    .filter(|check| check.condition.keys().contains(&col))
+                    .map(|check| check.name.clone())
+                    .collect(),
+                unique_constraints: schema
+                    .unique_constraints
+                    .iter()
+                    .filter(|constraint| constraint.cols.contains(col))
+                    .map(|constraint| constraint.name.clone())
+                    .collect(),
+                indexed: Index::exists(self, col),
+                auto_now: schema.auto_now.contains(col),
+            })
+            .collect();
 
-        match pos {
-            Some(pos) => {
-                schema.cols.remove(pos);
-                schema.types.remove(pos);
+        Ok(info)
+    }
 
-                debug_assert_eq!(schema.cols.len(), schema.types.len());
-                self.write_schema(schema)?;
-                Ok(())
+    /// The `CREATE TABLE` statement of the table, parsing it gives back the same schema.
+    ///
+    /// Checks are always written as named table constraints so their names are kept.
+    pub fn show_create(&self) -> TableResult<String> {
+        let schema = self.read_schema()?;
+        let mut entries = Vec::new();
+        for (pos, col) in schema.cols.iter().enumerate() {
+            let mut entry = format!("{} {}", col, schema.types[pos].sql_name());
+            if schema.primary_key.as_ref() == Some(col) {
+                entry.push_str(" PRIMARY KEY");
             }
-            None => Err(TableError::ColNotFound(col_name.into())),
+            if !schema.nullable[pos] {
+                entry.push_str(" NOT NULL");
+            }
+            if schema.unique.contains(col) {
+                entry.push_str(" UNIQUE");
+            }
+            if let Some(default) = &schema.defaults[pos] {
+                entry.push_str(&format!(" DEFAULT '{}'", default.replace('\'', "''")));
+            }
+            if schema.auto_now.contains(col) {
+                entry.push_str(" ON UPDATE NOW");
+            }
+            for fk in schema.foreign_keys.iter().filter(|fk| &fk.col == col) {
+                entry.push_str(&format!(" REFERENCES {}({})", fk.ref_table, fk.ref_col));
+                if fk.cascade {
+                    entry.push_str(" ON DELETE CASCADE");
+                }
+            }
+            entries.push(entry);
+        }
+        for constraint in &schema.unique_constraints {
+            entries.push(format!(
+                "CONSTRAINT {} UNIQUE ({})",
+                constraint.name,
+                constraint.cols.join(", ")
+            ));
+        }
+        for check in &schema.checks {
+            entries.push(format!(
+                "CONSTRAINT {} CHECK ({})",
+                check.name, check.condition
+            ));
         }
-    }
 
-    fn read(&self) -> Result<TableEntries, TableError> {
-        self.exists_or_err()?;
-        let table = get_table_path(self);
+        Ok(format!(
+            "CREATE TABLE {} (\n    {}\n);",
+            self.table_name,
+            entries.join(",\n    ")
+        ))
+    }
 
-        let content = fs::read_to_string(table)?;
+    /// Join with `other` on equality of the `on.0` column of this table and the `on.1` column
+    /// of `other`.
+    ///
+    /// Output columns are prefixed with their table name, e.g. `users.id` and `orders.user_id`,
+    /// and both `cols` and `condition` must use the prefixed names. Rows are returned in the
+    /// order of this table, then of `other`. `NULL` values never join.
+    ///
+    /// With [`JoinKind::Left`] the rows of this table without a match are kept once, with every
+    /// column of `other` present and set to `None`. [`JoinKind::Right`] does the same for the
+    /// rows of `other`, which are returned after the matched rows.
+    pub fn join(
+        &self,
+        other: &Table,
+        kind: JoinKind,
+        on: (String, String),
+        cols: SelectCols,
+        condition: Option<Condition>,
+    ) -> TableResult<TableEntries> {
+        let (left_col, right_col) = on;
+        let left_schema = self.read_schema()?;
+        let right_schema = other.read_schema()?;
+        let schema = left_schema
+            .clone()
+            .prefixed(self.table_name)
+            .merge(right_schema.clone().prefixed(other.table_name));
+        let condition = self.resolve(&schema, condition)?;
 
-        Ok(serde_json::from_str(&content)?)
-    }
+        for (table, schema, col) in [
+            (self, &left_schema, &left_col),
+            (other, &right_schema, &right_col),
+        ] {
+            if !table.col_exist(schema, col) {
+                return Err(TableError::ColNotFound(format!(
+                    "{}.{}",
+                    table.table_name, col
+                )));
+            }
+        }
 
-    fn write(&self, entries: &TableEntries) -> TableResult<()> {
-        self.exists_or_err()?;
-        let table = get_table_path(self);
-        let entries = json!(entries);
-        fs::write(table, entries.to_string())?;
-        Ok(())
-    }
+        let left = self.read()?;
+        let right = other.read()?;
 
-    fn read_schema(&self) -> TableResult<Schema> {
-        self.exists_or_err()?;
-        let schema = get_schema_path(self);
+        // Index the smaller side and probe it with the larger one
+        let (build, build_col, probe, probe_col, build_is_left) = if left.len() <= right.len() {
+            (&left, &left_col, &right, &right_col, true)
+        } else {
+            (&right, &right_col, &left, &left_col, false)
+        };
 
-        let content = fs::read_to_string(schema)?;
+        let mut index: HashMap<&String, Vec<usize>> = HashMap::new();
+        for (idx, entry) in build.iter().enumerate() {
+            if let Some(Some(value)) = entry.get(build_col) {
+                index.entry(value).or_default().push(idx);
+            }
+        }
 
-        Ok(serde_json::from_str(&content)?)
-    }
+        let mut pairs = Vec::new();
+        for (probe_idx, entry) in probe.iter().enumerate() {
+            if let Some(Some(value)) = entry.get(probe_col) {
+                for &build_idx in index.get(value).into_iter().flatten() {
+                    pairs.push(match build_is_left {
+                        true => (Some(build_idx), Some(probe_idx)),
+                        false => (Some(probe_idx), Some(build_idx)),
+                    });
+                }
+            }
+        }
 
-    fn write_schema(&self, schema: Schema) -> TableResult<()> {
-        self.exists_or_err()?;
-        let path = get_schema_path(self);
-        let schema = json!(schema);
-        fs::write(path, serde_json::to_string_pretty(&schema)?)?;
-        Ok(())
-    }
+        if kind == JoinKind::Left {
+            let matched: HashSet<_> = pairs.iter().map(|(l, _)| *l).collect();
+            pairs.extend(
+                (0..left.len())
+                    .filter(|l| !matched.contains(&Some(*l)))
+                    .map(|l| (Some(l), None)),
+            );
+        }
+        if kind == JoinKind::Right {
+            let matched: HashSet<_> = pairs.iter().map(|(_, r)| *r).collect();
+            pairs.extend(
+                (0..right.len())
+                    .filter(|r| !matched.contains(&Some(*r)))
+                    .map(|r| (None, Some(r))),
+            );
+        }
+        // Unmatched rows of `other` come last
+        pairs.sort_unstable_by_key(|&(l, r)| (l.is_none(), l, r));
 
-    fn exist(&self) -> bool {
-        let schema = get_schema_path(self);
-        let table = get_table_path(self);
-        schema.exists() && table.exists()
-    }
+        let side = |table: &Table, schema: &Schema, entries: &TableEntries, idx: Option<usize>| {
+            match idx {
+                Some(idx) => Table::prefix_entry(table.table_name, &entries[idx]),
+                None => schema
+                    .cols
+                    .iter()
+                    .map(|col| (format!("{}.{}", table.table_name, col), None))
+                    .collect(),
+            }
+        };
 
-    fn exists_or_err(&self) -> TableResult<()> {
-        Database::exists_or_err(self.db)?;
+        let entries = pairs
+            .into_iter()
+            .map(|(l, r)| {
+                let mut entry = side(self, &left_schema, &left, l);
+                entry.extend(side(other, &right_schema, &right, r));
+                entry
+            })
+            .filter(|e| Table::match_query(&schema, &condition, e))
+            .map(|entry| Table::project(&schema, &cols, entry))
+            .collect();
 
-        if !self.exist() {
-            Err(TableError::TableNotFond(self.table_name.to_string()))
-        } else {
-            Ok(())
-        }
+        Ok(entries)
     }
 
-    fn match_query(condition: &Option<Condition>, entry: &HashMap<String, String>) -> bool {
-        if condition.is_none() {
-            return true;
+    /// Compute an aggregate over the `col` values of the rows matching the condition.
+    ///
+    /// `NULL` values are skipped and the result is `None` when it's undefined, like the `AVG` or
+    /// `MIN` of no values. `SUM` and `AVG` need a numeric column, `MIN` and `MAX` compare with
+    /// the column type so text columns are compared lexicographically.
+    pub fn aggregate(
+        &self,
+        col: &str,
+        func: AggFunc,
+        condition: Option<Condition>,
+    ) -> TableResult<Option<String>> {
+        let schema = self.read_schema()?;
+        let condition = self.resolve(&schema, condition)?;
+        if !(func == AggFunc::Count && col == "*") {
+            self.col_exist_or_err(&schema, col)?;
         }
 
-        let Condition {
-            key,
-            value,
-            operator,
-        } = condition.as_ref().unwrap();
+        let mut rows = Vec::new();
+        self.visit_rows(|entry| {
+            if Table::match_query(&schema, &condition, &entry) {
+                rows.push(entry);
+            }
+            true
+        })?;
 
-        match entry.get(key) {
-            None => false,
-            Some(v) => match operator {
-                Operator::Eq => v == value,
-                Operator::NotEq => v != value,
-                Operator::Gt => v > value,
-                Operator::Lt => v < value,
-                Operator::GtEq => v >= value,
-                Operator::LtEq => v <= value,
-            },
-        }
+        Table::aggregate_rows(&schema, func, col, &rows)
     }
 
-    fn get_col_pos(&self, schema: &Schema, col_name: &str) -> Option<usize> {
-        schema.cols.iter().position(|c| c == col_name)
+    /// Group the rows matching the condition by the distinct combination of their `group_cols`
+    /// values and compute the given aggregates for every group.
+    ///
+    /// Each aggregate is a `(result_col, func, source_col)` triple. Every output row holds the
+    /// group columns and each aggregate under its `result_col`. Groups are returned in the order
+    /// they are first seen, `NULL` group values form their own group and `COUNT(*)` counts every
+    /// row of the group.
+    ///
+    /// Output rows not matching `having` are dropped, it may only refer to the group columns
+    /// and the `result_col`s or it fails with [`TableError::ColNotFound`]. Aggregates are
+    /// compared with their result type, so counts and sums are compared as numbers.
+    pub fn group_by(
+        &self,
+        group_cols: Vec<String>,
+        aggregates: Vec<(String, AggFunc, String)>,
+        condition: Option<Condition>,
+        having: Option<Condition>,
+    ) -> TableResult<TableEntries> {
+        self.grouped(&group_cols, &aggregates, condition, having)
     }
 
-    fn col_exist(&self, schema: &Schema, col_name: &str) -> bool {
-        self.get_col_pos(schema, col_name).is_some()
+    /// Group the rows matching the condition by the value of `group_col` and compute the given
+    /// aggregates for every group.
+    ///
+    /// Every output row holds the group value under `group_col` and each aggregate under its
+    /// [`AggFunc::alias`], e.g. `COUNT(id)`. See [`Table::group_by`].
+    ///
+    /// The `having` condition filters the output rows, so it may only refer to `group_col` and
+    /// the computed aliases. Aggregates are compared as numbers.
+    pub fn select_grouped(
+        &self,
+        group_col: &str,
+        aggs: Vec<(AggFunc, String)>,
+        condition: Option<Condition>,
+        having: Option<Condition>,
+    ) -> TableResult<TableEntries> {
+        let aggregates = aggs
+            .into_iter()
+            .map(|(func, col)| (func.alias(&col), func, col))
+            .collect::<Vec<_>>();
+
+        self.grouped(&[group_col.to_string()], &aggregates, condition, having)
     }
 
-    fn col_exist_or_err(&self, schema: &Schema, col_name: &str) -> TableResult<()> {
-        if !self.col_exist(schema, col_name) {
-            Err(TableError::ColNotFound(col_name.to_string()))
+    /// Shared by [`Table::group_by`] and [`Table::select_grouped`].
+    fn grouped(
+        &self,
+        group_cols: &[String],
+        aggregates: &[(String, AggFunc, String)],
+        condition: Option<Condition>,
+        having: Option<Condition>,
+    ) -> TableResult<TableEntries> {
+        let schema = self.read_schema()?;
+        let condition = self.resolve(&schema, condition)?;
+        let having = self.resolve(&schema, having)?;
+        let mut grouped_schema = Schema::new(vec![], vec![]);
+        for col in group_cols {
+            grouped_schema.cols.push(col.clone());
+            grouped_schema
+                .types
+                .push(self.get_col_type(&schema, col)?.clone());
+            grouped_schema.nullable.push(true);
+        }
+        for (name, func, col) in aggregates {
+            let dtype = match func {
+                AggFunc::Count => DataType::INT,
+                AggFunc::Avg => DataType::FLOAT,
+                _ => self.get_col_type(&schema, col)?.clone(),
+            };
+            if col != "*" {
+                self.col_exist_or_err(&schema, col)?;
+            }
+
+            grouped_schema.cols.push(name.clone());
+            grouped_schema.types.push(dtype);
+            grouped_schema.nullable.push(true);
+        }
+
+        if let Some(having) = &having {
+            for key in having.keys() {
+                self.col_exist_or_err(&grouped_schema, key)?;
+            }
+        }
+
+        let mut groups: Vec<(Vec<Option<String>>, TableEntries)> = Vec::new();
+        let mut group_pos = HashMap::new();
+        self.visit_rows(|entry| {
+            if Table::match_query(&schema, &condition, &entry) {
+                let key = group_cols
+                    .iter()
+                    .map(|col| entry.get(col).cloned().flatten())
+                    .collect::<Vec<_>>();
+                let pos = *group_pos.entry(key.clone()).or_insert_with(|| {
+                    groups.push((key, Vec::new()));
+                    groups.len() - 1
+                });
+                groups[pos].1.push(entry);
+            }
+            true
+        })?;
+
+        let mut entries = Vec::new();
+        for (key, rows) in groups {
+            let mut map: HashMap<_, _> = group_cols.iter().cloned().zip(key).collect();
+            for (name, func, col) in aggregates {
+                let value = Table::aggregate_rows(&schema, *func, col, &rows)?;
+                map.insert(name.clone(), value);
+            }
+
+            if Table::match_query(&grouped_schema, &having, &map) {
+                entries.push(map);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Delete the rows matching the condition.
+    ///
+    /// Rows referenced by a foreign key fail with [`TableError::ReferencedRow`], unless the key
+    /// cascades and the referencing rows are deleted too.
+    pub fn delete(&self, condition: Condition) -> TableResult<()> {
+        // Subqueries may read this table, so they're run before it's locked
+        let condition = self.resolve_condition(&self.read_schema()?, condition)?;
+        self.recover()?;
+
+        let cascades = {
+            let _lock = self.lock(false)?;
+            let schema = self.load_schema()?;
+            let (entries, cascades) = self.delete_entries(&schema, self.load()?, condition)?;
+            self.store(&entries)?;
+            cascades
+        };
+        self.run_cascades(cascades)
+    }
+
+    /// Remove the rows matching the condition from `all_entries` and return the remaining rows
+    /// along with the deletes to cascade to the referencing tables.
+    pub(crate) fn delete_entries(
+        &self,
+        schema: &Schema,
+        all_entries: TableEntries,
+        condition: Condition,
+    ) -> TableResult<(TableEntries, Vec<Cascade>)> {
+        let condition = self.resolve(schema, Some(condition))?;
+
+        let (deleted, entries): (Vec<_>, Vec<_>) = all_entries
+            .into_iter()
+            .partition(|e| Table::match_query(schema, &condition, e));
+        let mut cascades = Vec::new();
+        for table_name in Database::get_db_tables(self.db)? {
+            let table = Table::new(self.db, &table_name)?;
+            let is_self = table_name == self.table_name;
+            let foreign_keys = match is_self {
+                true => schema.foreign_keys.clone(),
+                false => table.read_schema()?.foreign_keys,
+            };
+            for fk in foreign_keys {
+                if fk.ref_table != self.table_name {
+                    continue;
+                }
+
+                let remaining = Table::col_values(&entries, &fk.ref_col);
+                let removed = Table::col_values(&deleted, &fk.ref_col)
+                    .into_iter()
+                    .filter(|v| !remaining.contains(v))
+                    .collect::<HashSet<_>>();
+                let rows = match is_self {
+                    true => entries.clone(),
+                    false => table.read()?,
+                };
+                let referenced = Table::col_values(&rows, &fk.col)
+                    .into_iter()
+                    .any(|v| removed.contains(v));
+
+                if !referenced {
+                    continue;
+                } else if !fk.cascade {
+                    return Err(TableError::ReferencedRow(format!(
+                        "{}.{} references {}.{}",
+                        table_name, fk.col, fk.ref_table, fk.ref_col
+                    )));
+                }
+                let values = removed.into_iter().cloned().collect::<Vec<_>>();
+                cascades.push(Cascade {
+                    table_name: table_name.clone(),
+                    col: fk.col,
+                    values,
+                });
+            }
+        }
+
+        Ok((entries, cascades))
+    }
+
+    /// Delete the rows referencing deleted rows, once the deletes that caused them are written.
+    pub(crate) fn run_cascades(&self, cascades: Vec<Cascade>) -> TableResult<()> {
+        for cascade in cascades {
+            let condition = Condition::leaf(&cascade.col, Operator::In, cascade.values);
+            Table::new(self.db, &cascade.table_name)?.delete(condition)?;
+        }
+        Ok(())
+    }
+
+    /// Set the given columns on every row matching the condition and return the number of
+    /// updated rows. A `None` condition updates every row.
+    ///
+    /// Every value is validated against its column before anything is written.
+    pub fn update(
+        &self,
+        set: HashMap<String, Option<String>>,
+        condition: Option<Condition>,
+    ) -> TableResult<usize> {
+        let condition = self.resolve(&self.read_schema()?, condition)?;
+        self.recover()?;
+
+        let _lock = self.lock(false)?;
+        let schema = self.load_schema()?;
+        let mut all_entries = self.load()?;
+        let updated = self.update_entries(&schema, &mut all_entries, set, condition)?;
+
+        self.store(&all_entries)?;
+        Ok(updated)
+    }
+
+    /// Flip the boolean column on every row matching the condition and return the number of
+    /// flipped values, `NULL`s are left as they are. A `None` condition matches every row.
+    pub fn toggle_bool(&self, col_name: &str, condition: Option<Condition>) -> TableResult<usize> {
+        let condition = self.resolve(&self.read_schema()?, condition)?;
+        self.recover()?;
+
+        let _lock = self.lock(false)?;
+        let schema = self.load_schema()?;
+        if !self.get_col_type(&schema, col_name)?.is_bool() {
+            return Err(TableError::NotBoolean(col_name.to_string()));
+        }
+        let mut all_entries = self.load()?;
+        let mut toggled = 0;
+        for (idx, entry) in all_entries.iter_mut().enumerate() {
+            if !Table::match_query(&schema, &condition, entry) {
+                continue;
+            }
+            let Some(Some(value)) = entry.get_mut(col_name) else {
+                continue;
+            };
+            if value.is_empty() {
+                continue;
+            }
+            *value = (!value.eq_ignore_ascii_case("true")).to_string();
+            toggled += 1;
+            // Only rows whose value flipped count as updated
+            schema.touch(entry, &[&col_name.to_string()]);
+            schema.check_row(entry, idx)?;
+        }
+        if toggled == 0 {
+            return Ok(0);
+        }
+        schema.check_constraints(&all_entries)?;
+
+        self.store(&all_entries)?;
+        Ok(toggled)
+    }
+
+    /// Apply the update to `all_entries` and return the number of updated rows, on error
+    /// `all_entries` may be partly updated.
+    pub(crate) fn update_entries(
+        &self,
+        schema: &Schema,
+        all_entries: &mut TableEntries,
+        set: HashMap<String, Option<String>>,
+        condition: Option<Condition>,
+    ) -> TableResult<usize> {
+        let condition = self.resolve(schema, condition)?;
+
+        for col in set.keys() {
+            self.col_exist_or_err(schema, col)?;
+        }
+        let set = set
+            .into_iter()
+            .map(|(col, val)| Ok((col.clone(), schema.coerce_value(&col, val)?)))
+            .collect::<TableResult<HashMap<_, _>>>()?;
+
+        let mut updated = 0;
+        for (idx, entry) in all_entries.iter_mut().enumerate() {
+            if !Table::match_query(schema, &condition, entry) {
+                continue;
+            }
+            for (col, val) in &set {
+                schema
+                    .validate(col, val.as_ref())
+                    .map_err(|e| e.at_row(idx))?;
+                entry.insert(col.clone(), schema.normalize(col, val.clone()));
+            }
+            schema.touch(entry, &set.keys().collect::<Vec<_>>());
+            schema.check_row(entry, idx)?;
+            updated += 1;
+        }
+
+        schema.check_constraints(all_entries)?;
+        if schema
+            .foreign_keys
+            .iter()
+            .any(|fk| set.contains_key(&fk.col))
+        {
+            self.check_foreign_keys(schema, all_entries, all_entries)?;
+        }
+        Ok(updated)
+    }
+
+    /// Change the type of a column and convert its existing values. Fails with the first row
+    /// that can't be converted, or replaces those values with the type default when `force`
+    /// is set.
+    pub fn alter(&self, col_name: &str, datatype: DataType, force: bool) -> TableResult<()> {
+        self.exists_or_err()?;
+        datatype.validate()?;
+        let mut schema = self.read_schema()?;
+        let p = schema.cols.iter().position(|c| c == &col_name.to_string());
+
+        match p {
+            None => Err(TableError::ColNotFound(col_name.into())),
+            Some(pos) => match schema.types.get(pos) {
+                None => Err(TableError::ColTypeNotFound(col_name.into())),
+                Some(_) => {
+                    let mut entries = self.read()?;
+                    for (i, entry) in entries.iter_mut().enumerate() {
+                        let Some(Some(value)) = entry.get_mut(col_name) else {
+                            continue;
+                        };
+                        if value.is_empty() {
+                            continue;
+                        }
+                        *value = match datatype.convert(value) {
+                            Ok(converted) => converted,
+                            Err(_) if force => datatype.default(),
+                            Err(_) => return Err(TableError::InvalidRow(i, value.clone())),
+                        };
+                    }
+
+                    schema.types[pos] = datatype;
+                    for (i, entry) in entries.iter().enumerate() {
+                        schema
+                            .validate(col_name, entry.get(col_name).and_then(|v| v.as_ref()))
+                            .map_err(|e| e.at_row(i))?;
+                        schema.check_row(entry, i)?;
+                    }
+                    schema.check_constraints(&entries)?;
+                    self.replace_files(&entries, &schema)?;
+
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    pub fn drop(&self) -> TableResult<()> {
+        self.exists_or_err()?;
+
+        let schema = get_schema_path(self);
+        let table = get_table_path(self);
+
+        {
+            let _lock = self.lock(false)?;
+            for col in self.load_schema()?.cols {
+                if Index::exists(self, &col) {
+                    fs::remove_file(get_index_path(self, &col))?;
+                }
+            }
+            fs::remove_file(schema)?;
+            fs::remove_file(table)?;
+            for path in [
+                get_seq_path(self),
+                get_version_path(self),
+                get_wal_path(self),
+            ] {
+                if path.exists() {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        let _ = fs::remove_file(get_lock_path(self));
+
+        Ok(())
+    }
+
+    /// Copy the schema, rows and sequence of the table to `dest_table_name` in `dest_db`.
+    ///
+    /// The source is read under a single lock so the copy is a consistent snapshot. Everything
+    /// is written to temporary files first and a failed copy removes what it wrote, so it never
+    /// leaves a half written destination table.
+    pub fn copy_to(&self, dest_db: &str, dest_table_name: &str) -> TableResult<()> {
+        self.exists_or_err()?;
+        self.recover()?;
+        let (schema, entries, seq) = {
+            let _lock = self.lock(true)?;
+            (self.load_schema()?, self.load()?, self.load_seq()?)
+        };
+
+        let dest = Table::new(dest_db, dest_table_name)?;
+        if dest.name_taken() {
+            return Err(TableError::TableAlreadyExists(dest_table_name.to_string()));
+        }
+
+        // The sequence goes last, a stray one would be picked up by the next table of that name
+        let res = dest
+            .replace_files(&entries, &schema)
+            .and_then(|_| match seq > 0 {
+                true => dest.write_seq(seq),
+                false => Ok(()),
+            });
+        if res.is_err() {
+            let (table, schema, seq) = (
+                get_table_path(&dest),
+                get_schema_path(&dest),
+                get_seq_path(&dest),
+            );
+            for path in [
+                table.with_extension("json.tmp"),
+                schema.with_extension("json.tmp"),
+                seq.with_extension("seq.tmp"),
+                table,
+                schema,
+                seq,
+            ] {
+                let _ = fs::remove_file(path);
+            }
+        }
+        res
+    }
+
+    /// Insert the rows of a CSV file and return how many were imported along with the rows
+    /// that were skipped because a value doesn't fit its column or breaks a constraint.
+    ///
+    /// With `has_header` the first record names the columns, otherwise the values follow the
+    /// order of [`SelectCols::All`]. Empty cells are `NULL`. The imported rows are written
+    /// once at the end, so an error leaves the table as it was.
+    pub fn import_csv(
+        &self,
+        path: &Path,
+        has_header: bool,
+        delimiter: u8,
+    ) -> TableResult<(usize, Vec<TableWarning>)> {
+        self.exists_or_err()?;
+        self.recover()?;
+        let _lock = self.lock(false)?;
+        let schema = self.load_schema()?;
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(has_header)
+            .delimiter(delimiter)
+            .from_path(path)?;
+
+        let (cols, col_names) = match has_header {
+            true => {
+                let names = reader
+                    .headers()?
+                    .iter()
+                    .map(|h| h.trim().to_string())
+                    .collect::<Vec<_>>();
+                for name in &names {
+                    self.col_exist_or_err(&schema, name)?;
+                }
+                let cols = SelectCols::Cols(names.iter().map(|n| n.as_str().into()).collect());
+                (cols, names)
+            }
+            false => {
+                let names = schema
+                    .cols
+                    .iter()
+                    .zip(&schema.types)
+                    .filter(|(_, dtype)| **dtype != DataType::SERIAL)
+                    .map(|(col, _)| col.clone())
+                    .collect();
+                (SelectCols::All, names)
+            }
+        };
+
+        let mut all_entries = self.load()?;
+        let old_seq = self.load_seq()?;
+        let mut seq = old_seq;
+        let mut imported = 0;
+        let mut warnings = Vec::new();
+        let mut batch = Vec::new();
+        for (idx, record) in reader.records().enumerate() {
+            let values = record?
+                .iter()
+                .map(|v| (!v.is_empty()).then(|| v.to_string()))
+                .collect::<Vec<_>>();
+
+            let invalid = col_names
+                .iter()
+                .zip(&values)
+                .find_map(|(col, val)| schema.validate(col, val.as_ref()).err());
+            match invalid {
+                Some(error) => warnings.push(TableWarning {
+                    row: idx,
+                    error: error.at_row(idx),
+                }),
+                None => batch.push((idx, values)),
+            }
+
+            if batch.len() == CSV_BATCH_SIZE {
+                let batch = std::mem::take(&mut batch);
+                imported += self.import_batch(
+                    &schema,
+                    &mut all_entries,
+                    &mut seq,
+                    &cols,
+                    batch,
+                    &mut warnings,
+                );
+            }
+        }
+        imported += self.import_batch(
+            &schema,
+            &mut all_entries,
+            &mut seq,
+            &cols,
+            batch,
+            &mut warnings,
+        );
+
+        if seq != old_seq {
+            self.store_seq(seq)?;
+        }
+        if imported > 0 {
+            self.store(&all_entries)?;
+        }
+        Ok((imported, warnings))
+    }
+
+    /// Append a batch of `(record index, values)` CSV rows to `all_entries` and return how
+    /// many were added. A rejected batch is retried row by row so only the rows at fault are
+    /// reported in `warnings`.
+    fn import_batch(
+        &self,
+        schema: &Schema,
+        all_entries: &mut TableEntries,
+        seq: &mut i64,
+        cols: &SelectCols,
+        batch: Vec<(usize, Vec<Option<String>>)>,
+        warnings: &mut Vec<TableWarning>,
+    ) -> usize {
+        let start_seq = *seq;
+        let values = batch.iter().map(|(_, values)| values.clone()).collect();
+        if self
+            .insert_entries(schema, all_entries, seq, cols.clone(), values)
+            .is_ok()
+        {
+            return batch.len();
+        }
+
+        // Nothing was written yet, so the rejected rows don't use up `SERIAL` values
+        *seq = start_seq;
+        let mut imported = 0;
+        for (row, values) in batch {
+            let row_seq = *seq;
+            match self.insert_entries(schema, all_entries, seq, cols.clone(), vec![values]) {
+                Ok(_) => imported += 1,
+                Err(error) => {
+                    *seq = row_seq;
+                    warnings.push(TableWarning {
+                        row,
+                        error: error.at_row(row),
+                    });
+                }
+            }
+        }
+        imported
+    }
+
+    /// Write every row to a CSV file with the values in schema column order, `NULL` and
+    /// missing values are written as empty cells.
+    pub fn export_csv(&self, path: &Path, include_header: bool, delimiter: u8) -> TableResult<()> {
+        let schema = self.read_schema()?;
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_path(path)?;
+
+        if include_header {
+            writer.write_record(&schema.cols)?;
+        }
+        for entry in self.read()? {
+            writer.write_record(schema.cols.iter().map(|col| {
+                entry
+                    .get(col)
+                    .and_then(|v| v.as_deref())
+                    .unwrap_or_default()
+            }))?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Drop the table if it exists and return whether it did.
+    pub fn drop_if_exists(&self) -> TableResult<bool> {
+        match self.drop() {
+            Ok(()) => Ok(true),
+            Err(TableError::TableNotFond(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Remove every row, `SERIAL` columns restart from 1 if `reset_seq` is set.
+    pub fn truncate(&self, reset_seq: bool) -> Result<(), TableError> {
+        self.write(&vec![])?;
+        if reset_seq {
+            self.write_seq(0)?;
+        }
+        Ok(())
+    }
+
+    /// Add a new column. Existing rows get the column default if it has one and `None` if it's
+    /// nullable. A `NOT NULL` column without a default fails with [`TableError::NullViolation`]
+    /// unless the table is empty or `force` is set, then rows get the type default.
+    pub fn add_col(
+        &self,
+        col_name: &str,
+        datatype: DataType,
+        options: ColOptions,
+    ) -> TableResult<()> {
+        let mut schema = self.read_schema()?;
+        datatype.validate()?;
+
+        if self.col_exist(&schema, col_name) {
+            Err(TableError::ColAlreadyExist(col_name.into()))
+        } else if schema.cols.len() != schema.types.len() {
+            Err(TableError::NumberMismatch(format!(
+                "cols = {}, types = {}",
+                schema.cols.len(),
+                schema.types.len()
+            )))
         } else {
-            Ok(())
+            schema.cols.push(col_name.into());
+            schema.types.push(datatype.clone());
+            schema.nullable.push(options.nullable);
+            schema.defaults.push(options.default.clone());
+            if options.unique {
+                schema.unique.push(col_name.into());
+            }
+            if options.auto_now {
+                schema.auto_now.push(col_name.into());
+                schema.validate_auto_now()?;
+            }
+            if let Some(condition) = &options.check {
+                let check = Check {
+                    name: format!("{}_check", col_name),
+                    condition: condition.clone(),
+                };
+                schema.validate_check(&check)?;
+                schema.checks.push(check);
+            }
+
+            if let Some(default) = &options.default {
+                datatype.is_valid(default)?;
+            }
+            let all_entries = self.read()?;
+            let default = match (&options.default, options.nullable) {
+                (Some(default), _) => Some(datatype.normalize(default)),
+                (None, true) => None,
+                (None, false) if all_entries.is_empty() || options.force => {
+                    Some(datatype.default())
+                }
+                (None, false) => return Err(TableError::NullViolation(col_name.into(), 0)),
+            };
+            let new_entries = all_entries
+                .into_iter()
+                .map(|mut entry| {
+                    entry.insert(col_name.into(), default.clone());
+
+                    entry
+                })
+                .collect::<Vec<HashMap<_, _>>>();
+
+            if !options.force {
+                schema.check_constraints(&new_entries)?;
+                for (idx, entry) in new_entries.iter().enumerate() {
+                    schema.check_row(entry, idx)?;
+                }
+            }
+            self.replace_files(&new_entries, &schema)
         }
     }
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Schema {
-    cols: Vec<String>,
-    types: Vec<DataType>,
+    /// Add a check constraint, failing if an existing row doesn't pass it unless
+    /// `skip_validation` is set.
+    pub fn add_check(&self, check: Check, skip_validation: bool) -> TableResult<()> {
+        let mut schema = self.read_schema()?;
+        if schema.checks.iter().any(|c| c.name == check.name) {
+            return Err(TableError::InvalidCheck(check.name));
+        }
+        schema.validate_check(&check)?;
+        schema.checks.push(check);
+
+        if !skip_validation {
+            for (idx, entry) in self.read()?.iter().enumerate() {
+                schema.check_row(entry, idx)?;
+            }
+        }
+        self.write_schema(schema)
+    }
+
+    /// Turn value coercion on or off for later inserts and updates, see [`DataType::coerce`].
+    pub fn set_coerce(&self, coerce: bool) -> TableResult<()> {
+        let mut schema = self.read_schema()?;
+        schema.coerce = coerce;
+        self.write_schema(schema)
+    }
+
+    /// Make the combined values of `cols` unique, failing if existing rows already share them.
+    pub fn add_unique_constraint(&self, name: &str, cols: &[&str]) -> TableResult<()> {
+        let mut schema = self.read_schema()?;
+        schema.unique_constraints.push(UniqueConstraint {
+            name: name.to_string(),
+            cols: cols.iter().map(|c| c.to_string()).collect(),
+        });
+        schema.validate_unique_constraints()?;
+        schema.check_constraints(&self.read()?)?;
+        self.write_schema(schema)
+    }
+
+    /// Remove the unique constraint `name`.
+    pub fn drop_unique_constraint(&self, name: &str) -> TableResult<()> {
+        let mut schema = self.read_schema()?;
+        let pos = schema
+            .unique_constraints
+            .iter()
+            .position(|c| c.name == name)
+            .ok_or_else(|| TableError::ConstraintNotFound(name.to_string()))?;
+        schema.unique_constraints.remove(pos);
+        self.write_schema(schema)
+    }
+
+    /// Remove the check constraint `name`.
+    pub fn drop_check(&self, name: &str) -> TableResult<()> {
+        let mut schema = self.read_schema()?;
+        let pos = schema
+            .checks
+            .iter()
+            .position(|c| c.name == name)
+            .ok_or_else(|| TableError::CheckNotFound(name.to_string()))?;
+        schema.checks.remove(pos);
+        self.write_schema(schema)
+    }
+
+    /// Rename the table files, foreign keys referencing the table are updated afterwards.
+    ///
+    /// The files are moved one by one and moved back if any of them fails.
+    pub fn rename(&mut self, new_name: &'a str) -> TableResult<()> {
+        self.exists_or_err()?;
+        self.recover()?;
+        let old = Table::new(self.db, self.table_name)?;
+        let new = Table::new(self.db, new_name)?;
+        if new.name_taken() {
+            return Err(TableError::TableAlreadyExists(new_name.to_string()));
+        }
+
+        let mut moves = vec![
+            (get_table_path(&old), get_table_path(&new)),
+            (get_schema_path(&old), get_schema_path(&new)),
+        ];
+        if get_seq_path(&old).exists() {
+            moves.push((get_seq_path(&old), get_seq_path(&new)));
+        }
+        if get_version_path(&old).exists() {
+            moves.push((get_version_path(&old), get_version_path(&new)));
+        }
+        for col in self.read_schema()?.cols {
+            if Index::exists(&old, &col) {
+                moves.push((get_index_path(&old, &col), get_index_path(&new, &col)));
+            }
+        }
+        for (done, (from, to)) in moves.iter().enumerate() {
+            if let Err(e) = fs::rename(from, to) {
+                // Move the renamed files back so the table is never half renamed
+                for (from, to) in &moves[..done] {
+                    let _ = fs::rename(to, from);
+                }
+                return Err(e.into());
+            }
+        }
+        self.table_name = new_name;
+
+        self.update_foreign_keys(|fk| match fk.ref_table == old.table_name {
+            true => {
+                fk.ref_table = new_name.to_string();
+                true
+            }
+            false => false,
+        })
+    }
+
+    /// Rename a column, keeping its values and constraints.
+    ///
+    /// The rows and schema are written to temporary files first and then moved in place, foreign
+    /// keys of other tables referencing the column are updated afterwards.
+    pub fn rename_col(&self, old_name: &str, new_name: &str) -> TableResult<()> {
+        let mut schema = self.read_schema()?;
+        self.col_exist_or_err(&schema, old_name)?;
+        if self.col_exist(&schema, new_name) {
+            return Err(TableError::ColAlreadyExist(new_name.into()));
+        }
+
+        let rename = |col: &mut String| {
+            if col == old_name {
+                *col = new_name.to_string();
+            }
+        };
+        schema.cols.iter_mut().for_each(rename);
+        schema.primary_key.iter_mut().for_each(rename);
+        schema.unique.iter_mut().for_each(rename);
+        schema.auto_now.iter_mut().for_each(rename);
+        for constraint in schema.unique_constraints.iter_mut() {
+            constraint.cols.iter_mut().for_each(rename);
+        }
+        for check in schema.checks.iter_mut() {
+            check.condition.rename_col(old_name, new_name);
+        }
+        for fk in schema.foreign_keys.iter_mut() {
+            rename(&mut fk.col);
+            if fk.ref_table == self.table_name {
+                rename(&mut fk.ref_col);
+            }
+        }
+
+        let entries = self
+            .read()?
+            .into_iter()
+            .map(|mut entry| {
+                if let Some(value) = entry.remove(old_name) {
+                    entry.insert(new_name.to_string(), value);
+                }
+                entry
+            })
+            .collect::<Vec<_>>();
+
+        self.replace_files(&entries, &schema)?;
+        // Moved once the schema uses the new name, the rows keep their order so it's still valid
+        if Index::exists(self, old_name) {
+            let old_index = get_index_path(self, old_name);
+            if let Err(e) = fs::rename(&old_index, get_index_path(self, new_name)) {
+                // A later column reusing the old name mustn't pick up this index
+                let _ = fs::remove_file(old_index);
+                return Err(e.into());
+            }
+        }
+
+        self.update_foreign_keys(|fk| {
+            match fk.ref_table == self.table_name && fk.ref_col == old_name {
+                true => {
+                    fk.ref_col = new_name.to_string();
+                    true
+                }
+                false => false,
+            }
+        })
+    }
+
+    /// Build an [`Index`] on `col`, kept up to date by every write to the table.
+    pub fn create_index(&self, col: &str) -> TableResult<()> {
+        Index::create(self, col)
+    }
+
+    pub fn drop_index(&self, col: &str) -> TableResult<()> {
+        self.exists_or_err()?;
+        Index::drop(self, col)
+    }
+
+    /// Remove the primary key designation, the column and its values are kept.
+    pub fn drop_primary_key(&self) -> TableResult<()> {
+        let mut schema = self.read_schema()?;
+        if schema.primary_key.take().is_none() {
+            return Err(TableError::NoPrimaryKey(self.table_name.to_string()));
+        }
+        self.write_schema(schema)
+    }
+
+    /// Remove a column from the schema, the primary key column can't be removed until
+    /// [`Table::drop_primary_key`] is called.
+    pub fn remove_col<T: Into<String> + Copy>(&self, col_name: T) -> TableResult<()> {
+        // Todo: Col should be removed from the table
+        let mut schema = self.read_schema()?;
+        let pos = schema.cols.iter().position(|c| c == &col_name.into());
+
+        match pos {
+            Some(pos) => {
+                if schema.primary_key.as_ref() == Some(&schema.cols[pos]) {
+                    return Err(TableError::PrimaryKeyCol(schema.cols[pos].clone()));
+                }
+                if let Some(constraint) = schema
+                    .unique_constraints
+                    .iter()
+                    .find(|c| c.cols.contains(&schema.cols[pos]))
+                {
+                    return Err(TableError::ColInConstraint(
+                        schema.cols[pos].clone(),
+                        constraint.name.clone(),
+                    ));
+                }
+                let col = schema.cols.remove(pos);
+                schema.types.remove(pos);
+                schema.nullable.remove(pos);
+                schema.defaults.remove(pos);
+                schema.unique.retain(|c| c != &col);
+                schema.auto_now.retain(|c| c != &col);
+                schema.foreign_keys.retain(|fk| fk.col != col);
+                schema
+                    .checks
+                    .retain(|check| !check.condition.keys().contains(&&col));
+
+                debug_assert_eq!(schema.cols.len(), schema.types.len());
+                self.write_schema(schema)?;
+                if Index::exists(self, &col) {
+                    fs::remove_file(get_index_path(self, &col))?;
+                }
+                Ok(())
+            }
+            None => Err(TableError::ColNotFound(col_name.into())),
+        }
+    }
+
+    pub(crate) fn read(&self) -> Result<TableEntries, TableError> {
+        self.exists_or_err()?;
+        self.recover()?;
+        let _lock = self.lock(true)?;
+        self.load()
+    }
+
+    /// Read the rows without locking the table, for callers already holding the lock.
+    pub(crate) fn load(&self) -> TableResult<TableEntries> {
+        let content = fs::read_to_string(get_table_path(self))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Deserialize the rows one by one until `visit` returns `false`, the remaining rows are
+    /// skipped without being built.
+    fn visit_rows<F>(&self, visit: F) -> TableResult<()>
+    where
+        F: FnMut(HashMap<String, Option<String>>) -> bool,
+    {
+        self.visit_matching_rows(&None, visit)
+    }
+
+    /// Like [`Table::visit_rows`], but when an index applies to `condition` only the rows it
+    /// points at are built. `visit` still has to match them.
+    fn visit_matching_rows<F>(&self, condition: &Option<Condition>, visit: F) -> TableResult<()>
+    where
+        F: FnMut(HashMap<String, Option<String>>) -> bool,
+    {
+        struct ScanVisitor<F> {
+            visit: F,
+            positions: Option<Vec<usize>>,
+        }
+
+        impl<'de, F> Visitor<'de> for ScanVisitor<F>
+        where
+            F: FnMut(HashMap<String, Option<String>>) -> bool,
+        {
+            type Value = ();
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an array of rows")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+                let mut pos = 0;
+                loop {
+                    let wanted = match &self.positions {
+                        Some(positions) => positions.binary_search(&pos).is_ok(),
+                        None => true,
+                    };
+                    pos += 1;
+                    if !wanted {
+                        match seq.next_element::<IgnoredAny>()? {
+                            Some(_) => continue,
+                            None => break,
+                        }
+                    }
+                    match seq.next_element()? {
+                        Some(entry) => {
+                            if !(self.visit)(entry) {
+                                break;
+                            }
+                        }
+                        None => break,
+                    }
+                }
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(())
+            }
+        }
+
+        self.exists_or_err()?;
+        self.recover()?;
+        let (content, positions) = {
+            let _lock = self.lock(true)?;
+            let positions = match condition {
+                Some(condition) => Index::positions(self, &self.load_schema()?, condition)?,
+                None => None,
+            };
+            (fs::read_to_string(get_table_path(self))?, positions)
+        };
+        let mut deserializer = serde_json::Deserializer::from_str(&content);
+        deserializer.deserialize_seq(ScanVisitor { visit, positions })?;
+        deserializer.end()?;
+        Ok(())
+    }
+
+    /// The last value taken from the table sequence, 0 if none was taken yet.
+    pub(crate) fn read_seq(&self) -> TableResult<i64> {
+        let _lock = self.lock(true)?;
+        self.load_seq()
+    }
+
+    /// Read the sequence without locking the table, for callers already holding the lock.
+    fn load_seq(&self) -> TableResult<i64> {
+        let path = get_seq_path(self);
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let content = fs::read_to_string(path)?;
+        content
+            .trim()
+            .parse()
+            .map_err(|_| TableError::TypeErr(DataTypesErr::InvalidInt(content)))
+    }
+
+    /// Save the sequence to a temporary file then rename it, so a crash never leaves it
+    /// half written.
+    pub(crate) fn write_seq(&self, seq: i64) -> TableResult<()> {
+        let _lock = self.lock(false)?;
+        self.store_seq(seq)
+    }
+
+    /// Save the sequence without locking the table, for callers already holding the lock.
+    fn store_seq(&self, seq: i64) -> TableResult<()> {
+        let path = get_seq_path(self);
+        let tmp = path.with_extension("seq.tmp");
+        fs::write(&tmp, seq.to_string())?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Save the rows through the write-ahead log `<table>.wal`: they're appended to the log as
+    /// a line of JSON, then they replace the data file and the log is removed. A crash after
+    /// the line is logged is finished by [`Table::recover`].
+    pub(crate) fn write(&self, entries: &TableEntries) -> TableResult<()> {
+        self.exists_or_err()?;
+        let _lock = self.lock(false)?;
+        self.store(entries)
+    }
+
+    /// Write the rows without locking the table, for callers already holding the lock.
+    fn store(&self, entries: &TableEntries) -> TableResult<()> {
+        let content = json!(entries).to_string();
+        let mut wal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(get_wal_path(self))?;
+        wal.write_all(format!("{}\n", content).as_bytes())?;
+        wal.sync_all()?;
+
+        self.replace_rows(&content)?;
+        Index::update_all(self, &self.load_schema()?, entries)?;
+        fs::remove_file(get_wal_path(self))?;
+        Ok(())
+    }
+
+    /// Finish a write cut short by a crash, returning whether there was one. Reads call it
+    /// first, so it only needs calling to recover a table before it's read.
+    ///
+    /// The last complete line of the write-ahead log holds the rows of the last logged write,
+    /// they replace the data file and the log is removed. A line without its newline was being
+    /// logged when the crash happened, the data file wasn't touched yet so it's dropped.
+    pub fn recover(&self) -> TableResult<bool> {
+        let path = get_wal_path(self);
+        if !path.exists() {
+            return Ok(false);
+        }
+
+        let _lock = self.lock(false)?;
+        // Another process may have recovered the table while this one waited for the lock
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        let complete = &content[..content.rfind('\n').map_or(0, |end| end + 1)];
+        let replayed = match complete.lines().last() {
+            Some(line) => {
+                let entries: TableEntries = serde_json::from_str(line)?;
+                self.replace_rows(line)?;
+                Index::update_all(self, &self.load_schema()?, &entries)?;
+                true
+            }
+            None => false,
+        };
+        fs::remove_file(path)?;
+        Ok(replayed)
+    }
+
+    /// Write the rows to a temporary file and move it in place. The data file is replaced
+    /// rather than truncated so an open scan keeps reading the old rows.
+    fn replace_rows(&self, content: &str) -> TableResult<()> {
+        let table = get_table_path(self);
+        let tmp = table.with_extension("json.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp, table)?;
+        Ok(())
+    }
+
+    /// Write the rows and schema to temporary files and move them in place, the schema last
+    /// since it's what makes the table visible.
+    fn replace_files(&self, entries: &TableEntries, schema: &Schema) -> TableResult<()> {
+        let table_path = get_table_path(self);
+        let schema_path = get_schema_path(self);
+        let table_tmp = table_path.with_extension("json.tmp");
+        let schema_tmp = schema_path.with_extension("json.tmp");
+        let _lock = self.lock(false)?;
+        fs::write(&table_tmp, json!(entries).to_string())?;
+        fs::write(&schema_tmp, serde_json::to_string_pretty(schema)?)?;
+        fs::rename(table_tmp, table_path)?;
+        fs::rename(schema_tmp, schema_path)?;
+        // The rows of an interrupted write would undo these ones
+        if get_wal_path(self).exists() {
+            fs::remove_file(get_wal_path(self))?;
+        }
+        Index::update_all(self, schema, entries)
+    }
+
+    pub(crate) fn read_schema(&self) -> TableResult<Schema> {
+        self.exists_or_err()?;
+        let _lock = self.lock(true)?;
+        self.load_schema()
+    }
+
+    /// Read the schema without locking the table, for callers already holding the lock.
+    fn load_schema(&self) -> TableResult<Schema> {
+        let content = fs::read_to_string(get_schema_path(self))?;
+        let schema: Schema = serde_json::from_str(&content)?;
+
+        Ok(schema.normalized())
+    }
+
+    fn write_schema(&self, schema: Schema) -> TableResult<()> {
+        self.exists_or_err()?;
+        let path = get_schema_path(self);
+        let schema = json!(schema);
+        let _lock = self.lock(false)?;
+        fs::write(path, serde_json::to_string_pretty(&schema)?)?;
+        Ok(())
+    }
+
+    pub(crate) fn exist(&self) -> bool {
+        let schema = get_schema_path(self);
+        let table = get_table_path(self);
+        schema.exists() && table.exists()
+    }
+
+    fn exists_or_err(&self) -> TableResult<()> {
+        Database::exists_or_err(self.db)?;
+
+        if !self.exist() {
+            Err(TableError::TableNotFond(self.table_name.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Run the subqueries of the condition and replace them by the set of values they return.
+    ///
+    /// `REGEXP` patterns are compiled here, so an invalid one fails the query with
+    /// [`TableError::InvalidRegex`] and matching rows reuses the compiled pattern. Ordering a
+    /// column that can't be ordered fails with [`TableError::NotOrdered`].
+    fn resolve(
+        &self,
+        schema: &Schema,
+        condition: Option<Condition>,
+    ) -> TableResult<Option<Condition>> {
+        condition
+            .map(|c| self.resolve_condition(schema, c))
+            .transpose()
+    }
+
+    fn resolve_condition(&self, schema: &Schema, condition: Condition) -> TableResult<Condition> {
+        let resolve = |c: Box<Condition>| self.resolve_condition(schema, *c).map(Box::new);
+
+        if let Condition::Leaf { key, operator, .. } = &condition {
+            let ordered = matches!(
+                operator,
+                Operator::Gt
+                    | Operator::Lt
+                    | Operator::GtEq
+                    | Operator::LtEq
+                    | Operator::Between
+                    | Operator::NotBetween
+            );
+            if ordered
+                && schema
+                    .col_type(key)
+                    .is_some_and(|dtype| !dtype.is_ordered())
+            {
+                return Err(TableError::NotOrdered(key.clone(), operator.to_string()));
+            }
+        }
+
+        Ok(match condition {
+            Condition::And(left, right) => Condition::And(resolve(left)?, resolve(right)?),
+            Condition::Or(left, right) => Condition::Or(resolve(left)?, resolve(right)?),
+            Condition::Not(inner) => Condition::Not(resolve(inner)?),
+            Condition::Leaf {
+                key,
+                operator,
+                value:
+                    ConditionValue::Subquery {
+                        table,
+                        col,
+                        condition,
+                    },
+            } => {
+                let table = Table::new(self.db, &table)?;
+                table.col_exist_or_err(&table.read_schema()?, &col)?;
+
+                let values = table
+                    .select(
+                        SelectCols::Cols(vec![col.clone().into()]),
+                        condition.map(|c| *c),
+                        SelectOptions::default(),
+                    )?
+                    .into_iter()
+                    .filter_map(|mut entry| entry.remove(&col).flatten())
+                    .collect::<Vec<_>>();
+
+                Condition::leaf(key, operator, values)
+            }
+            leaf => {
+                if let Condition::Leaf {
+                    operator: Operator::Regexp | Operator::NotRegexp,
+                    value: ConditionValue::Single(pattern),
+                    ..
+                } = &leaf
+                {
+                    cached_regex(pattern).map_err(|e| TableError::InvalidRegex(e.to_string()))?;
+                }
+                leaf
+            }
+        })
+    }
+
+    /// Keep only the selected columns of an entry, missing columns are `None`.
+    pub(crate) fn project(
+        schema: &Schema,
+        cols: &SelectCols,
+        entry: HashMap<String, Option<String>>,
+    ) -> HashMap<String, Option<String>> {
+        match cols {
+            SelectCols::All => entry,
+            SelectCols::Cols(selectd_cols) => selectd_cols
+                .iter()
+                .map(|item| {
+                    let value = match &item.expr {
+                        SelectExpr::Col(col) => entry.get(col.trim()).cloned().flatten(),
+                        SelectExpr::Case(case) => case
+                            .arms
+                            .iter()
+                            .find(|(condition, _)| {
+                                Table::match_condition(schema, condition, &entry)
+                            })
+                            .map(|(_, value)| value.clone())
+                            .or_else(|| case.default.clone()),
+                    };
+                    (item.name().to_string(), value)
+                })
+                .collect(),
+        }
+    }
+
+    fn prefix_entry(
+        table_name: &str,
+        entry: &HashMap<String, Option<String>>,
+    ) -> HashMap<String, Option<String>> {
+        entry
+            .iter()
+            .map(|(k, v)| (format!("{}.{}", table_name, k), v.clone()))
+            .collect()
+    }
+
+    fn order_entries(
+        order: &[(&OrderClause, &DataType)],
+        a: &HashMap<String, Option<String>>,
+        b: &HashMap<String, Option<String>>,
+    ) -> Ordering {
+        for (clause, dtype) in order {
+            let a = a.get(&clause.col).and_then(|v| v.as_ref());
+            let b = b.get(&clause.col).and_then(|v| v.as_ref());
+            let ord = match (a, b) {
+                (Some(a), Some(b)) => dtype.compare(a, b).unwrap_or_else(|| a.cmp(b)),
+                _ => a.is_some().cmp(&b.is_some()),
+            };
+            let ord = match clause.direction {
+                SortDirection::Asc => ord,
+                SortDirection::Desc => ord.reverse(),
+            };
+
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+
+        Ordering::Equal
+    }
+
+    /// A hashable key of an entry's values, ordered by column name
+    fn entry_key(entry: &HashMap<String, Option<String>>) -> Vec<(String, Option<String>)> {
+        let mut key: Vec<_> = entry.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        key.sort();
+        key
+    }
+
+    /// Apply `update` to the foreign keys of every table in the database, it returns whether
+    /// the key changed so only the changed schemas are written.
+    fn update_foreign_keys<F: FnMut(&mut ForeignKey) -> bool>(
+        &self,
+        mut update: F,
+    ) -> TableResult<()> {
+        for table_name in Database::get_db_tables(self.db)? {
+            let table = Table::new(self.db, &table_name)?;
+            let mut schema = table.read_schema()?;
+            let mut changed = false;
+            for fk in schema.foreign_keys.iter_mut() {
+                changed |= update(fk);
+            }
+            if changed {
+                table.write_schema(schema)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The non-null values of a column.
+    fn col_values<'e>(
+        entries: &'e [HashMap<String, Option<String>>],
+        col: &str,
+    ) -> HashSet<&'e String> {
+        entries
+            .iter()
+            .filter_map(|e| e.get(col).and_then(|v| v.as_ref()))
+            .collect()
+    }
+
+    /// Check that every non-null foreign key value of `rows` exists in the referenced table,
+    /// `own` holds the rows of this table for keys referencing it.
+    fn check_foreign_keys(
+        &self,
+        schema: &Schema,
+        rows: &[HashMap<String, Option<String>>],
+        own: &TableEntries,
+    ) -> TableResult<()> {
+        for fk in &schema.foreign_keys {
+            let ref_entries = match fk.ref_table == self.table_name {
+                true => None,
+                false => Some(Table::new(self.db, &fk.ref_table)?.read()?),
+            };
+            let ref_values = Table::col_values(ref_entries.as_ref().unwrap_or(own), &fk.ref_col);
+
+            if let Some(value) = Table::col_values(rows, &fk.col)
+                .into_iter()
+                .find(|v| !ref_values.contains(v))
+            {
+                return Err(TableError::ForeignKeyViolation(format!(
+                    "{}.{} = {}",
+                    self.table_name, fk.col, value
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn match_query(
+        schema: &Schema,
+        condition: &Option<Condition>,
+        entry: &HashMap<String, Option<String>>,
+    ) -> bool {
+        match condition {
+            None => true,
+            Some(condition) => Table::match_condition(schema, condition, entry),
+        }
+    }
+
+    fn match_condition(
+        schema: &Schema,
+        condition: &Condition,
+        entry: &HashMap<String, Option<String>>,
+    ) -> bool {
+        Table::eval_condition(schema, condition, entry) == Some(true)
+    }
+
+    /// Evaluate a condition with SQL three-valued logic, a comparison with `NULL` is unknown
+    /// (`None`) so that `NOT` of it doesn't match either.
+    fn eval_condition(
+        schema: &Schema,
+        condition: &Condition,
+        entry: &HashMap<String, Option<String>>,
+    ) -> Option<bool> {
+        match condition {
+            Condition::And(left, right) => match Table::eval_condition(schema, left, entry) {
+                Some(false) => Some(false),
+                left => match Table::eval_condition(schema, right, entry) {
+                    Some(false) => Some(false),
+                    right => left.zip(right).map(|_| true),
+                },
+            },
+            Condition::Or(left, right) => match Table::eval_condition(schema, left, entry) {
+                Some(true) => Some(true),
+                left => match Table::eval_condition(schema, right, entry) {
+                    Some(true) => Some(true),
+                    right => left.zip(right).map(|_| false),
+                },
+            },
+            Condition::Not(inner) => Table::eval_condition(schema, inner, entry).map(|m| !m),
+            Condition::Leaf {
+                key,
+                value,
+                operator,
+            } => match entry.get(key).and_then(|v| v.as_ref()) {
+                // A missing column is treated as NULL
+                None => match operator {
+                    Operator::IsNull => Some(true),
+                    Operator::IsNotNull => Some(false),
+                    _ => None,
+                },
+                Some(_) if *operator == Operator::IsNull => Some(false),
+                Some(_) if *operator == Operator::IsNotNull => Some(true),
+                // A missing path is unknown like `NULL`, so `NOT` of it doesn't match either
+                Some(v) if matches!(operator, Operator::JsonPathEq(_)) => {
+                    let (Operator::JsonPathEq(path), ConditionValue::Single(value)) =
+                        (operator, value)
+                    else {
+                        return Some(false);
+                    };
+                    json_extract(v, path).map(|found| found == *value)
+                }
+                Some(v) => Some(match value {
+                    ConditionValue::Single(value) => {
+                        Table::compare(operator, schema.col_type(key), v, value)
+                    }
+                    ConditionValue::Column(col) => {
+                        // Both sides need the same type to be compared by value
+                        let dtype = schema
+                            .col_type(key)
+                            .filter(|t| schema.col_type(col) == Some(t));
+                        match entry.get(col).and_then(|v| v.as_ref()) {
+                            Some(value) => Table::compare(operator, dtype, v, value),
+                            None => return None,
+                        }
+                    }
+                    ConditionValue::Set(values) => {
+                        // Numbers are compared by value so `01` is in `(1, 2)`
+                        let dtype = schema.col_type(key).filter(|dtype| dtype.is_numeric());
+                        let found = values.iter().any(|value| match dtype {
+                            Some(dtype) => dtype.compare(v, value) == Some(Ordering::Equal),
+                            None => value == v,
+                        });
+
+                        match operator {
+                            Operator::In => found,
+                            Operator::NotIn => !found,
+                            _ => false,
+                        }
+                    }
+                    ConditionValue::Range(low, high) => {
+                        // A range where low > high never matches, so NOT BETWEEN always does
+                        let in_range = match schema.col_type(key) {
+                            Some(dtype) => dtype.compare(v, low).zip(dtype.compare(v, high)),
+                            None => Some((v.as_str().cmp(low), v.as_str().cmp(high))),
+                        }
+                        .map(|(low, high)| low != Ordering::Less && high != Ordering::Greater);
+
+                        match operator {
+                            Operator::Between => in_range == Some(true),
+                            Operator::NotBetween => in_range == Some(false),
+                            _ => false,
+                        }
+                    }
+                    ConditionValue::Empty => false,
+                    // Subqueries are resolved before matching
+                    ConditionValue::Subquery { .. } => false,
+                }),
+            },
+        }
+    }
+
+    fn aggregate_rows(
+        schema: &Schema,
+        func: AggFunc,
+        col: &str,
+        rows: &TableEntries,
+    ) -> TableResult<Option<String>> {
+        if func == AggFunc::Count && col == "*" {
+            return Ok(Some(rows.len().to_string()));
+        }
+
+        let dtype = schema
+            .col_type(col)
+            .ok_or_else(|| TableError::ColTypeNotFound(col.to_string()))?;
+        let values = rows
+            .iter()
+            .filter_map(|row| row.get(col).and_then(|v| v.as_ref()))
+            .collect::<Vec<_>>();
+
+        let value = match func {
+            AggFunc::Count => Some(values.len().to_string()),
+            // Added up exactly, a float would lose precision past 2^53
+            AggFunc::Sum if dtype.is_integer() => {
+                let mut sum: i64 = 0;
+                for value in &values {
+                    dtype.is_valid(value)?;
+                    sum = value
+                        .parse::<i64>()
+                        .ok()
+                        .and_then(|value| sum.checked_add(value))
+                        .ok_or_else(|| TableError::SumOverflow(col.to_string()))?;
+                }
+                Some(sum.to_string())
+            }
+            AggFunc::Sum | AggFunc::Avg => {
+                if !dtype.is_numeric() {
+                    return Err(TableError::TypeErr(DataTypesErr::InvalidType(format!(
+                        "Can't compute {:?} of `{}` with type {:?}",
+                        func, col, dtype
+                    ))));
+                }
+
+                let mut sum = 0.0;
+                for value in &values {
+                    dtype.is_valid(value)?;
+                    sum += value.parse::<f64>().unwrap_or_default();
+                }
+
+                let value = match func {
+                    AggFunc::Avg if values.is_empty() => return Ok(None),
+                    AggFunc::Avg => dtype.round(&(sum / values.len() as f64).to_string()),
+                    _ => dtype.round(&sum.to_string()),
+                };
+
+                Some(value)
+            }
+            AggFunc::Min | AggFunc::Max => {
+                let wanted = match func {
+                    AggFunc::Min => Ordering::Less,
+                    _ => Ordering::Greater,
+                };
+
+                values
+                    .into_iter()
+                    .reduce(|best, value| match dtype.compare(value, best) {
+                        Some(ord) if ord == wanted => value,
+                        _ => best,
+                    })
+                    .cloned()
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn compare(operator: &Operator, dtype: Option<&DataType>, v: &str, value: &str) -> bool {
+        // Booleans are equal whatever their case, e.g. `TRUE` and `true`, UUIDs whatever their form
+        let eq = || match dtype {
+            Some(dtype) if dtype.has_aliases() => dtype.compare(v, value) == Some(Ordering::Equal),
+            _ => v == value,
+        };
+        // Ordered operators respect the column type so numbers aren't compared as strings
+        let ord = || match dtype {
+            Some(dtype) => dtype.compare(v, value),
+            None => Some(v.cmp(value)),
+        };
+
+        match operator {
+            Operator::Eq | Operator::In => eq(),
+            Operator::NotEq | Operator::NotIn => !eq(),
+            Operator::Gt => ord() == Some(Ordering::Greater),
+            Operator::Lt => ord() == Some(Ordering::Less),
+            Operator::GtEq => matches!(ord(), Some(Ordering::Greater | Ordering::Equal)),
+            Operator::LtEq => matches!(ord(), Some(Ordering::Less | Ordering::Equal)),
+            Operator::Like => like_match(v, value),
+            Operator::NotLike => !like_match(v, value),
+            Operator::IEq => v.to_lowercase() == value.to_lowercase(),
+            Operator::INotEq => v.to_lowercase() != value.to_lowercase(),
+            Operator::ILike => like_match(&v.to_lowercase(), &value.to_lowercase()),
+            Operator::NotILike => !like_match(&v.to_lowercase(), &value.to_lowercase()),
+            // A pattern that isn't a valid regex matches nothing, not even with `NOT`
+            Operator::Regexp => regex_match(v, value) == Some(true),
+            Operator::NotRegexp => regex_match(v, value) == Some(false),
+            Operator::JsonPathEq(path) => json_extract(v, path).is_some_and(|found| found == value),
+            Operator::Between | Operator::NotBetween | Operator::IsNull | Operator::IsNotNull => {
+                false
+            }
+        }
+    }
+
+    fn get_col_pos(&self, schema: &Schema, col_name: &str) -> Option<usize> {
+        schema.cols.iter().position(|c| c == col_name)
+    }
+
+    fn get_col_type<'s>(&self, schema: &'s Schema, col_name: &str) -> TableResult<&'s DataType> {
+        match self.get_col_pos(schema, col_name) {
+            None => Err(TableError::ColNotFound(col_name.to_string())),
+            Some(pos) => match schema.types.get(pos) {
+                Some(dtype) => Ok(dtype),
+                None => Err(TableError::ColTypeNotFound(col_name.to_string())),
+            },
+        }
+    }
+
+    fn col_exist(&self, schema: &Schema, col_name: &str) -> bool {
+        self.get_col_pos(schema, col_name).is_some()
+    }
+
+    fn col_exist_or_err(&self, schema: &Schema, col_name: &str) -> TableResult<()> {
+        if !self.col_exist(schema, col_name) {
+            Err(TableError::ColNotFound(col_name.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The rows of `a` followed by the rows of `b`. With `distinct` only the first of full
+/// duplicate rows is kept, like `UNION` rather than `UNION ALL`.
+pub fn table_union(mut a: TableEntries, b: TableEntries, distinct: bool) -> TableEntries {
+    if !distinct {
+        a.extend(b);
+        return a;
+    }
+
+    let mut seen = HashSet::new();
+    a.into_iter()
+        .chain(b)
+        .filter(|entry| seen.insert(Table::entry_key(entry)))
+        .collect()
+}
+
+/// The rows of `a` that are also in `b`, comparing full rows, like `INTERSECT`. Only the first
+/// of duplicate rows is kept.
+pub fn table_intersect(a: TableEntries, b: TableEntries) -> TableEntries {
+    let wanted = b.iter().map(Table::entry_key).collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+    a.into_iter()
+        .filter(|entry| {
+            let key = Table::entry_key(entry);
+            wanted.contains(&key) && seen.insert(key)
+        })
+        .collect()
+}
+
+/// The rows of `a` that aren't in `b`, comparing full rows, like `EXCEPT`. Only the first of
+/// duplicate rows is kept.
+pub fn table_except(a: TableEntries, b: TableEntries) -> TableEntries {
+    let mut seen = b.iter().map(Table::entry_key).collect::<HashSet<_>>();
+    a.into_iter()
+        .filter(|entry| seen.insert(Table::entry_key(entry)))
+        .collect()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Schema {
+    pub(crate) cols: Vec<String>,
+    pub(crate) types: Vec<DataType>,
+    /// Whether each column accepts `None`, schemas written before this existed are nullable
+    #[serde(default)]
+    pub(crate) nullable: Vec<bool>,
+    /// The column whose values must be unique, it's never nullable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) primary_key: Option<String>,
+    /// Columns whose non-null values must be unique
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) unique: Vec<String>,
+    /// The value of each column when an insert doesn't set it
+    #[serde(default)]
+    pub(crate) defaults: Vec<Option<String>>,
+    /// `DATE`, `DATETIME` and `TIMESTAMP` columns set to `NOW` by every update that doesn't set them, e.g.
+    /// `updated_at`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) auto_now: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) foreign_keys: Vec<ForeignKey>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) checks: Vec<Check>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) unique_constraints: Vec<UniqueConstraint>,
+    /// Coerce inserted and updated values to the column types, see [`DataType::coerce`],
+    /// instead of rejecting any value not written in the stored form
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) coerce: bool,
+}
+
+/// A column whose non-null values must exist in `ref_col` of `ref_table`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+    pub col: String,
+    pub ref_table: String,
+    pub ref_col: String,
+    /// Deleting a referenced row deletes the rows referencing it instead of failing
+    #[serde(default)]
+    pub cascade: bool,
+}
+
+/// A condition every row must pass, rows where it's unknown because of a `NULL` pass too.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub condition: Condition,
+}
+
+/// Columns whose combined values must be unique, rows with a `NULL` in any of them are skipped.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct UniqueConstraint {
+    pub name: String,
+    pub cols: Vec<String>,
+}
+
+impl Schema {
+    /// A schema where every column is nullable.
+    pub fn new(cols: Vec<String>, types: Vec<DataType>) -> Self {
+        let nullable = vec![true; cols.len()];
+        let defaults = vec![None; cols.len()];
+        Self {
+            cols,
+            types,
+            nullable,
+            primary_key: None,
+            unique: Vec::new(),
+            defaults,
+            auto_now: Vec::new(),
+            foreign_keys: Vec::new(),
+            checks: Vec::new(),
+            unique_constraints: Vec::new(),
+            coerce: false,
+        }
+    }
+
+    /// Add a column with the given settings, `options.force` doesn't apply to a new table.
+    pub fn with_col(mut self, name: &str, datatype: DataType, options: ColOptions) -> Self {
+        self.cols.push(name.to_string());
+        self.types.push(datatype);
+        self.nullable.push(options.nullable);
+        self.defaults.push(options.default);
+        if options.unique {
+            self.unique.push(name.to_string());
+        }
+        if options.auto_now {
+            self.auto_now.push(name.to_string());
+        }
+        if let Some(condition) = options.check {
+            self.checks.push(Check {
+                name: format!("{}_check", name),
+                condition,
+            });
+        }
+        self
+    }
+
+    /// Make `col` the primary key, which also makes it `NOT NULL`.
+    pub fn with_primary_key(mut self, col: &str) -> Self {
+        self.primary_key = Some(col.to_string());
+        self
+    }
+
+    /// Add a foreign key, its column must be a column of the schema.
+    pub fn with_foreign_key(mut self, foreign_key: ForeignKey) -> Self {
+        self.foreign_keys.push(foreign_key);
+        self
+    }
+
+    /// Add a table check, which may refer to any column.
+    pub fn with_check(mut self, check: Check) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    /// Add a unique constraint over several columns.
+    pub fn with_unique_constraint(mut self, constraint: UniqueConstraint) -> Self {
+        self.unique_constraints.push(constraint);
+        self
+    }
+
+    /// Validate a value against the type and nullability of a column, a `NOT NULL` column
+    /// rejects empty strings too.
+    pub fn validate(&self, col_name: &str, value: Option<&String>) -> TableResult<()> {
+        let pos = self
+            .cols
+            .iter()
+            .position(|c| c == col_name)
+            .ok_or_else(|| TableError::ColNotFound(col_name.to_string()))?;
+
+        match value {
+            None if self.nullable[pos] => Ok(()),
+            None => Err(TableError::NullViolation(col_name.to_string(), 0)),
+            Some(value) if value.is_empty() && !self.nullable[pos] => {
+                Err(TableError::NullViolation(col_name.to_string(), 0))
+            }
+            Some(value) => match self.types.get(pos) {
+                Some(DataType::VARCHAR(limit)) if value.chars().count() > *limit => Err(
+                    TableError::TooLong(col_name.to_string(), *limit, value.chars().count()),
+                ),
+                Some(DataType::BLOB(limit)) if base64_decoded_len(value) > *limit => Err(
+                    TableError::TooLarge(col_name.to_string(), *limit, base64_decoded_len(value)),
+                ),
+                Some(dtype) => Ok(dtype.is_valid(value)?),
+                None => Err(TableError::ColTypeNotFound(col_name.to_string())),
+            },
+        }
+    }
+
+    /// The value written to `col`, coerced to the column type if the schema opts in.
+    fn coerce_value(&self, col_name: &str, value: Option<String>) -> TableResult<Option<String>> {
+        match (self.coerce, self.col_type(col_name), value) {
+            (true, Some(dtype), Some(value)) => dtype
+                .coerce(&value)
+                .map(Some)
+                .map_err(|e| TableError::CoercionErr(col_name.to_string(), e)),
+            (_, _, value) => Ok(value),
+        }
+    }
+
+    /// The form a value is stored in, see [`DataType::normalize`].
+    fn normalize(&self, col_name: &str, value: Option<String>) -> Option<String> {
+        match self.col_type(col_name) {
+            Some(dtype) => value.map(|v| dtype.normalize(&v)),
+            None => value,
+        }
+    }
+
+    /// The same schema with every column named `<table_name>.<col>`.
+    fn prefixed(mut self, table_name: &str) -> Self {
+        self.cols = self
+            .cols
+            .iter()
+            .map(|col| format!("{}.{}", table_name, col))
+            .collect();
+        self
+    }
+
+    /// Append the columns of `other`.
+    fn merge(mut self, other: Schema) -> Self {
+        self.cols.extend(other.cols);
+        self.types.extend(other.types);
+        self.nullable.extend(other.nullable);
+        self.defaults.extend(other.defaults);
+        self
+    }
+
+    /// Fail with [`TableError::CheckViolation`] if `entry` doesn't pass one of the checks.
+    fn check_row(&self, entry: &HashMap<String, Option<String>>, row: usize) -> TableResult<()> {
+        match self
+            .checks
+            .iter()
+            .find(|check| Table::eval_condition(self, &check.condition, entry) == Some(false))
+        {
+            Some(check) => Err(TableError::CheckViolation(check.name.clone(), row)),
+            None => Ok(()),
+        }
+    }
+
+    /// A check can only use the columns of the row it's evaluated on.
+    fn validate_check(&self, check: &Check) -> TableResult<()> {
+        fn has_subquery(condition: &Condition) -> bool {
+            match condition {
+                Condition::Leaf { value, .. } => matches!(value, ConditionValue::Subquery { .. }),
+                Condition::And(left, right) | Condition::Or(left, right) => {
+                    has_subquery(left) || has_subquery(right)
+                }
+                Condition::Not(inner) => has_subquery(inner),
+            }
+        }
+
+        if has_subquery(&check.condition) {
+            return Err(TableError::InvalidCheck(check.name.clone()));
+        }
+        match check
+            .condition
+            .keys()
+            .into_iter()
+            .find(|key| !self.cols.contains(key))
+        {
+            Some(key) => Err(TableError::ColNotFound(key.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Check that no two entries share the same primary key or unique value.
+    fn check_constraints(&self, entries: &TableEntries) -> TableResult<()> {
+        if let Some(pk) = &self.primary_key {
+            if let Some(key) = Self::find_duplicate(pk, entries) {
+                return Err(TableError::DuplicateKey(key.clone()));
+            }
+        }
+
+        for col in &self.unique {
+            if let Some(value) = Self::find_duplicate(col, entries) {
+                return Err(TableError::UniqueViolation(col.clone(), value.clone()));
+            }
+        }
+
+        for constraint in &self.unique_constraints {
+            if let Some(key) = Self::find_duplicate_key(&constraint.cols, entries) {
+                let key = key.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+                return Err(TableError::UniqueViolation(
+                    constraint.name.clone(),
+                    format!("({})", key.join(", ")),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The first values of `cols` seen twice, skipping rows where one of them is null.
+    fn find_duplicate_key<'e>(
+        cols: &[String],
+        entries: &'e TableEntries,
+    ) -> Option<Vec<&'e String>> {
+        let mut seen = HashSet::new();
+        entries
+            .iter()
+            .filter_map(|e| {
+                cols.iter()
+                    .map(|col| e.get(col).and_then(|v| v.as_ref()))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .find(|key| !seen.insert(key.clone()))
+    }
+
+    /// The constraint columns must exist and the names be distinct.
+    fn validate_unique_constraints(&self) -> TableResult<()> {
+        let mut names = HashSet::new();
+        for constraint in &self.unique_constraints {
+            if !names.insert(&constraint.name) {
+                return Err(TableError::DuplicateConstraint(constraint.name.clone()));
+            }
+            if let Some(col) = constraint.cols.iter().find(|c| !self.cols.contains(c)) {
+                return Err(TableError::ColNotFound(col.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The first non-null value of `col_name` seen twice.
+    fn find_duplicate<'e>(col_name: &str, entries: &'e TableEntries) -> Option<&'e String> {
+        let mut seen = HashSet::new();
+        entries
+            .iter()
+            .filter_map(|e| e.get(col_name).and_then(|v| v.as_ref()))
+            .find(|value| !seen.insert(*value))
+    }
+
+    /// Fill the per-column settings missing from older schema files.
+    fn normalized(mut self) -> Self {
+        self.nullable.resize(self.cols.len(), true);
+        self.defaults.resize(self.cols.len(), None);
+        if let Some(pos) = self
+            .primary_key
+            .as_ref()
+            .and_then(|pk| self.cols.iter().position(|c| c == pk))
+        {
+            self.nullable[pos] = false;
+        }
+        self
+    }
+
+    fn validate_auto_now(&self) -> TableResult<()> {
+        for col in &self.auto_now {
+            match self.col_type(col) {
+                None => return Err(TableError::ColNotFound(col.clone())),
+                Some(dtype) if !dtype.is_time() => return Err(TableError::NotTime(col.clone())),
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the `auto_now` columns of an updated row to `NOW`, except the `updated` ones.
+    fn touch(&self, entry: &mut HashMap<String, Option<String>>, updated: &[&String]) {
+        for col in self.auto_now.iter().filter(|col| !updated.contains(col)) {
+            entry.insert(col.clone(), self.normalize(col, Some(NOW.to_string())));
+        }
+    }
+
+    pub(crate) fn col_type(&self, col_name: &str) -> Option<&DataType> {
+        let pos = self.cols.iter().position(|c| c == col_name)?;
+        self.types.get(pos)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_json::json;
+    use std::{collections::HashMap, fs, time::Duration};
+
+    use crate::{
+        database::{Database, DatabaseError, CURR_DB},
+        index::Index,
+        lock::FileLock,
+        query_parser::{
+            AggFunc, Condition, ConditionValue, JoinKind, Operator, OrderClause, Query,
+            QueryParser, SelectCols, SelectItem, SelectOptions, SortDirection, TableQuery,
+        },
+        types::{DataType, DataTypesErr, Value},
+    };
+
+    use super::{
+        get_db_path, get_lock_path, get_schema_path, get_seq_path, get_table_path, get_wal_path,
+        table_except, table_intersect, table_union, Check, ColOptions, ForeignKey, Schema, Table,
+        TableEntries, TableError, UniqueConstraint,
+    };
+
+    /// A throwaway database that is dropped once the test finishes.
+    pub(crate) struct TestDB(pub(crate) String);
+
+    impl TestDB {
+        pub(crate) fn new(name: &str) -> Self {
+            let _ = Database::drop(name);
+            Database::new(name).unwrap();
+            Self(name.to_string())
+        }
+    }
+
+    impl Drop for TestDB {
+        fn drop(&mut self) {
+            let _ = Database::drop(&self.0);
+        }
+    }
+
+    pub(crate) fn users_table(db: &TestDB) -> Table<'_> {
+        let table = Table::new(&db.0, "users").unwrap();
+        table
+            .create(Schema::new(
+                vec!["name".into(), "age".into()],
+                vec![DataType::TEXT, DataType::INT],
+            ))
+            .unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![
+                    row(&["jone", "20"]),
+                    row(&["jane", "30"]),
+                    row(&["doe", "30"]),
+                ],
+            )
+            .unwrap();
+        table
+    }
+
+    fn orders_table(db: &TestDB) -> Table<'_> {
+        let table = Table::new(&db.0, "orders").unwrap();
+        table
+            .create(Schema::new(
+                vec!["id".into(), "user".into(), "total".into()],
+                vec![DataType::INT, DataType::TEXT, DataType::INT],
+            ))
+            .unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![
+                    row(&["1", "jane", "10"]),
+                    row(&["2", "jone", "25"]),
+                    row(&["3", "jane", "40"]),
+                    row(&["4", "ghost", "5"]),
+                ],
+            )
+            .unwrap();
+        table
+    }
+
+    pub(crate) fn row(values: &[&str]) -> Vec<Option<String>> {
+        values.iter().map(|v| Some(v.to_string())).collect()
+    }
+
+    pub(crate) fn set(assignments: &[(&str, &str)]) -> HashMap<String, Option<String>> {
+        assignments
+            .iter()
+            .map(|(col, val)| (col.to_string(), Some(val.to_string())))
+            .collect()
+    }
+
+    pub(crate) fn eq(key: &str, value: &str) -> Condition {
+        Condition::leaf(key, Operator::Eq, value)
+    }
+
+    #[test]
+    fn select_with_compound_condition() {
+        let db = TestDB::new("test_select_compound_condition");
+        let table = users_table(&db);
+
+        let condition = Condition::or(
+            eq("name", "jone"),
+            Condition::and(
+                eq("age", "30"),
+                Condition::leaf("name", Operator::NotEq, "doe"),
+            ),
+        );
+        let entries = table
+            .select(SelectCols::All, Some(condition), SelectOptions::default())
+            .unwrap();
+        let names = entries
+            .iter()
+            .map(|e| e["name"].as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["jone", "jane"]);
+    }
+
+    #[test]
+    fn select_with_like_condition() {
+        let db = TestDB::new("test_select_like_condition");
+        let table = users_table(&db);
+
+        let like = Condition::leaf("name", Operator::Like, "j%e");
+        let entries = table
+            .select(SelectCols::All, Some(like), SelectOptions::default())
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let not_like = Condition::leaf("name", Operator::NotLike, "_o%");
+        let entries = table
+            .select(SelectCols::All, Some(not_like), SelectOptions::default())
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["name"].as_deref(), Some("jane"));
+
+        let count = |operator, value| {
+            table
+                .count(Some(Condition::leaf("name", operator, value)))
+                .unwrap()
+        };
+        assert_eq!(count(Operator::Like, "J%"), 0);
+        assert_eq!(count(Operator::ILike, "J%"), 2);
+        assert_eq!(count(Operator::NotILike, "J%"), 1);
+        assert_eq!(count(Operator::Eq, "JANE"), 0);
+        assert_eq!(count(Operator::IEq, "JANE"), 1);
+        assert_eq!(count(Operator::INotEq, "JANE"), 2);
+    }
+
+    #[test]
+    fn select_with_json_path_condition() {
+        let db = TestDB::new("test_select_json_path");
+        let table = Table::new(&db.0, "events").unwrap();
+        table
+            .create(Schema::new(
+                vec!["id".into(), "data".into()],
+                vec![DataType::INT, DataType::JSON],
+            ))
+            .unwrap();
+        let doc = r#"{"user": {"name": "jone", "tags": ["a", "b"]}, "n": 2}"#;
+        table
+            .insert(
+                SelectCols::All,
+                vec![
+                    row(&["1", doc]),
+                    row(&["2", r#"{"user": {"name": "jane"}}"#]),
+                ],
+            )
+            .unwrap();
+        assert!(matches!(
+            table.insert(SelectCols::All, vec![row(&["3", "{oops"])]),
+            Err(TableError::TypeErr(DataTypesErr::InvalidJson(_)))
+        ));
+
+        let ids = |condition: Condition| {
+            table
+                .select(SelectCols::All, Some(condition), SelectOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|e| e["id"].clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let path = |path: &str, value: &str| {
+            Condition::leaf("data", Operator::JsonPathEq(path.into()), value)
+        };
+        assert_eq!(ids(path("$.user.name", "jane")), vec!["2"]);
+        assert_eq!(ids(path("$.user.tags[1]", "b")), vec!["1"]);
+        assert_eq!(ids(path("$.n", "2")), vec!["1"]);
+        // A missing path matches nothing, with or without `NOT`
+        assert_eq!(ids(Condition::not(path("$.n", "2"))), Vec::<String>::new());
+
+        // The document is returned as it was written
+        let entries = table
+            .select(
+                SelectCols::Cols(vec!["data".into()]),
+                Some(eq("id", "1")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries[0]["data"].as_deref(), Some(doc));
+    }
+
+    #[test]
+    fn select_with_regexp_condition() {
+        let db = TestDB::new("test_select_regexp_condition");
+        let table = users_table(&db);
+        let count = |operator, value| {
+            table
+                .count(Some(Condition::leaf("name", operator, value)))
+                .unwrap()
+        };
+        assert_eq!(count(Operator::Regexp, "^j.n"), 2);
+        assert_eq!(count(Operator::Regexp, "o"), 2);
+        assert_eq!(count(Operator::NotRegexp, "^(jane|doe)$"), 1);
+        assert_eq!(count(Operator::Regexp, "(?i)^J"), 2);
+
+        // Invalid patterns fail the query instead of matching nothing
+        let invalid = Condition::leaf("name", Operator::NotRegexp, "(jane");
+        assert!(matches!(
+            table.select(
+                SelectCols::All,
+                Some(invalid.clone()),
+                SelectOptions::default()
+            ),
+            Err(TableError::InvalidRegex(_))
+        ));
+        assert!(matches!(
+            table.delete(Condition::not(invalid)),
+            Err(TableError::InvalidRegex(_))
+        ));
+        assert_eq!(table.count(None).unwrap(), 3);
+    }
+
+    #[test]
+    fn select_with_in_condition() {
+        let db = TestDB::new("test_select_in_condition");
+        let table = users_table(&db);
+
+        let names = vec!["jone".to_string(), "doe".to_string()];
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(Condition::leaf("name", Operator::In, names.clone())),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(Condition::leaf("name", Operator::NotIn, names)),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["name"].as_deref(), Some("jane"));
+    }
+
+    #[test]
+    fn delete_with_in_subquery() {
+        let db = TestDB::new("test_delete_with_in_subquery");
+        let users = users_table(&db);
+        let orders = orders_table(&db);
+
+        let subquery = |operator: Operator, age: &str| {
+            Condition::leaf(
+                "user",
+                operator,
+                ConditionValue::Subquery {
+                    table: "users".into(),
+                    col: "name".into(),
+                    condition: Some(Box::new(eq("age", age))),
+                },
+            )
+        };
+
+        assert_eq!(
+            orders.count(Some(subquery(Operator::NotIn, "30"))).unwrap(),
+            2
+        );
+        orders.delete(subquery(Operator::In, "30")).unwrap();
+        let ids: Vec<_> = orders
+            .select(SelectCols::All, None, SelectOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|e| e["id"].clone())
+            .collect();
+        assert_eq!(ids, vec![Some("2".into()), Some("4".into())]);
+        assert_eq!(users.count(None).unwrap(), 3);
+    }
+
+    #[test]
+    fn subquery_on_missing_table_or_col() {
+        let db = TestDB::new("test_subquery_missing_table_or_col");
+        let users = users_table(&db);
+
+        let subquery = |table: &str, col: &str| {
+            Some(Condition::leaf(
+                "name",
+                Operator::In,
+                ConditionValue::Subquery {
+                    table: table.into(),
+                    col: col.into(),
+                    condition: None,
+                },
+            ))
+        };
+
+        let res = users.count(subquery("orders", "user"));
+        assert!(matches!(res, Err(TableError::TableNotFond(table)) if table == "orders"));
+        let res = users.count(subquery("users", "email"));
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "email"));
+    }
+
+    #[test]
+    fn select_with_empty_in_condition() {
+        let db = TestDB::new("test_select_empty_in_condition");
+        let table = users_table(&db);
+
+        let empty = Condition::leaf("name", Operator::In, Vec::<String>::new());
+        let entries = table
+            .select(SelectCols::All, Some(empty), SelectOptions::default())
+            .unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn select_with_numeric_in_condition() {
+        let db = TestDB::new("test_select_numeric_in_condition");
+        let table = users_table(&db);
+
+        let ages = vec!["020".to_string(), "31".to_string()];
+        assert_eq!(
+            table
+                .count(Some(Condition::leaf("age", Operator::In, ages.clone())))
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            table
+                .count(Some(Condition::leaf("age", Operator::NotIn, ages)))
+                .unwrap(),
+            2
+        );
+
+        // Text is still matched exactly
+        let names = vec!["Jane".to_string(), " jone".to_string()];
+        assert_eq!(
+            table
+                .count(Some(Condition::leaf("name", Operator::In, names)))
+                .unwrap(),
+            0
+        );
+    }
+
+    fn between(key: &str, operator: Operator, low: &str, high: &str) -> Condition {
+        Condition::leaf(
+            key,
+            operator,
+            ConditionValue::Range(low.into(), high.into()),
+        )
+    }
+
+    #[test]
+    fn select_with_between_condition() {
+        let db = TestDB::new("test_select_between_condition");
+        let table = users_table(&db);
+
+        // `age` is an INT column, so "9" is lower than "20" even though it sorts after it
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(between("age", Operator::Between, "9", "25")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["name"].as_deref(), Some("jone"));
+
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(between("age", Operator::NotBetween, "9", "25")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn ordered_operators_compare_numbers() {
+        let db = TestDB::new("test_ordered_operators");
+        let table = Table::new(&db.0, "nums").unwrap();
+        table
+            .create(Schema::new(vec!["n".into()], vec![DataType::INT]))
+            .unwrap();
+        table
+            .insert(SelectCols::All, vec![row(&["9"]), row(&["10"])])
+            .unwrap();
+        let count = |operator, value| {
+            table
+                .count(Some(Condition::leaf("n", operator, value)))
+                .unwrap()
+        };
+
+        assert_eq!(count(Operator::Gt, "9"), 1);
+        assert_eq!(count(Operator::Lt, "10"), 1);
+        assert_eq!(count(Operator::GtEq, "10"), 1);
+        assert_eq!(count(Operator::LtEq, "9"), 1);
+        assert_eq!(count(Operator::GtEq, "9"), 2);
+
+        // A value that isn't a number never matches instead of being compared as text
+        fs::write(get_table_path(&table), r#"[{"n":"9"},{"n":"ten"}]"#).unwrap();
+        assert_eq!(count(Operator::Gt, "1"), 1);
+        assert_eq!(count(Operator::Lt, "100"), 1);
+        assert_eq!(count(Operator::GtEq, "a"), 0);
+
+        // Forcing the type replaces "ten" with 0.0
+        table.alter("n", DataType::FLOAT, true).unwrap();
+        table
+            .insert(SelectCols::All, vec![row(&["10.5"]), row(&["9.75"])])
+            .unwrap();
+        assert_eq!(count(Operator::Gt, "9.8"), 1);
+        assert_eq!(count(Operator::Lt, "10"), 3);
+        assert_eq!(count(Operator::LtEq, "9"), 2);
+
+        // Scientific notation is ordered by value, garbage is rejected when it's inserted
+        table.alter("n", DataType::DOUBLE, false).unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![row(&["1e2"]), row(&[".5"]), row(&["-1.25"])],
+            )
+            .unwrap();
+        assert!(matches!(
+            table.insert(SelectCols::All, vec![row(&["NaN"])]),
+            Err(TableError::TypeErr(DataTypesErr::InvalidFloat(_)))
+        ));
+        assert_eq!(count(Operator::Gt, "10.5"), 1);
+        let options = SelectOptions {
+            order: Some(vec![OrderClause::new("n", SortDirection::Desc)]),
+            ..Default::default()
+        };
+        let values = table
+            .select(SelectCols::All, None, options)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry["n"].clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec!["1e2", "10.5", "9.75", "9", ".5", "0.0", "-1.25"]
+        );
+    }
+
+    #[test]
+    fn select_with_parsed_between_condition() {
+        let db = TestDB::new("test_select_parsed_between");
+        let table = users_table(&db);
+        table
+            .insert(
+                SelectCols::All,
+                vec![row(&["bob", "10"]), row(&["ann", "9"])],
+            )
+            .unwrap();
+
+        let query =
+            QueryParser::parse("SELECT name FROM users WHERE age BETWEEN 9 AND 30").unwrap();
+        let condition = match query {
+            Query::Table {
+                query: TableQuery::Select { condition, .. },
+                ..
+            } => condition,
+            _ => panic!("Unexpected query"),
+        };
+
+        // Both bounds are included and 10 isn't compared as text
+        assert_eq!(table.count(condition).unwrap(), 5);
+    }
+
+    #[test]
+    fn select_with_inverted_between_range() {
+        let db = TestDB::new("test_select_inverted_between");
+        let table = users_table(&db);
+
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(between("age", Operator::Between, "30", "20")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert!(entries.is_empty());
+
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(between("age", Operator::NotBetween, "30", "20")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn select_with_limit_and_offset() {
+        let db = TestDB::new("test_select_limit_offset");
+        let table = users_table(&db);
+
+        let options = SelectOptions {
+            limit: Some(1),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let entries = table.select(SelectCols::All, None, options).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["name"].as_deref(), Some("jane"));
+
+        let options = SelectOptions {
+            limit: Some(5),
+            offset: None,
+            ..Default::default()
+        };
+        let entries = table
+            .select(SelectCols::All, Some(eq("age", "30")), options)
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn select_with_offset_past_the_end() {
+        let db = TestDB::new("test_select_offset_past_end");
+        let table = users_table(&db);
+
+        let options = SelectOptions {
+            limit: None,
+            offset: Some(10),
+            ..Default::default()
+        };
+        let entries = table.select(SelectCols::All, None, options).unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn select_with_offset_past_the_end_ordered() {
+        let db = TestDB::new("test_select_offset_past_end_ordered");
+        let table = users_table(&db);
+
+        let options = SelectOptions {
+            limit: Some(2),
+            offset: Some(3),
+            order: Some(vec![OrderClause::new("age", SortDirection::Desc)]),
+            ..Default::default()
+        };
+        let entries = table.select(SelectCols::All, None, options).unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn select_with_limit_stops_scanning() {
+        let db = TestDB::new("test_select_limit_stops_scanning");
+        let table = users_table(&db);
+        // The last row isn't a valid entry, so reading it would fail
+        std::fs::write(
+            get_table_path(&table),
+            r#"[{"name": "jone", "age": "20"}, {"name": "jane", "age": "30"}, {"name": 1}]"#,
+        )
+        .unwrap();
+
+        let options = SelectOptions {
+            limit: Some(1),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let entries = table.select(SelectCols::All, None, options).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["name"].as_deref(), Some("jane"));
+
+        let res = table.select(SelectCols::All, None, SelectOptions::default());
+        assert!(matches!(res, Err(TableError::SerializationErr(_))));
+    }
+
+    #[test]
+    fn union_tables() {
+        let db = TestDB::new("test_union_tables");
+        let live = users_table(&db);
+        let archive = Table::new(&db.0, "archive").unwrap();
+        archive
+            .create(Schema::new(
+                vec!["age".into(), "name".into()],
+                vec![DataType::INT, DataType::TEXT],
+            ))
+            .unwrap();
+        archive
+            .insert(
+                SelectCols::All,
+                vec![
+                    row(&["30", "jane"]),
+                    row(&["40", "old"]),
+                    row(&["40", "old"]),
+                ],
+            )
+            .unwrap();
+
+        let names = |entries: TableEntries| {
+            entries
+                .into_iter()
+                .map(|e| e["name"].clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let entries = live
+            .union(&archive, SelectCols::All, None, None, false)
+            .unwrap();
+        assert_eq!(names(entries), vec!["jone", "jane", "doe", "old"]);
+
+        let entries = live
+            .union(
+                &archive,
+                SelectCols::Cols(vec!["name".into()]),
+                Some(eq("age", "30")),
+                None,
+                true,
+            )
+            .unwrap();
+        assert_eq!(names(entries), vec!["jane", "doe", "jane", "old", "old"]);
+
+        let orders = orders_table(&db);
+        let res = live.union(&orders, SelectCols::All, None, None, false);
+        assert!(
+            matches!(res, Err(TableError::UnionMismatch(msg)) if msg.ends_with("age, id, name, total, user"))
+        );
+        let res = live.union(
+            &orders,
+            SelectCols::Cols(vec!["name".into()]),
+            None,
+            None,
+            false,
+        );
+        assert!(
+            matches!(res, Err(TableError::UnionMismatch(msg)) if msg.ends_with("columns name"))
+        );
+    }
+
+    #[test]
+    fn union_across_databases() {
+        let db = TestDB::new("test_union_with");
+        let other_db = TestDB::new("test_union_with_other");
+        let users = users_table(&db);
+        let orders = orders_table(&other_db);
+
+        let user_cols = SelectCols::Cols(vec!["name".into()]);
+        let order_cols = SelectCols::Cols(vec![SelectItem::new("user", Some("name"))]);
+        let mut names = users
+            .union_with(&orders, user_cols.clone(), order_cols)
+            .unwrap()
+            .into_iter()
+            .map(|e| e["name"].clone().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["doe", "ghost", "jane", "jone"]);
+
+        let res = users.union_with(&orders, user_cols, SelectCols::All);
+        assert!(
+            matches!(res, Err(TableError::SchemaMismatch(msg)) if msg.ends_with("id, total, user"))
+        );
+    }
+
+    #[test]
+    fn union_entries() {
+        let entry = |name: &str| HashMap::from([("name".to_string(), Some(name.to_string()))]);
+        let a = vec![entry("jone"), entry("jane"), entry("jone")];
+        let b = vec![entry("doe"), entry("jane")];
+
+        assert_eq!(table_union(a.clone(), b.clone(), false).len(), 5);
+        assert_eq!(
+            table_union(a, b, true),
+            vec![entry("jone"), entry("jane"), entry("doe")]
+        );
+        assert!(table_union(vec![], vec![], true).is_empty());
+    }
+
+    #[test]
+    fn intersect_and_except_entries() {
+        let entry = |name: &str, age: Option<&str>| {
+            HashMap::from([
+                ("name".to_string(), Some(name.to_string())),
+                ("age".to_string(), age.map(|a| a.to_string())),
+            ])
+        };
+        let a = vec![
+            entry("jone", Some("20")),
+            entry("jane", None),
+            entry("jone", Some("20")),
+            entry("doe", Some("30")),
+        ];
+        let b = vec![
+            entry("jane", None),
+            entry("jone", Some("20")),
+            entry("doe", None),
+        ];
+
+        assert_eq!(
+            table_intersect(a.clone(), b.clone()),
+            vec![entry("jone", Some("20")), entry("jane", None)]
+        );
+        assert_eq!(table_except(a.clone(), b), vec![entry("doe", Some("30"))]);
+        assert_eq!(
+            table_except(a.clone(), vec![]),
+            vec![
+                entry("jone", Some("20")),
+                entry("jane", None),
+                entry("doe", Some("30"))
+            ]
+        );
+        assert!(table_intersect(a, vec![]).is_empty());
+    }
+
+    #[test]
+    fn count_rows() {
+        let db = TestDB::new("test_count_rows");
+        let table = users_table(&db);
+
+        assert_eq!(table.count(None).unwrap(), 3);
+        assert_eq!(table.count(Some(eq("age", "30"))).unwrap(), 2);
+        assert_eq!(table.count(Some(eq("age", "99"))).unwrap(), 0);
+
+        let missing = Table::new(&db.0, "missing").unwrap();
+        assert!(matches!(
+            missing.count(None),
+            Err(TableError::TableNotFond(_))
+        ));
+    }
+
+    #[test]
+    fn aggregate_col() {
+        let db = TestDB::new("test_aggregate_col");
+        let table = users_table(&db);
+        table
+            .insert(SelectCols::Cols(vec!["name".into()]), vec![row(&["bob"])])
+            .unwrap();
+
+        let agg = |col: &str, func: AggFunc, condition: Option<Condition>| {
+            table.aggregate(col, func, condition).unwrap()
+        };
+        assert_eq!(agg("age", AggFunc::Sum, None).as_deref(), Some("80"));
+        assert_eq!(agg("age", AggFunc::Count, None).as_deref(), Some("3"));
+        assert_eq!(agg("*", AggFunc::Count, None).as_deref(), Some("4"));
+        assert_eq!(
+            agg("age", AggFunc::Avg, Some(eq("age", "30"))).as_deref(),
+            Some("30")
+        );
+        assert_eq!(agg("age", AggFunc::Max, None).as_deref(), Some("30"));
+        // Text columns compare lexicographically
+        assert_eq!(agg("name", AggFunc::Min, None).as_deref(), Some("bob"));
+        assert_eq!(agg("name", AggFunc::Max, None).as_deref(), Some("jone"));
+
+        // Aggregates over no values
+        assert_eq!(agg("age", AggFunc::Avg, Some(eq("age", "99"))), None);
+        assert_eq!(agg("name", AggFunc::Min, Some(eq("age", "99"))), None);
+
+        let res = table.aggregate("email", AggFunc::Max, None);
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "email"));
+        let res = table.aggregate("name", AggFunc::Avg, None);
+        assert!(matches!(
+            res,
+            Err(TableError::TypeErr(DataTypesErr::InvalidType(_)))
+        ));
+    }
+
+    #[test]
+    fn sum_of_large_integers() {
+        let db = TestDB::new("test_sum_of_large_integers");
+        let table = users_table(&db);
+        table.delete(eq("age", "30")).unwrap();
+        table
+            .insert(SelectCols::All, vec![row(&["max", "9007199254740993"])])
+            .unwrap();
+
+        // Past 2^53, where adding as floats would round to 9007199254741012
+        let sum = table.aggregate("age", AggFunc::Sum, None).unwrap();
+        assert_eq!(sum.as_deref(), Some("9007199254741013"));
+
+        table
+            .insert(SelectCols::All, vec![row(&["big", &i64::MAX.to_string()])])
+            .unwrap();
+        let res = table.aggregate("age", AggFunc::Sum, None);
+        assert!(matches!(res, Err(TableError::SumOverflow(col)) if col == "age"));
+    }
+
+    #[test]
+    fn decimal_values_are_rounded() {
+        let db = TestDB::new("test_decimal_values");
+        let table = Table::new(&db.0, "prices").unwrap();
+        table
+            .create(Schema::new(
+                vec!["item".into(), "price".into()],
+                vec![DataType::TEXT, DataType::DECIMAL(2)],
+            ))
+            .unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![row(&["a", "0.1"]), row(&["b", "0.2"]), row(&["c", "1.005"])],
+            )
+            .unwrap();
+        table
+            .update(set(&[("price", "2.499")]), Some(eq("item", "c")))
+            .unwrap();
+
+        let prices = table
+            .read()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry["price"].clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(prices, vec!["0.10", "0.20", "2.50"]);
+        let sum = table.aggregate("price", AggFunc::Sum, None).unwrap();
+        assert_eq!(sum.as_deref(), Some("2.80"));
+        let avg = table.aggregate("price", AggFunc::Avg, None).unwrap();
+        assert_eq!(avg.as_deref(), Some("0.93"));
+    }
+
+    #[test]
+    fn bool_values_and_toggle() {
+        let db = TestDB::new("test_bool_toggle");
+        let table = Table::new(&db.0, "flags").unwrap();
+        table
+            .create(Schema::new(
+                vec!["name".into(), "active".into()],
+                vec![DataType::TEXT, DataType::BOOL],
+            ))
+            .unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![
+                    row(&["a", "TRUE"]),
+                    row(&["b", "false"]),
+                    vec![Some("c".into()), None],
+                ],
+            )
+            .unwrap();
+        assert!(matches!(
+            table.insert(SelectCols::All, vec![row(&["d", "yes"])]),
+            Err(TableError::TypeErr(_))
+        ));
+        assert_eq!(table.count(Some(eq("active", "True"))).unwrap(), 1);
+        assert_eq!(table.count(Some(eq("active", "1"))).unwrap(), 1);
+
+        let active = |table: &Table| {
+            table
+                .read()
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry["active"].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            active(&table),
+            vec![Some("true".into()), Some("false".into()), None]
+        );
+
+        assert_eq!(table.toggle_bool("active", None).unwrap(), 2);
+        assert_eq!(
+            active(&table),
+            vec![Some("false".into()), Some("true".into()), None]
+        );
+        assert_eq!(
+            table.toggle_bool("active", Some(eq("name", "b"))).unwrap(),
+            1
+        );
+        assert_eq!(table.count(Some(eq("active", "FALSE"))).unwrap(), 2);
+        table.create_index("active").unwrap();
+        assert_eq!(table.count(Some(eq("active", "FALSE"))).unwrap(), 2);
+        assert!(matches!(
+            table.toggle_bool("name", None),
+            Err(TableError::NotBoolean(_))
+        ));
+    }
+
+    #[test]
+    fn date_values_are_compared_as_dates() {
+        let db = TestDB::new("test_date_values");
+        let table = Table::new(&db.0, "events").unwrap();
+        table
+            .create(Schema::new(
+                vec!["name".into(), "day".into()],
+                vec![DataType::TEXT, DataType::DATE],
+            ))
+            .unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![row(&["a", "2023-12-15"]), row(&["b", "2024-2-1"])],
+            )
+            .unwrap();
+        assert!(matches!(
+            table.insert(SelectCols::All, vec![row(&["c", "2024-02-30"])]),
+            Err(TableError::TypeErr(_))
+        ));
+
+        let later = Condition::leaf("day", Operator::Gt, "2024-01-31");
+        let entries = table
+            .select(SelectCols::All, Some(later), SelectOptions::default())
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["day"].as_deref(), Some("2024-02-01"));
+        let max = table.aggregate("day", AggFunc::Max, None).unwrap();
+        assert_eq!(max.as_deref(), Some("2024-02-01"));
+
+        // Text dates are checked and padded when the column becomes a DATE
+        table.alter("day", DataType::TEXT, false).unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![row(&["c", "2024-3-5"]), row(&["d", "2023-02-30"])],
+            )
+            .unwrap();
+        let res = table.alter("day", DataType::DATE, false);
+        assert!(matches!(res, Err(TableError::InvalidRow(3, value)) if value == "2023-02-30"));
+        table.delete(eq("name", "d")).unwrap();
+        table.alter("day", DataType::DATE, false).unwrap();
+        assert_eq!(
+            table.read().unwrap()[2]["day"].as_deref(),
+            Some("2024-03-05")
+        );
+    }
+
+    #[test]
+    fn auto_now_cols_are_set_on_update() {
+        let db = TestDB::new("test_auto_now");
+        let table = Table::new(&db.0, "posts").unwrap();
+        let schema = Schema {
+            defaults: vec![None, Some("NOW".into()), Some("NOW".into())],
+            auto_now: vec!["updated_at".into()],
+            ..Schema::new(
+                vec!["title".into(), "created_at".into(), "updated_at".into()],
+                vec![DataType::TEXT, DataType::TIMESTAMP, DataType::TIMESTAMP],
+            )
+        };
+        table.create(schema).unwrap();
+        table
+            .insert(SelectCols::Cols(vec!["title".into()]), vec![row(&["a"])])
+            .unwrap();
+        let entry = &table.read().unwrap()[0];
+        let created = entry["created_at"].clone().unwrap();
+        assert!(DataType::TIMESTAMP.is_valid(&created).is_ok());
+
+        let old = "2000-01-01T00:00:00Z";
+        table
+            .update(set(&[("created_at", old), ("updated_at", old)]), None)
+            .unwrap();
+        assert_eq!(table.read().unwrap()[0]["updated_at"].as_deref(), Some(old));
+        table.update(set(&[("title", "b")]), None).unwrap();
+        let entry = &table.read().unwrap()[0];
+        assert_eq!(entry["created_at"].as_deref(), Some(old));
+        assert!(entry["updated_at"].as_deref().unwrap() > old);
+        assert!(table.describe().unwrap()[2].auto_now);
+
+        // Toggling skips `NULL`s, so those rows aren't updated
+        let nullable = ColOptions {
+            nullable: true,
+            ..Default::default()
+        };
+        table.add_col("draft", DataType::BOOL, nullable).unwrap();
+        table.update(set(&[("updated_at", old)]), None).unwrap();
+        assert_eq!(table.toggle_bool("draft", None).unwrap(), 0);
+        assert_eq!(table.read().unwrap()[0]["updated_at"].as_deref(), Some(old));
+        table
+            .update(set(&[("draft", "true"), ("updated_at", old)]), None)
+            .unwrap();
+        assert_eq!(table.toggle_bool("draft", None).unwrap(), 1);
+        assert!(table.read().unwrap()[0]["updated_at"].as_deref().unwrap() > old);
+
+        let options = ColOptions {
+            nullable: true,
+            auto_now: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            table.add_col("edits", DataType::INT, options),
+            Err(TableError::NotTime(_))
+        ));
+    }
+
+    #[test]
+    fn datetime_defaults_to_now() {
+        let db = TestDB::new("test_datetime_now");
+        let table = Table::new(&db.0, "logs").unwrap();
+        let schema = Schema {
+            defaults: vec![None, Some("NOW()".into())],
+            ..Schema::new(
+                vec!["msg".into(), "created".into()],
+                vec![DataType::TEXT, DataType::DATETIME],
+            )
+        };
+        table.create(schema).unwrap();
+        table
+            .insert(SelectCols::Cols(vec!["msg".into()]), vec![row(&["a"])])
+            .unwrap();
+        table
+            .insert(SelectCols::All, vec![row(&["b", "2000-01-01 08:00:00"])])
+            .unwrap();
+        let res = table.insert(SelectCols::All, vec![row(&["c", "2000-01-01 25:61:00"])]);
+        assert!(matches!(
+            res,
+            Err(TableError::TypeErr(DataTypesErr::InvalidTimestamp(_)))
+        ));
+
+        let entries = table.read().unwrap();
+        let created = entries[0]["created"].clone().unwrap();
+        assert!(DataType::DATETIME.is_valid(&created).is_ok());
+        assert!(created.as_str() > "2000-01-01 08:00:00");
+        let later = Condition::leaf("created", Operator::Gt, "2000-01-01 07:59:59");
+        assert_eq!(table.count(Some(later)).unwrap(), 2);
+        let earlier = Condition::leaf("created", Operator::Lt, "2000-01-01 08:00:01");
+        assert_eq!(table.count(Some(earlier)).unwrap(), 1);
+    }
+
+    #[test]
+    fn uuid_keys_are_generated() {
+        let db = TestDB::new("test_uuid_keys");
+        let table = Table::new(&db.0, "users").unwrap();
+        let schema = Schema {
+            primary_key: Some("id".into()),
+            defaults: vec![Some("GEN_RANDOM_UUID()".into()), None, None],
+            ..Schema::new(
+                vec!["id".into(), "n".into(), "name".into()],
+                vec![DataType::UUID, DataType::SERIAL, DataType::TEXT],
+            )
+        };
+        table.create(schema).unwrap();
+        let name = SelectCols::Cols(vec!["name".into()]);
+        let res = table
+            .insert_returning(name.clone(), vec![row(&["a"]), row(&["b"])])
+            .unwrap();
+        let res_given = table
+            .insert_returning(
+                SelectCols::All,
+                vec![row(&["67E5504410B1426F9247BB680E5FE0C8", "c"])],
+            )
+            .unwrap();
+
+        let ids = table
+            .read()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry["id"].clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_ne!(ids[0], ids[1]);
+        let generated = |id: &str, n: &str| {
+            HashMap::from([("id".to_string(), id.to_string()), ("n".into(), n.into())])
+        };
+        assert_eq!(
+            res.generated,
+            vec![generated(&ids[0], "1"), generated(&ids[1], "2")]
+        );
+        assert_eq!(
+            res_given.generated,
+            vec![HashMap::from([("n".to_string(), "3".to_string())])]
+        );
+        assert!(ids.iter().all(|id| DataType::UUID.is_valid(id).is_ok()));
+        assert_eq!(ids[2], "67e55044-10b1-426f-9247-bb680e5fe0c8");
+
+        let condition = eq("id", "67E55044-10B1-426F-9247-BB680E5FE0C8");
+        assert_eq!(table.count(Some(condition.clone())).unwrap(), 1);
+        table.create_index("id").unwrap();
+        assert_eq!(table.count(Some(condition)).unwrap(), 1);
+    }
+
+    #[test]
+    fn select_typed_values() {
+        let db = TestDB::new("test_select_values");
+        let table = users_table(&db);
+        table
+            .insert(SelectCols::All, vec![vec![Some("max".into()), None]])
+            .unwrap();
+
+        let cols = SelectCols::parse("name AS n, age").unwrap();
+        let entries = table
+            .select_values(cols, Some(eq("name", "jone")), SelectOptions::default())
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&entries).unwrap(),
+            json!([{"n": "jone", "age": 20}])
+        );
+
+        let entries = table
+            .select_values(SelectCols::All, None, SelectOptions::default())
+            .unwrap();
+        let ages = entries.iter().map(|e| e["age"].clone()).collect::<Vec<_>>();
+        assert_eq!(
+            ages,
+            vec![Value::Int(20), Value::Int(30), Value::Int(30), Value::Null]
+        );
+        assert!(ages[0] < ages[1]);
+    }
+
+    #[test]
+    fn select_grouped_with_aggregates() {
+        let db = TestDB::new("test_select_grouped");
+        let table = users_table(&db);
+
+        let entries = table
+            .select_grouped(
+                "age",
+                vec![
+                    (AggFunc::Count, "*".into()),
+                    (AggFunc::Sum, "age".into()),
+                    (AggFunc::Avg, "age".into()),
+                    (AggFunc::Max, "name".into()),
+                ],
+                None,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["age"].as_deref(), Some("20"));
+        assert_eq!(entries[0]["COUNT(*)"].as_deref(), Some("1"));
+        assert_eq!(entries[1]["age"].as_deref(), Some("30"));
+        assert_eq!(entries[1]["COUNT(*)"].as_deref(), Some("2"));
+        assert_eq!(entries[1]["SUM(age)"].as_deref(), Some("60"));
+        assert_eq!(entries[1]["AVG(age)"].as_deref(), Some("30"));
+        assert_eq!(entries[1]["MAX(name)"].as_deref(), Some("jane"));
+    }
+
+    #[test]
+    fn group_by_multiple_cols() {
+        let db = TestDB::new("test_group_by_multiple_cols");
+        let orders = orders_table(&db);
+        orders
+            .insert(SelectCols::All, vec![row(&["5", "jane", "10"])])
+            .unwrap();
+
+        let entries = orders
+            .group_by(
+                vec!["user".into(), "total".into()],
+                vec![
+                    ("orders".into(), AggFunc::Count, "*".into()),
+                    ("first".into(), AggFunc::Min, "id".into()),
+                ],
+                Some(Condition::leaf("user", Operator::NotEq, "ghost")),
+                None,
+            )
+            .unwrap();
+
+        let rows: Vec<_> = entries
+            .iter()
+            .map(|e| {
+                (
+                    e["user"].as_deref(),
+                    e["total"].as_deref(),
+                    e["orders"].as_deref(),
+                    e["first"].as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Some("jane"), Some("10"), Some("2"), Some("1")),
+                (Some("jone"), Some("25"), Some("1"), Some("2")),
+                (Some("jane"), Some("40"), Some("1"), Some("3")),
+            ]
+        );
+
+        let res = orders.group_by(vec!["user".into(), "country".into()], vec![], None, None);
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "country"));
+    }
+
+    #[test]
+    fn group_by_with_having() {
+        let db = TestDB::new("test_group_by_having");
+        let orders = orders_table(&db);
+        orders
+            .insert(SelectCols::All, vec![row(&["5", "jone", "100"])])
+            .unwrap();
+        let group = |having| {
+            orders.group_by(
+                vec!["user".into()],
+                vec![
+                    ("orders".into(), AggFunc::Count, "*".into()),
+                    ("spent".into(), AggFunc::Sum, "total".into()),
+                ],
+                None,
+                Some(having),
+            )
+        };
+        let users = |entries: TableEntries| {
+            entries
+                .into_iter()
+                .map(|e| e["user"].clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // 125 > 50 > 5 only hold as numbers
+        let spent = Condition::leaf("spent", Operator::Gt, "49");
+        assert_eq!(users(group(spent).unwrap()), vec!["jane", "jone"]);
+        let big = Condition::and(
+            Condition::leaf("orders", Operator::GtEq, "2"),
+            Condition::leaf("user", Operator::NotEq, "jane"),
+        );
+        assert_eq!(users(group(big).unwrap()), vec!["jone"]);
+
+        // Only projected columns can be used
+        let res = group(Condition::leaf("total", Operator::Gt, "10"));
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "total"));
+    }
+
+    #[test]
+    fn select_grouped_with_condition() {
+        let db = TestDB::new("test_select_grouped_condition");
+        let table = users_table(&db);
+
+        let entries = table
+            .select_grouped(
+                "age",
+                vec![(AggFunc::Min, "name".into())],
+                Some(Condition::leaf("name", Operator::NotEq, "doe")),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1]["MIN(name)"].as_deref(), Some("jane"));
+    }
+
+    #[test]
+    fn select_grouped_by_unknown_col() {
+        let db = TestDB::new("test_select_grouped_unknown_col");
+        let table = users_table(&db);
+
+        let res = table.select_grouped("country", vec![(AggFunc::Count, "*".into())], None, None);
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "country"));
+    }
+
+    #[test]
+    fn sum_of_text_col() {
+        let db = TestDB::new("test_sum_of_text_col");
+        let table = users_table(&db);
+
+        let res = table.select_grouped("age", vec![(AggFunc::Sum, "name".into())], None, None);
+        assert!(matches!(res, Err(TableError::TypeErr(_))));
+    }
+
+    #[test]
+    fn select_grouped_with_having() {
+        let db = TestDB::new("test_select_grouped_having");
+        let table = users_table(&db);
+        table
+            .insert(
+                SelectCols::All,
+                (0..10).map(|_| row(&["bot", "5"])).collect(),
+            )
+            .unwrap();
+
+        // "10" > "9" only holds when the counts are compared as numbers
+        let entries = table
+            .select_grouped(
+                "name",
+                vec![(AggFunc::Count, "*".into())],
+                Some(Condition::leaf("age", Operator::LtEq, "20")),
+                Some(Condition::leaf("COUNT(*)", Operator::Gt, "9")),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["name"].as_deref(), Some("bot"));
+        assert_eq!(entries[0]["COUNT(*)"].as_deref(), Some("10"));
+    }
+
+    #[test]
+    fn select_grouped_having_unknown_alias() {
+        let db = TestDB::new("test_select_grouped_having_alias");
+        let table = users_table(&db);
+
+        let res = table.select_grouped(
+            "age",
+            vec![(AggFunc::Count, "*".into())],
+            None,
+            Some(Condition::leaf("SUM(age)", Operator::Gt, "10")),
+        );
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "SUM(age)"));
+    }
+
+    #[test]
+    fn inner_join() {
+        let db = TestDB::new("test_inner_join");
+        let users = users_table(&db);
+        let orders = orders_table(&db);
+
+        let on = ("name".to_string(), "user".to_string());
+        let entries = users
+            .join(&orders, JoinKind::Inner, on.clone(), SelectCols::All, None)
+            .unwrap();
+        let pairs: Vec<_> = entries
+            .iter()
+            .map(|e| (e["users.name"].as_deref(), e["orders.id"].as_deref()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (Some("jone"), Some("2")),
+                (Some("jane"), Some("1")),
+                (Some("jane"), Some("3")),
+            ]
+        );
+
+        // Same rows when the larger table drives the join
+        let entries = orders
+            .join(
+                &users,
+                JoinKind::Inner,
+                ("user".to_string(), "name".to_string()),
+                SelectCols::All,
+                None,
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+
+        let entries = users
+            .join(
+                &orders,
+                JoinKind::Inner,
+                on,
+                SelectCols::Cols(vec!["users.age".into(), "orders.total".into()]),
+                Some(Condition::leaf("orders.total", Operator::Gt, "20")),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].len(), 2);
+        assert_eq!(entries[0]["orders.total"].as_deref(), Some("25"));
+        assert_eq!(entries[1]["users.age"].as_deref(), Some("30"));
+    }
+
+    #[test]
+    fn left_join() {
+        let db = TestDB::new("test_left_join");
+        let users = users_table(&db);
+        let orders = orders_table(&db);
+
+        let entries = users
+            .join(
+                &orders,
+                JoinKind::Left,
+                ("name".to_string(), "user".to_string()),
+                SelectCols::Cols(vec!["users.name".into(), "orders.total".into()]),
+                None,
+            )
+            .unwrap();
+        let rows: Vec<_> = entries
+            .iter()
+            .map(|e| (e["users.name"].as_deref(), e["orders.total"].as_deref()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Some("jone"), Some("25")),
+                (Some("jane"), Some("10")),
+                (Some("jane"), Some("40")),
+                (Some("doe"), None),
+            ]
+        );
+
+        // Unmatched rows carry every right column as NULL
+        let entries = users
+            .join(
+                &orders,
+                JoinKind::Left,
+                ("name".to_string(), "user".to_string()),
+                SelectCols::All,
+                Some(Condition::leaf(
+                    "orders.id",
+                    Operator::IsNull,
+                    ConditionValue::Empty,
+                )),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].len(), 5);
+        assert_eq!(entries[0]["users.name"].as_deref(), Some("doe"));
+        assert_eq!(entries[0]["orders.user"], None);
+    }
+
+    #[test]
+    fn right_join() {
+        let db = TestDB::new("test_right_join");
+        let users = users_table(&db);
+        let orders = orders_table(&db);
+
+        let entries = users
+            .join(
+                &orders,
+                JoinKind::Right,
+                ("name".to_string(), "user".to_string()),
+                SelectCols::All,
+                None,
+            )
+            .unwrap();
+        let rows: Vec<_> = entries
+            .iter()
+            .map(|e| (e["users.name"].as_deref(), e["orders.id"].as_deref()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Some("jone"), Some("2")),
+                (Some("jane"), Some("1")),
+                (Some("jane"), Some("3")),
+                (None, Some("4")),
+            ]
+        );
+        assert_eq!(entries[3]["users.age"], None);
+
+        let missing = Table::new(&db.0, "missing").unwrap();
+        let res = users.join(
+            &missing,
+            JoinKind::Inner,
+            ("name".to_string(), "user".to_string()),
+            SelectCols::All,
+            None,
+        );
+        assert!(matches!(res, Err(TableError::TableNotFond(table)) if table == "missing"));
+    }
+
+    #[test]
+    fn join_on_missing_col() {
+        let db = TestDB::new("test_join_on_missing_col");
+        let users = users_table(&db);
+        let orders = orders_table(&db);
+
+        let res = users.join(
+            &orders,
+            JoinKind::Inner,
+            ("name".to_string(), "user_id".to_string()),
+            SelectCols::All,
+            None,
+        );
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "orders.user_id"));
+    }
+
+    #[test]
+    fn select_ordered() {
+        let db = TestDB::new("test_select_ordered");
+        let table = users_table(&db);
+        table
+            .insert(SelectCols::All, vec![row(&["bob", "9"]), vec![None, None]])
+            .unwrap();
+
+        let order = |clauses: Vec<OrderClause>| SelectOptions {
+            order: Some(clauses),
+            ..Default::default()
+        };
+        let names = |entries: TableEntries| {
+            entries
+                .into_iter()
+                .map(|e| e["name"].clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Ages are compared as numbers and NULL sorts first
+        let entries = table
+            .select(
+                SelectCols::All,
+                None,
+                order(vec![OrderClause::new("age", SortDirection::Asc)]),
+            )
+            .unwrap();
+        assert_eq!(
+            names(entries),
+            vec![
+                None,
+                Some("bob".into()),
+                Some("jone".into()),
+                Some("jane".into()),
+                Some("doe".into())
+            ]
+        );
+
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(Condition::leaf(
+                    "age",
+                    Operator::IsNotNull,
+                    ConditionValue::Empty,
+                )),
+                SelectOptions {
+                    limit: Some(3),
+                    ..order(vec![
+                        OrderClause::new("age", SortDirection::Desc),
+                        OrderClause::new("name", SortDirection::Asc),
+                    ])
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            names(entries),
+            vec![Some("doe".into()), Some("jane".into()), Some("jone".into())]
+        );
+    }
+
+    #[test]
+    fn select_ordered_is_stable() {
+        let db = TestDB::new("test_select_ordered_is_stable");
+        let orders = orders_table(&db);
+        orders
+            .insert(SelectCols::All, vec![row(&["5", "jane", "10"])])
+            .unwrap();
+
+        let ids = |order: Vec<OrderClause>| {
+            let options = SelectOptions {
+                order: Some(order),
+                ..Default::default()
+            };
+            orders
+                .select(SelectCols::All, None, options)
+                .unwrap()
+                .into_iter()
+                .map(|e| e["id"].clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // Rows with equal keys keep their insertion order
+        assert_eq!(
+            ids(vec![OrderClause::new("user", SortDirection::Asc)]),
+            vec!["4", "1", "3", "5", "2"]
+        );
+        assert_eq!(
+            ids(vec![
+                OrderClause::new("user", SortDirection::Desc),
+                OrderClause::new("total", SortDirection::Asc),
+            ]),
+            vec!["2", "1", "5", "3", "4"]
+        );
+    }
+
+    #[test]
+    fn select_ordered_by_missing_col() {
+        let db = TestDB::new("test_select_ordered_by_missing_col");
+        let table = users_table(&db);
+
+        let res = table.select(
+            SelectCols::All,
+            None,
+            SelectOptions {
+                order: Some(vec![OrderClause::new("email", SortDirection::Asc)]),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "email"));
+    }
+
+    #[test]
+    fn select_with_aliases() {
+        let db = TestDB::new("test_select_with_aliases");
+        let table = users_table(&db);
+        let cols = SelectCols::Cols(vec![
+            SelectItem::new("name", Some("full_name")),
+            SelectItem::new("age", Some("years")),
+        ]);
+
+        let entries = table
+            .select(
+                cols.clone(),
+                None,
+                SelectOptions {
+                    order: Some(vec![
+                        OrderClause::new("years", SortDirection::Desc),
+                        OrderClause::new("name", SortDirection::Asc),
+                    ]),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e["full_name"].as_deref()).collect();
+        assert_eq!(names, vec![Some("doe"), Some("jane"), Some("jone")]);
+        assert_eq!(entries[0]["years"].as_deref(), Some("30"));
+        assert!(!entries[0].contains_key("name"));
+
+        let cols = SelectCols::Cols(vec![
+            SelectItem::new("name", Some("x")),
+            SelectItem::new("age", Some("x")),
+        ]);
+        let res = table.select(cols, None, SelectOptions::default());
+        assert!(matches!(res, Err(TableError::DuplicateAlias(alias)) if alias == "x"));
+    }
+
+    #[test]
+    fn select_comparing_cols() {
+        let db = TestDB::new("test_select_comparing_cols");
+        let table = Table::new(&db.0, "trips").unwrap();
+        table
+            .create(Schema::new(
+                vec!["name".into(), "start".into(), "end".into()],
+                vec![DataType::TEXT, DataType::INT, DataType::INT],
+            ))
+            .unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![
+                    row(&["a", "9", "10"]),
+                    row(&["b", "5", "3"]),
+                    vec![Some("c".into()), Some("1".into()), None],
+                ],
+            )
+            .unwrap();
+
+        let names = |condition: &str| {
+            let query = QueryParser::parse(&format!("SELECT * FROM trips WHERE {}", condition));
+            let condition = match query.unwrap() {
+                Query::Table {
+                    query: TableQuery::Select { condition, .. },
+                    ..
+                } => condition,
+                _ => panic!("Unexpected query"),
+            };
+            table
+                .select(SelectCols::All, condition, SelectOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|e| e["name"].clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // Compared as numbers, `"9" > "10"` as text
+        assert_eq!(names("end > start"), vec!["a"]);
+        assert_eq!(names("NOT end > start"), vec!["b"]);
+        // There's no `b` column so no row matches, the text `b` has to be quoted
+        assert_eq!(names("name = b"), Vec::<String>::new());
+        assert_eq!(names("NOT name = b"), Vec::<String>::new());
+        assert_eq!(names("name = 'b'"), vec!["b"]);
+        assert_eq!(names("name = 'start'"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn select_case_expr() {
+        let db = TestDB::new("test_select_case_expr");
+        let table = users_table(&db);
+        let query = QueryParser::parse(
+            "SELECT name, CASE WHEN age > 25 THEN 'old' WHEN name = 'ghost' THEN 'young' END AS bracket, \
+             CASE WHEN age > 10 THEN 'teen' WHEN age > 25 THEN 'adult' ELSE 'none' END AS first FROM users",
+        )
+        .unwrap();
+        let cols = match query {
+            Query::Table {
+                query: TableQuery::Select { cols, .. },
+                ..
+            } => cols,
+            _ => panic!("Unexpected query"),
+        };
+
+        let entries = table.select(cols, None, SelectOptions::default()).unwrap();
+        let brackets: Vec<_> = entries.iter().map(|e| e["bracket"].as_deref()).collect();
+        // Without `ELSE` unmatched rows are `NULL`
+        assert_eq!(brackets, vec![None, Some("old"), Some("old")]);
+        // The first matching arm wins
+        assert!(entries
+            .iter()
+            .all(|e| e["first"].as_deref() == Some("teen")));
+        assert!(!entries[0].contains_key("age"));
+    }
+
+    #[test]
+    fn select_distinct() {
+        let db = TestDB::new("test_select_distinct");
+        let table = users_table(&db);
+
+        let entries = table
+            .select(
+                SelectCols::Cols(vec!["age".into()]),
+                None,
+                SelectOptions {
+                    distinct: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let ages: Vec<_> = entries.iter().map(|e| e["age"].as_deref()).collect();
+        assert_eq!(ages, vec![Some("20"), Some("30")]);
+
+        let entries = table
+            .select(
+                SelectCols::Cols(vec!["age".into()]),
+                None,
+                SelectOptions {
+                    distinct: true,
+                    offset: Some(1),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["age"].as_deref(), Some("30"));
+    }
+
+    #[test]
+    fn select_distinct_ordered_page() {
+        let db = TestDB::new("test_select_distinct_ordered_page");
+        let orders = orders_table(&db);
+
+        let options = SelectOptions {
+            distinct: true,
+            order: Some(vec![OrderClause::new("user", SortDirection::Desc)]),
+            limit: Some(2),
+            offset: Some(1),
+        };
+        let entries = orders
+            .select(SelectCols::Cols(vec!["user".into()]), None, options)
+            .unwrap();
+        let users: Vec<_> = entries.iter().map(|e| e["user"].as_deref()).collect();
+        assert_eq!(users, vec![Some("jane"), Some("ghost")]);
+    }
+
+    #[test]
+    fn insert_missing_nullable_col() {
+        let db = TestDB::new("test_insert_missing_nullable_col");
+        let table = users_table(&db);
+
+        table
+            .insert(SelectCols::Cols(vec!["name".into()]), vec![row(&["bob"])])
+            .unwrap();
+
+        let entries = table
+            .select(
+                SelectCols::Cols(vec!["age".into()]),
+                Some(eq("name", "bob")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["age"], None);
+
+        // NULL never matches a comparison
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(Condition::leaf("age", Operator::NotEq, "20")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn select_null_checks() {
+        let db = TestDB::new("test_select_null_checks");
+        let table = users_table(&db);
+        table
+            .insert(SelectCols::Cols(vec!["name".into()]), vec![row(&["bob"])])
+            .unwrap();
+
+        let is_null = Condition::leaf("age", Operator::IsNull, ConditionValue::Empty);
+        let entries = table
+            .select(SelectCols::All, Some(is_null), SelectOptions::default())
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["name"].as_deref(), Some("bob"));
+
+        let is_not_null = Condition::leaf("age", Operator::IsNotNull, ConditionValue::Empty);
+        let entries = table
+            .select(SelectCols::All, Some(is_not_null), SelectOptions::default())
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn select_not_condition() {
+        let db = TestDB::new("test_select_not_condition");
+        let table = users_table(&db);
+        table
+            .insert(SelectCols::Cols(vec!["name".into()]), vec![row(&["bob"])])
+            .unwrap();
+
+        // `age = 20` is unknown for bob, and so is its negation
+        let not_20 = Condition::not(eq("age", "20"));
+        let entries = table
+            .select(SelectCols::All, Some(not_20), SelectOptions::default())
+            .unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e["name"].as_deref()).collect();
+        assert_eq!(names, vec![Some("jane"), Some("doe")]);
+
+        // An unknown side doesn't matter once the other one decides
+        let con = Condition::or(eq("age", "20"), eq("name", "bob"));
+        assert_eq!(table.count(Some(con)).unwrap(), 2);
+        let con = Condition::not(Condition::and(eq("age", "20"), eq("name", "jone")));
+        assert_eq!(table.count(Some(con)).unwrap(), 3);
+    }
+
+    #[test]
+    fn null_checks_on_missing_key() {
+        let schema = Schema::new(vec!["email".into()], vec![DataType::TEXT]);
+        let missing = HashMap::new();
+        let null = HashMap::from([("email".to_string(), None)]);
+
+        for entry in [&missing, &null] {
+            let is_null = Condition::leaf("email", Operator::IsNull, ConditionValue::Empty);
+            let is_not_null = Condition::leaf("email", Operator::IsNotNull, ConditionValue::Empty);
+            assert!(Table::match_condition(&schema, &is_null, entry));
+            assert!(!Table::match_condition(&schema, &is_not_null, entry));
+        }
+    }
+
+    #[test]
+    fn insert_null_into_not_null_col() {
+        let db = TestDB::new("test_insert_null_not_null_col");
+        let table = Table::new(&db.0, "users").unwrap();
+        let schema = Schema {
+            nullable: vec![false, true],
+            ..Schema::new(
+                vec!["name".into(), "age".into()],
+                vec![DataType::TEXT, DataType::INT],
+            )
+        };
+        table.create(schema).unwrap();
+
+        let res = table.insert(SelectCols::All, vec![vec![None, Some("20".into())]]);
+        assert!(matches!(res, Err(TableError::NullViolation(col, _)) if col == "name"));
+
+        let res = table.insert(SelectCols::Cols(vec!["age".into()]), vec![row(&["20"])]);
+        assert!(matches!(res, Err(TableError::NullViolation(col, _)) if col == "name"));
+
+        let res = table.insert(
+            SelectCols::All,
+            vec![row(&["jone", "20"]), vec![Some("".into()), None]],
+        );
+        assert!(matches!(res, Err(TableError::NullViolation(col, 1)) if col == "name"));
+
+        table
+            .insert(
+                SelectCols::Cols(vec!["name".into()]),
+                vec![row(&["jone"]), row(&["jane"])],
+            )
+            .unwrap();
+        let res = table.update(
+            HashMap::from([("name".to_string(), None)]),
+            Some(eq("name", "jane")),
+        );
+        assert!(matches!(res, Err(TableError::NullViolation(col, 1)) if col == "name"));
+    }
+
+    pub(crate) fn keyed_table(db: &TestDB) -> Table<'_> {
+        let table = Table::new(&db.0, "keyed").unwrap();
+        table
+            .create(Schema {
+                primary_key: Some("id".into()),
+                ..Schema::new(
+                    vec!["id".into(), "name".into()],
+                    vec![DataType::INT, DataType::TEXT],
+                )
+            })
+            .unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![row(&["1", "jone"]), row(&["2", "jane"])],
+            )
+            .unwrap();
+        table
+    }
+
+    #[test]
+    fn insert_duplicate_primary_key() {
+        let db = TestDB::new("test_insert_duplicate_primary_key");
+        let table = keyed_table(&db);
+
+        let res = table.insert(SelectCols::All, vec![row(&["1", "doe"])]);
+        assert!(matches!(res, Err(TableError::DuplicateKey(key)) if key == "1"));
+
+        let res = table.insert(
+            SelectCols::All,
+            vec![row(&["3", "doe"]), row(&["3", "bob"])],
+        );
+        assert!(matches!(res, Err(TableError::DuplicateKey(key)) if key == "3"));
+
+        let res = table.insert(SelectCols::Cols(vec!["name".into()]), vec![row(&["doe"])]);
+        assert!(matches!(res, Err(TableError::NullViolation(col, _)) if col == "id"));
+
+        // Nothing was written by the failed inserts
+        assert_eq!(table.count(None).unwrap(), 2);
+        table
+            .insert(SelectCols::All, vec![row(&["3", "doe"])])
+            .unwrap();
+        assert_eq!(table.count(None).unwrap(), 3);
+    }
+
+    #[test]
+    fn update_duplicate_primary_key() {
+        let db = TestDB::new("test_update_duplicate_primary_key");
+        let table = keyed_table(&db);
+
+        let res = table.update(set(&[("id", "2")]), Some(eq("name", "jone")));
+        assert!(matches!(res, Err(TableError::DuplicateKey(key)) if key == "2"));
+
+        let updated = table
+            .update(set(&[("id", "5")]), Some(eq("name", "jone")))
+            .unwrap();
+        assert_eq!(updated, 1);
+    }
+
+    #[test]
+    fn copy_table() {
+        let db = TestDB::new("test_copy_table");
+        let backup = TestDB::new("test_copy_table_backup");
+        let table = users_table(&db);
+
+        table.copy_to(&db.0, "users_copy").unwrap();
+        table.copy_to(&backup.0, "users").unwrap();
+        table.delete(eq("name", "jone")).unwrap();
+
+        for copy in [
+            Table::new(&db.0, "users_copy").unwrap(),
+            Table::new(&backup.0, "users").unwrap(),
+        ] {
+            assert_eq!(copy.count(None).unwrap(), 3);
+            assert_eq!(copy.read_schema().unwrap(), table.read_schema().unwrap());
+        }
+
+        let res = table.copy_to(&db.0, "users_copy");
+        assert!(matches!(res, Err(TableError::TableAlreadyExists(_))));
+        let res = table.copy_to("test_copy_table_missing", "users");
+        assert!(matches!(
+            res,
+            Err(TableError::DBErr(DatabaseError::NotFound(_)))
+        ));
+    }
+
+    #[test]
+    fn failed_copy_leaves_nothing_behind() {
+        let db = TestDB::new("test_failed_copy");
+        let table = Table::new(&db.0, "tickets").unwrap();
+        table
+            .create(Schema::new(
+                vec!["id".into(), "title".into()],
+                vec![DataType::SERIAL, DataType::TEXT],
+            ))
+            .unwrap();
+        table
+            .insert(SelectCols::All, vec![row(&["a"]), row(&["b"])])
+            .unwrap();
+
+        // A directory in the way of the schema makes writing the copy fail
+        let dest = Table::new(&db.0, "copy").unwrap();
+        let blocker = get_schema_path(&dest).with_extension("json.tmp");
+        std::fs::create_dir(&blocker).unwrap();
+        assert!(matches!(
+            table.copy_to(&db.0, "copy"),
+            Err(TableError::IoErr(_))
+        ));
+        std::fs::remove_dir(&blocker).unwrap();
+        for path in [
+            get_table_path(&dest),
+            get_schema_path(&dest),
+            get_seq_path(&dest),
+        ] {
+            assert!(!path.exists(), "{:?} was left behind", path);
+        }
+
+        table.copy_to(&db.0, "copy").unwrap();
+        dest.insert(SelectCols::All, vec![row(&["c"])]).unwrap();
+        assert_eq!(dest.count(Some(eq("id", "3"))).unwrap(), 1);
+    }
+
+    #[test]
+    fn drop_if_exists() {
+        let db = TestDB::new("test_drop_if_exists");
+        let table = users_table(&db);
+
+        assert!(table.drop_if_exists().unwrap());
+        assert!(!table.drop_if_exists().unwrap());
+        assert!(matches!(table.drop(), Err(TableError::TableNotFond(_))));
+
+        assert!(Database::drop_if_exists(&db.0).unwrap());
+        assert!(!Database::drop_if_exists(&db.0).unwrap());
+    }
+
+    #[test]
+    fn list_tables() {
+        let db = TestDB::new("test_list_tables");
+        assert!(Database::list_tables(&db.0, false).unwrap().is_empty());
+
+        keyed_table(&db);
+        users_table(&db);
+        assert_eq!(
+            Database::list_tables(&db.0, false).unwrap(),
+            vec!["keyed", "users"]
+        );
+        assert!(Database::incomplete_tables(&db.0).unwrap().is_empty());
+
+        let db_path = get_db_path(&db.0);
+        std::fs::write(db_path.join("orphan.json"), "[]").unwrap();
+        std::fs::write(db_path.join("ghost.schema.json"), "{}").unwrap();
+        std::fs::write(db_path.join("users.age.index.json"), "{}").unwrap();
+        assert_eq!(
+            Database::list_tables(&db.0, false).unwrap(),
+            vec!["keyed", "users"]
+        );
+        assert_eq!(
+            Database::incomplete_tables(&db.0).unwrap(),
+            vec!["ghost", "orphan"]
+        );
+
+        assert!(matches!(
+            Database::list_tables("test_list_tables_missing", false),
+            Err(DatabaseError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn backup_and_restore() {
+        let db = TestDB::new("test_backup");
+        let restored = TestDB::new("test_backup_restored");
+        Database::drop(&restored.0).unwrap();
+        let table = users_table(&db);
+        Index::create(&table, "age").unwrap();
+        let archive =
+            std::env::temp_dir().join(format!("sql_backup_{}.tar.gz", std::process::id()));
+
+        Database::backup(&db.0, &archive).unwrap();
+        // Later writes aren't in the backup
+        table.delete(eq("name", "jone")).unwrap();
+        Database::restore(&archive, &restored.0).unwrap();
+
+        let copy = Table::new(&restored.0, "users").unwrap();
+        assert!(!get_lock_path(&copy).exists());
+        assert_eq!(copy.read_schema().unwrap(), table.read_schema().unwrap());
+        assert_eq!(copy.read().unwrap().len(), 3);
+        assert_eq!(copy.count(Some(eq("age", "30"))).unwrap(), 2);
+
+        assert!(matches!(
+            Database::restore(&archive, &restored.0),
+            Err(DatabaseError::DuplicatedDB(_))
+        ));
+        assert!(matches!(
+            Database::backup("test_backup_missing", &archive),
+            Err(DatabaseError::NotFound(_))
+        ));
+
+        std::fs::write(&archive, "not an archive").unwrap();
+        assert!(matches!(
+            Database::restore(&archive, "test_backup_invalid"),
+            Err(DatabaseError::IoError(_))
+        ));
+        assert!(!Database::exists("test_backup_invalid"));
+        std::fs::remove_file(archive).unwrap();
+    }
+
+    #[test]
+    fn get_dbs() {
+        let db = TestDB::new("test_get_dbs");
+        let dbs = Database::get_dbs().unwrap();
+        assert!(dbs.contains(&db.0));
+        assert!(!dbs.iter().any(|d| d == CURR_DB));
+    }
+
+    #[test]
+    fn import_csv() {
+        let db = TestDB::new("test_import_csv");
+        let table = users_table(&db);
+        let path = get_db_path(&db.0).join("users.csv");
+
+        std::fs::write(&path, "age,name\n40,bob\nold,max\n,eve\n").unwrap();
+        let (imported, warnings) = table.import_csv(&path, true, b',').unwrap();
+        assert_eq!(imported, 2);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].row, 1);
+        assert!(matches!(warnings[0].error, TableError::TypeErr(_)));
+
+        let entries = table.read().unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[3]["name"].as_deref(), Some("bob"));
+        assert_eq!(entries[4]["age"], None);
+
+        std::fs::write(&path, "ann;25\nbo;26").unwrap();
+        let (imported, warnings) = table.import_csv(&path, false, b';').unwrap();
+        assert_eq!((imported, warnings.len()), (2, 0));
+        assert_eq!(table.count(None).unwrap(), 7);
+
+        std::fs::write(&path, "email\na@b.c\n").unwrap();
+        let res = table.import_csv(&path, true, b',');
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "email"));
+    }
+
+    #[test]
+    fn import_csv_skips_rows_breaking_constraints() {
+        let db = TestDB::new("test_import_csv_constraints");
+        let table = users_table(&db);
+        table
+            .add_check(
+                Check {
+                    name: "adult".into(),
+                    condition: Condition::leaf("age", Operator::GtEq, "18"),
+                },
+                false,
+            )
+            .unwrap();
+        let path = get_db_path(&db.0).join("users.csv");
+
+        std::fs::write(
+            &path,
+            "name,age
+bob,40
+kid,9
+max,50
+",
+        )
+        .unwrap();
+        let (imported, warnings) = table.import_csv(&path, true, b',').unwrap();
+        assert_eq!(imported, 2);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].row, 1);
+        assert!(matches!(warnings[0].error, TableError::CheckViolation(..)));
+        assert_eq!(table.count(None).unwrap(), 5);
+
+        // A file that can't be read to the end imports nothing
+        std::fs::write(
+            &path,
+            "name,age
+ann,25
+bo,26,extra
+",
+        )
+        .unwrap();
+        assert!(table.import_csv(&path, true, b',').is_err());
+        assert_eq!(table.count(None).unwrap(), 5);
+    }
+
+    #[test]
+    fn export_csv() {
+        let db = TestDB::new("test_export_csv");
+        let table = users_table(&db);
+        table
+            .insert(SelectCols::All, vec![vec![Some("max".into()), None]])
+            .unwrap();
+        let path = get_db_path(&db.0).join("users.csv");
+
+        table.export_csv(&path, true, b',').unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(csv, "name,age\njone,20\njane,30\ndoe,30\nmax,\n");
+
+        table.export_csv(&path, false, b';').unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(csv.lines().next(), Some("jone;20"));
+
+        // Exported rows can be imported back
+        let copy = Table::new(&db.0, "copy").unwrap();
+        copy.create(table.read_schema().unwrap()).unwrap();
+        let (imported, _) = copy.import_csv(&path, false, b';').unwrap();
+        assert_eq!(imported, 4);
+    }
+
+    #[test]
+    fn get_by_primary_key() {
+        let db = TestDB::new("test_get_by_primary_key");
+        let keyed = keyed_table(&db);
+
+        let entry = keyed.get("2").unwrap().unwrap();
+        assert_eq!(entry["name"].as_deref(), Some("jane"));
+        assert_eq!(keyed.get("02").unwrap().unwrap()["name"], entry["name"]);
+        assert!(keyed.get("3").unwrap().is_none());
+
+        let res = keyed.remove_col("id");
+        assert!(matches!(res, Err(TableError::PrimaryKeyCol(col)) if col == "id"));
+        keyed.drop_primary_key().unwrap();
+        assert!(matches!(keyed.get("2"), Err(TableError::NoPrimaryKey(_))));
+        keyed.remove_col("id").unwrap();
+        assert_eq!(keyed.read_schema().unwrap().cols, vec!["name"]);
+
+        let users = users_table(&db);
+        assert!(matches!(
+            users.get("jone"),
+            Err(TableError::NoPrimaryKey(_))
+        ));
+    }
+
+    #[test]
+    fn lock_timeout() {
+        let db = TestDB::new("test_lock_timeout");
+        let table = users_table(&db).with_lock_timeout(Duration::from_millis(20));
+
+        let lock = FileLock::exclusive(&get_lock_path(&table), None).unwrap();
+        let res = table.insert(SelectCols::All, vec![row(&["max", "40"])]);
+        assert!(matches!(res, Err(TableError::LockTimeout(name)) if name == "users"));
+        assert!(matches!(table.count(None), Err(TableError::LockTimeout(_))));
+
+        drop(lock);
+        table
+            .insert(SelectCols::All, vec![row(&["max", "40"])])
+            .unwrap();
+        assert_eq!(table.count(None).unwrap(), 4);
+    }
+
+    #[test]
+    fn concurrent_writes_keep_every_row() {
+        let db = TestDB::new("test_concurrent_writes");
+        users_table(&db);
+
+        std::thread::scope(|s| {
+            for writer in 0..8 {
+                let db = &db.0;
+                s.spawn(move || {
+                    // Every writer opens the lock file on its own like another process would
+                    let table = Table::new(db, "users").unwrap();
+                    for i in 0..25 {
+                        let name = format!("w{}_{}", writer, i);
+                        table
+                            .insert(SelectCols::All, vec![row(&[&name, "40"])])
+                            .unwrap();
+                        table
+                            .update(set(&[("age", "41")]), Some(eq("name", &name)))
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let table = Table::new(&db.0, "users").unwrap();
+        assert_eq!(table.count(None).unwrap(), 203);
+        assert_eq!(table.count(Some(eq("age", "41"))).unwrap(), 200);
+    }
+
+    #[test]
+    fn check_constraints() {
+        let db = TestDB::new("test_check_constraints");
+        let table = users_table(&db);
+        let condition =
+            |query: &str| match QueryParser::parse(&format!("SELECT * FROM users WHERE {}", query))
+                .unwrap()
+            {
+                Query::Table {
+                    query: TableQuery::Select { condition, .. },
+                    ..
+                } => condition.unwrap(),
+                q => panic!("Unexpected query {:?}", q),
+            };
+        let check = |name: &str, query: &str| Check {
+            name: name.into(),
+            condition: condition(query),
+        };
+
+        let res = table.add_check(check("adult", "age >= 25"), false);
+        assert!(matches!(res, Err(TableError::CheckViolation(name, 0)) if name == "adult"));
+        table.add_check(check("adult", "age >= 25"), true).unwrap();
+        let res = table.add_check(check("adult", "age > 0"), false);
+        assert!(matches!(res, Err(TableError::InvalidCheck(name)) if name == "adult"));
+        let res = table.add_check(check("mail", "email != ''"), false);
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "email"));
+
+        let res = table.insert(
+            SelectCols::All,
+            vec![row(&["max", "40"]), row(&["tim", "12"])],
+        );
+        assert!(matches!(res, Err(TableError::CheckViolation(name, 1)) if name == "adult"));
+        assert_eq!(table.count(None).unwrap(), 3);
+        // An unknown result because of a NULL passes the check
+        table
+            .insert(SelectCols::All, vec![vec![Some("max".into()), None]])
+            .unwrap();
+
+        let res = table.update(set(&[("age", "18")]), Some(eq("name", "jane")));
+        assert!(matches!(res, Err(TableError::CheckViolation(name, 1)) if name == "adult"));
+
+        table.rename_col("age", "years").unwrap();
+        let res = table.update(set(&[("years", "18")]), Some(eq("name", "jane")));
+        assert!(matches!(res, Err(TableError::CheckViolation(_, _))));
+
+        table.drop_check("adult").unwrap();
+        assert!(matches!(
+            table.drop_check("adult"),
+            Err(TableError::CheckNotFound(_))
+        ));
+        table
+            .update(set(&[("years", "18")]), Some(eq("name", "jane")))
+            .unwrap();
+
+        table
+            .add_col(
+                "score",
+                DataType::INT,
+                ColOptions {
+                    nullable: true,
+                    check: Some(condition("score BETWEEN 0 AND 10")),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let res = table.update(set(&[("score", "11")]), None);
+        assert!(matches!(res, Err(TableError::CheckViolation(name, 0)) if name == "score_check"));
+        table.remove_col("score").unwrap();
+        assert!(table.read_schema().unwrap().checks.is_empty());
+    }
+
+    #[test]
+    fn composite_unique_constraint() {
+        let db = TestDB::new("test_composite_unique_constraint");
+        let table = Table::new(&db.0, "visits").unwrap();
+        let schema = |cols: &[&str]| Schema {
+            unique_constraints: vec![UniqueConstraint {
+                name: "one_per_day".into(),
+                cols: cols.iter().map(|&c| c.into()).collect(),
+            }],
+            ..Schema::new(
+                vec!["user_id".into(), "date".into(), "page".into()],
+                vec![DataType::INT, DataType::TEXT, DataType::TEXT],
+            )
+        };
+        let res = table.create(schema(&["user_id", "day"]));
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "day"));
+        table.create(schema(&["user_id", "date"])).unwrap();
+
+        table
+            .insert(
+                SelectCols::All,
+                vec![
+                    row(&["1", "mon", "a"]),
+                    row(&["1", "tue", "a"]),
+                    row(&["2", "mon", "a"]),
+                    vec![Some("1".into()), None, Some("a".into())],
+                    vec![Some("1".into()), None, Some("a".into())],
+                ],
+            )
+            .unwrap();
+        let res = table.insert(SelectCols::All, vec![row(&["1", "mon", "b"])]);
+        assert!(matches!(
+            res,
+            Err(TableError::UniqueViolation(name, key)) if name == "one_per_day" && key == "(1, mon)"
+        ));
+        let res = table.insert(
+            SelectCols::All,
+            vec![row(&["3", "mon", "a"]), row(&["3", "mon", "b"])],
+        );
+        assert!(matches!(res, Err(TableError::UniqueViolation(_, _))));
+        assert_eq!(table.count(None).unwrap(), 5);
+
+        let res = table.add_unique_constraint("one_page", &["user_id", "page"]);
+        assert!(matches!(res, Err(TableError::UniqueViolation(name, _)) if name == "one_page"));
+        let res = table.add_unique_constraint("one_per_day", &["page"]);
+        assert!(matches!(res, Err(TableError::DuplicateConstraint(_))));
+        let res = table.remove_col("date");
+        assert!(matches!(
+            res,
+            Err(TableError::ColInConstraint(col, name)) if col == "date" && name == "one_per_day"
+        ));
+        table.rename_col("date", "day").unwrap();
+        assert_eq!(
+            table.read_schema().unwrap().unique_constraints[0].cols,
+            vec!["user_id", "day"]
+        );
+        table.drop_unique_constraint("one_per_day").unwrap();
+        assert!(matches!(
+            table.drop_unique_constraint("one_per_day"),
+            Err(TableError::ConstraintNotFound(_))
+        ));
+        table.remove_col("day").unwrap();
+    }
+
+    #[test]
+    fn create_existing_table() {
+        let db = TestDB::new("test_create_existing_table");
+        let table = users_table(&db);
+        let schema = || {
+            Schema::new(
+                vec!["name".into(), "age".into()],
+                vec![DataType::TEXT, DataType::INT],
+            )
+        };
+
+        // Creating again used to wipe the rows
+        let res = table.create(schema());
+        assert!(matches!(res, Err(TableError::TableAlreadyExists(name)) if name == "users"));
+        table.create_if_not_exists(schema()).unwrap();
+        assert_eq!(table.count(None).unwrap(), 3);
+
+        let res =
+            table.create_if_not_exists(Schema::new(vec!["name".into()], vec![DataType::TEXT]));
+        assert!(matches!(res, Err(TableError::SchemaMismatch(_))));
+
+        let other = Table::new(&db.0, "other").unwrap();
+        other.create_if_not_exists(schema()).unwrap();
+        assert_eq!(other.count(None).unwrap(), 0);
+    }
+
+    #[test]
+    fn create_with_unknown_primary_key() {
+        let db = TestDB::new("test_create_unknown_primary_key");
+        let table = Table::new(&db.0, "keyed").unwrap();
+
+        let res = table.create(Schema {
+            primary_key: Some("uuid".into()),
+            ..Schema::new(vec!["id".into()], vec![DataType::INT])
+        });
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "uuid"));
+    }
+
+    #[test]
+    fn unique_col_violation() {
+        let db = TestDB::new("test_unique_col_violation");
+        let table = Table::new(&db.0, "accounts").unwrap();
+        table
+            .create(Schema {
+                unique: vec!["email".into()],
+                ..Schema::new(
+                    vec!["name".into(), "email".into()],
+                    vec![DataType::TEXT, DataType::TEXT],
+                )
+            })
+            .unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![
+                    row(&["jone", "jone@mail.com"]),
+                    vec![Some("jane".into()), None],
+                    vec![Some("doe".into()), None],
+                ],
+            )
+            .unwrap();
+
+        let res = table.insert(SelectCols::All, vec![row(&["jone", "jone@mail.com"])]);
+        assert!(matches!(
+            res,
+            Err(TableError::UniqueViolation(col, value)) if col == "email" && value == "jone@mail.com"
+        ));
+
+        let res = table.update(set(&[("email", "jone@mail.com")]), Some(eq("name", "jane")));
+        assert!(matches!(res, Err(TableError::UniqueViolation(col, _)) if col == "email"));
+        assert_eq!(table.count(None).unwrap(), 3);
+
+        // Duplicates within the same batch reject the whole batch
+        let res = table.insert(
+            SelectCols::All,
+            vec![row(&["max", "max@mail.com"]), row(&["max", "max@mail.com"])],
+        );
+        assert!(matches!(
+            res,
+            Err(TableError::UniqueViolation(col, value)) if col == "email" && value == "max@mail.com"
+        ));
+        assert_eq!(table.count(None).unwrap(), 3);
+    }
+
+    #[test]
+    fn add_unique_col() {
+        let db = TestDB::new("test_add_unique_col");
+        let table = users_table(&db);
+        let unique = ColOptions {
+            unique: true,
+            default: Some("1".into()),
+            ..Default::default()
+        };
+
+        // Every existing row would get the same default value
+        let res = table.add_col("code", DataType::INT, unique.clone());
+        assert!(matches!(res, Err(TableError::UniqueViolation(col, _)) if col == "code"));
+        assert!(!table
+            .read_schema()
+            .unwrap()
+            .cols
+            .contains(&"code".to_string()));
+
+        let nullable = ColOptions {
+            nullable: true,
+            default: None,
+            ..unique.clone()
+        };
+        table.add_col("email", DataType::TEXT, nullable).unwrap();
+
+        let forced = ColOptions {
+            force: true,
+            ..unique
+        };
+        table.add_col("code", DataType::INT, forced).unwrap();
+        assert_eq!(table.read_schema().unwrap().unique, vec!["email", "code"]);
+    }
+
+    #[test]
+    fn insert_with_defaults() {
+        let db = TestDB::new("test_insert_with_defaults");
+        let table = Table::new(&db.0, "accounts").unwrap();
+        let schema = |default: &str| Schema {
+            nullable: vec![false, false, false],
+            defaults: vec![None, Some(default.into()), None],
+            ..Schema::new(
+                vec!["name".into(), "status".into(), "age".into()],
+                vec![
+                    DataType::TEXT,
+                    DataType::ENUM(vec!["active".into(), "banned".into()]),
+                    DataType::INT,
+                ],
+            )
+        };
+
+        let res = table.create(schema("unknown"));
+        assert!(matches!(res, Err(TableError::TypeErr(_))));
+        table.create(schema("banned")).unwrap();
+
+        let cols = |cols: &[&str]| SelectCols::Cols(cols.iter().map(|&c| c.into()).collect());
+        table
+            .insert(cols(&["name", "age"]), vec![row(&["jone", "20"])])
+            .unwrap();
+        let res = table.insert(cols(&["name", "status"]), vec![row(&["jane", "active"])]);
+        assert!(matches!(res, Err(TableError::NullViolation(col, _)) if col == "age"));
+
+        let entries = table
+            .select(SelectCols::All, None, SelectOptions::default())
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["status"].as_deref(), Some("banned"));
+
+        table
+            .add_col(
+                "role",
+                DataType::TEXT,
+                ColOptions {
+                    default: Some("member".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        table
+            .insert(cols(&["name", "age"]), vec![row(&["doe", "30"])])
+            .unwrap();
+        let entries = table
+            .select(SelectCols::All, None, SelectOptions::default())
+            .unwrap();
+        assert!(entries
+            .iter()
+            .all(|e| e["role"].as_deref() == Some("member")));
+    }
+
+    pub(crate) fn posts_table<'t>(db: &'t TestDB, cascade: bool) -> Table<'t> {
+        let table = Table::new(&db.0, "posts").unwrap();
+        table
+            .create(Schema {
+                foreign_keys: vec![ForeignKey {
+                    col: "author".into(),
+                    ref_table: "keyed".into(),
+                    ref_col: "id".into(),
+                    cascade,
+                }],
+                ..Schema::new(
+                    vec!["title".into(), "author".into()],
+                    vec![DataType::TEXT, DataType::INT],
+                )
+            })
+            .unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![row(&["a", "1"]), vec![Some("b".into()), None]],
+            )
+            .unwrap();
+        table
+    }
+
+    #[test]
+    fn describe_columns() {
+        let db = TestDB::new("test_describe_columns");
+        keyed_table(&db);
+        let posts = posts_table(&db, true);
+        posts
+            .add_check(
+                Check {
+                    name: "title_check".into(),
+                    condition: Condition::leaf("title", Operator::NotEq, ""),
+                },
+                false,
+            )
+            .unwrap();
+        posts
+            .add_unique_constraint("posts_title_author_key", &["title", "author"])
+            .unwrap();
+        Index::create(&posts, "author").unwrap();
+
+        let info = posts.describe().unwrap();
+        assert_eq!(
+            info.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+            vec!["title", "author"]
+        );
+        assert_eq!(info[0].datatype, DataType::TEXT);
+        assert_eq!(info[0].checks, vec!["title_check"]);
+        assert!(info[0].foreign_key.is_none() && !info[0].indexed);
+        assert_eq!(info[1].foreign_key.as_ref().unwrap().ref_table, "keyed");
+        assert!(info[1].nullable && info[1].indexed && info[1].checks.is_empty());
+        assert_eq!(info[1].unique_constraints, vec!["posts_title_author_key"]);
+
+        let keyed = Table::new(&db.0, "keyed").unwrap().describe().unwrap();
+        assert!(keyed[0].primary_key && !keyed[0].nullable);
+        assert!(!keyed[1].primary_key && keyed[1].default.is_none());
+
+        assert!(matches!(
+            Table::new(&db.0, "missing").unwrap().describe(),
+            Err(TableError::TableNotFond(_))
+        ));
+    }
+
+    #[test]
+    fn create_with_schema_builder() {
+        let db = TestDB::new("test_create_with_schema_builder");
+        let not_null = ColOptions {
+            default: Some("guest".into()),
+            ..Default::default()
+        };
+        let schema = Schema::new(vec!["id".into()], vec![DataType::INT])
+            .with_primary_key("id")
+            .with_col("name", DataType::TEXT, not_null)
+            .with_unique_constraint(UniqueConstraint {
+                name: "users_id_name_key".into(),
+                cols: vec!["id".into(), "name".into()],
+            });
+        let table = Table::new(&db.0, "users").unwrap();
+        table.create(schema).unwrap();
+
+        let info = table.describe().unwrap();
+        assert!(info[0].primary_key && !info[0].nullable);
+        assert!(!info[1].nullable && info[1].default.as_deref() == Some("guest"));
+        assert_eq!(info[1].unique_constraints, vec!["users_id_name_key"]);
+
+        // Every column needs a type
+        let mut schema = Schema::new(vec!["a".into(), "b".into()], vec![DataType::INT]);
+        schema.nullable = vec![true];
+        let res = Table::new(&db.0, "mismatch").unwrap().create(schema);
+        assert!(matches!(res, Err(TableError::NumberMismatch(_))));
+    }
+
+    #[test]
+    fn foreign_key_violation() {
+        let db = TestDB::new("test_foreign_key_violation");
+        let keyed = keyed_table(&db);
+        let posts = posts_table(&db, false);
+
+        let res = posts.insert(SelectCols::All, vec![row(&["c", "3"])]);
+        assert!(matches!(res, Err(TableError::ForeignKeyViolation(_))));
+        let res = posts.update(set(&[("author", "3")]), Some(eq("title", "a")));
+        assert!(matches!(res, Err(TableError::ForeignKeyViolation(_))));
+        posts
+            .update(set(&[("author", "2")]), Some(eq("title", "b")))
+            .unwrap();
+
+        let res = keyed.delete(eq("id", "1"));
+        assert!(matches!(res, Err(TableError::ReferencedRow(_))));
+        assert_eq!(keyed.count(None).unwrap(), 2);
+
+        posts.delete(eq("title", "a")).unwrap();
+        keyed.delete(eq("id", "1")).unwrap();
+        assert_eq!(keyed.count(None).unwrap(), 1);
+    }
+
+    #[test]
+    fn foreign_key_cascade() {
+        let db = TestDB::new("test_foreign_key_cascade");
+        let keyed = keyed_table(&db);
+        let posts = posts_table(&db, true);
+
+        keyed.delete(eq("id", "1")).unwrap();
+        let entries = posts
+            .select(SelectCols::All, None, SelectOptions::default())
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["title"].as_deref(), Some("b"));
+
+        let res = Table::new(&db.0, "comments").unwrap().create(Schema {
+            foreign_keys: vec![ForeignKey {
+                col: "post".into(),
+                ref_table: "posts".into(),
+                ref_col: "id".into(),
+                cascade: false,
+            }],
+            ..Schema::new(vec!["post".into()], vec![DataType::INT])
+        });
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "posts.id"));
+    }
+
+    #[test]
+    fn rename_table() {
+        let db = TestDB::new("test_rename_table");
+        let mut keyed = keyed_table(&db);
+        let posts = posts_table(&db, false);
+
+        let res = keyed.rename("posts");
+        assert!(matches!(res, Err(TableError::TableAlreadyExists(name)) if name == "posts"));
+        let mut missing = Table::new(&db.0, "missing").unwrap();
+        let res = missing.rename("other");
+        assert!(matches!(res, Err(TableError::TableNotFond(name)) if name == "missing"));
+
+        keyed.rename("people").unwrap();
+        assert_eq!(keyed.table_name, "people");
+        assert_eq!(keyed.count(None).unwrap(), 2);
+        assert!(!Table::new(&db.0, "keyed").unwrap().exist());
+        assert_eq!(
+            posts.read_schema().unwrap().foreign_keys[0].ref_table,
+            "people"
+        );
+
+        let res = keyed.delete(eq("id", "1"));
+        assert!(matches!(res, Err(TableError::ReferencedRow(_))));
+    }
+
+    #[test]
+    fn rename_table_rolls_back() {
+        let db = TestDB::new("test_rename_table_rolls_back");
+        let mut table = Table::new(&db.0, "posts").unwrap();
+        table
+            .create(Schema::new(
+                vec!["id".into(), "title".into()],
+                vec![DataType::SERIAL, DataType::TEXT],
+            ))
+            .unwrap();
+        table.insert(SelectCols::All, vec![row(&["a"])]).unwrap();
+
+        // The sequence file can't be moved over a non empty directory
+        let blocker = get_seq_path(&Table::new(&db.0, "articles").unwrap());
+        std::fs::create_dir_all(blocker.join("file")).unwrap();
+
+        assert!(matches!(
+            table.rename("articles"),
+            Err(TableError::IoErr(_))
+        ));
+        assert_eq!(table.table_name, "posts");
+        assert_eq!(table.count(None).unwrap(), 1);
+        assert!(!Table::new(&db.0, "articles").unwrap().exist());
+    }
+
+    #[test]
+    fn rename_col() {
+        let db = TestDB::new("test_rename_col");
+        let keyed = keyed_table(&db);
+        let posts = posts_table(&db, false);
+
+        let res = keyed.rename_col("uuid", "key");
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "uuid"));
+        let res = keyed.rename_col("id", "name");
+        assert!(matches!(res, Err(TableError::ColAlreadyExist(col)) if col == "name"));
+
+        keyed.rename_col("id", "key").unwrap();
+        let schema = keyed.read_schema().unwrap();
+        assert_eq!(schema.cols, vec!["key", "name"]);
+        assert_eq!(schema.primary_key.as_deref(), Some("key"));
+        assert_eq!(posts.read_schema().unwrap().foreign_keys[0].ref_col, "key");
+
+        let entries = keyed
+            .select(
+                SelectCols::All,
+                Some(eq("key", "2")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries[0]["name"].as_deref(), Some("jane"));
+        assert!(!entries[0].contains_key("id"));
+
+        let res = posts.insert(SelectCols::All, vec![row(&["c", "3"])]);
+        assert!(matches!(res, Err(TableError::ForeignKeyViolation(_))));
+        posts
+            .insert(SelectCols::All, vec![row(&["c", "2"])])
+            .unwrap();
+    }
+
+    #[test]
+    fn rename_col_with_legacy_rows() {
+        let db = TestDB::new("test_rename_col_legacy_rows");
+        let table = users_table(&db);
+
+        // Rows written before the column existed don't have its key
+        let mut entries = table.read().unwrap();
+        entries[0].remove("age");
+        table.write(&entries).unwrap();
+
+        table.rename_col("age", "years").unwrap();
+        let entries = table.read().unwrap();
+        assert!(!entries[0].contains_key("age"));
+        assert!(!entries[0].contains_key("years"));
+        assert_eq!(entries[1]["years"].as_deref(), Some("30"));
+    }
+
+    #[test]
+    fn rename_indexed_col() {
+        let db = TestDB::new("test_rename_indexed_col");
+        let table = users_table(&db);
+        Index::create(&table, "age").unwrap();
+
+        // A directory in the way of the schema makes the rename fail
+        let blocker = get_schema_path(&table).with_extension("json.tmp");
+        fs::create_dir(&blocker).unwrap();
+        assert!(table.rename_col("age", "years").is_err());
+        fs::remove_dir(&blocker).unwrap();
+        assert!(Index::exists(&table, "age") && !Index::exists(&table, "years"));
+        assert_eq!(table.count(Some(eq("age", "30"))).unwrap(), 2);
+
+        table.rename_col("age", "years").unwrap();
+        assert!(!Index::exists(&table, "age") && Index::exists(&table, "years"));
+        assert_eq!(table.count(Some(eq("years", "30"))).unwrap(), 2);
+    }
+
+    #[test]
+    fn insert_serial_col() {
+        let db = TestDB::new("test_insert_serial_col");
+        let table = Table::new(&db.0, "posts").unwrap();
+        table
+            .create(Schema::new(
+                vec!["id".into(), "title".into()],
+                vec![DataType::SERIAL, DataType::TEXT],
+            ))
+            .unwrap();
+
+        table
+            .insert(SelectCols::All, vec![row(&["a"]), row(&["b"])])
+            .unwrap();
+        table
+            .insert(SelectCols::Cols(vec!["title".into()]), vec![row(&["c"])])
+            .unwrap();
+
+        let ids = |table: &Table| {
+            table
+                .select(SelectCols::All, None, SelectOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|e| e["id"].clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&table), vec!["1", "2", "3"]);
+
+        // Serial values can't be set
+        let res = table.insert(SelectCols::All, vec![row(&["9", "d"])]);
+        assert!(matches!(res, Err(TableError::AutoIncrement(col)) if col == "id"));
+        let res = table.insert(
+            SelectCols::Cols(vec!["id".into(), "title".into()]),
+            vec![row(&["9", "d"])],
+        );
+        assert!(matches!(res, Err(TableError::AutoIncrement(col)) if col == "id"));
+
+        table.truncate(false).unwrap();
+        table.insert(SelectCols::All, vec![row(&["d"])]).unwrap();
+        assert_eq!(ids(&table), vec!["4"]);
+
+        table.truncate(true).unwrap();
+        table.insert(SelectCols::All, vec![row(&["e"])]).unwrap();
+        assert_eq!(ids(&table), vec!["1"]);
+    }
+
+    #[test]
+    fn add_nullable_col() {
+        let db = TestDB::new("test_add_nullable_col");
+        let table = users_table(&db);
+
+        let nullable = ColOptions {
+            nullable: true,
+            ..Default::default()
+        };
+        table.add_col("email", DataType::TEXT, nullable).unwrap();
+        // The existing rows need an explicit value for a `NOT NULL` column
+        let res = table.add_col("active", DataType::BOOL, ColOptions::default());
+        assert!(matches!(res, Err(TableError::NullViolation(col, _)) if col == "active"));
+        let res = table.add_col(
+            "active",
+            DataType::BOOL,
+            ColOptions {
+                default: Some("maybe".into()),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(res, Err(TableError::TypeErr(_))));
+        table
+            .add_col(
+                "active",
+                DataType::BOOL,
+                ColOptions {
+                    default: Some("false".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let entries = table
+            .select(SelectCols::All, None, SelectOptions::default())
+            .unwrap();
+        assert!(entries.iter().all(|e| e["email"].is_none()));
+        assert!(entries
+            .iter()
+            .all(|e| e["active"].as_deref() == Some("false")));
+    }
+
+    #[test]
+    fn alter_checks_existing_rows() {
+        let db = TestDB::new("test_alter_checks_existing_rows");
+        let table = users_table(&db);
+
+        let res = table.alter("name", DataType::INT, false);
+        assert!(matches!(res, Err(TableError::InvalidRow(0, value)) if value == "jone"));
+        assert_eq!(table.read_schema().unwrap().types[0], DataType::TEXT);
+
+        table.alter("age", DataType::TEXT, false).unwrap();
+        assert_eq!(table.read_schema().unwrap().types[1], DataType::TEXT);
+    }
+
+    #[test]
+    fn varchar_limits_characters() {
+        let db = TestDB::new("test_varchar_limit");
+        let table = Table::new(&db.0, "users").unwrap();
+        table
+            .create(Schema::new(vec!["name".into()], vec![DataType::VARCHAR(5)]))
+            .unwrap();
+        // 5 characters but 10 bytes
+        table
+            .insert(SelectCols::All, vec![row(&["jöñé€"]), row(&["doe"])])
+            .unwrap();
+        let res = table.insert(SelectCols::All, vec![row(&["jonathan"])]);
+        assert!(matches!(res, Err(TableError::TooLong(col, 5, 8)) if col == "name"));
+        let res = table.update(set(&[("name", "abcdef")]), Some(eq("name", "doe")));
+        assert!(matches!(res, Err(TableError::TooLong(_, 5, 6))));
+
+        let res = table.alter("name", DataType::VARCHAR(4), false);
+        assert!(matches!(res, Err(TableError::InvalidRow(0, value)) if value == "jöñé€"));
+        assert_eq!(table.read_schema().unwrap().types[0], DataType::VARCHAR(5));
+        table.delete(eq("name", "jöñé€")).unwrap();
+        table.alter("name", DataType::VARCHAR(3), false).unwrap();
+        assert_eq!(table.read_schema().unwrap().types[0], DataType::VARCHAR(3));
+    }
+
+    #[test]
+    fn coerce_written_values() {
+        let db = TestDB::new("test_coerce_values");
+        let table = Table::new(&db.0, "users").unwrap();
+        table
+            .create(Schema::new(
+                vec!["name".into(), "age".into(), "active".into()],
+                vec![DataType::TEXT, DataType::INT, DataType::BOOL],
+            ))
+            .unwrap();
+        // Strict unless the table opts in
+        let res = table.insert(SelectCols::All, vec![row(&["jone", " 42 ", "1"])]);
+        assert!(matches!(
+            res,
+            Err(TableError::TypeErr(DataTypesErr::InvalidInt(_)))
+        ));
+
+        table.set_coerce(true).unwrap();
+        assert!(table.read_schema().unwrap().coerce);
+        table
+            .insert(
+                SelectCols::All,
+                vec![row(&["jone", " 42 ", "1"]), row(&["jane", "007", "0"])],
+            )
+            .unwrap();
+        assert_eq!(table.count(Some(eq("age", "7"))).unwrap(), 1);
+        assert_eq!(
+            table.read().unwrap()[0],
+            HashMap::from([
+                ("name".to_string(), Some("jone".to_string())),
+                ("age".to_string(), Some("42".to_string())),
+                ("active".to_string(), Some("true".to_string())),
+            ])
+        );
+        table
+            .update(set(&[("age", "30.0")]), Some(eq("name", "jane")))
+            .unwrap();
+        assert_eq!(table.count(Some(eq("age", "30"))).unwrap(), 1);
+
+        let res = table.insert(SelectCols::All, vec![row(&["doe", "42.5", "0"])]);
+        assert!(matches!(
+            res,
+            Err(TableError::CoercionErr(col, e)) if col == "age" && e.reason == "not a whole number"
+        ));
+        let res = table.update(set(&[("active", "yes")]), None);
+        assert!(matches!(res, Err(TableError::CoercionErr(col, _)) if col == "active"));
+        assert_eq!(table.read().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn write_ahead_log_recovery() {
+        let db = TestDB::new("test_wal_recovery");
+        let table = users_table(&db);
+        Index::create(&table, "age").unwrap();
+        let wal = get_wal_path(&table);
+        assert!(!wal.exists());
+        assert!(!table.recover().unwrap());
+
+        // A crash after logging a write, the data file still holds the old rows
+        let logged = json!([{"name": "max", "age": "40"}, {"name": "jane", "age": "40"}]);
+        fs::write(&wal, format!("[]\n{}\n[{{\"name\"", logged)).unwrap();
+        assert_eq!(table.read().unwrap().len(), 2);
+        assert!(!wal.exists());
+        assert_eq!(table.count(Some(eq("age", "40"))).unwrap(), 2);
+
+        // A crash while logging, the write never happened
+        fs::write(&wal, r#"[{"name":"max","age""#).unwrap();
+        assert!(!table.recover().unwrap());
+        assert!(!wal.exists());
+        assert_eq!(table.read().unwrap().len(), 2);
+
+        fs::write(&wal, "not json\n").unwrap();
+        assert!(matches!(table.read(), Err(TableError::SerializationErr(_))));
+        fs::remove_file(&wal).unwrap();
+
+        table
+            .insert(SelectCols::All, vec![row(&["doe", "20"])])
+            .unwrap();
+        assert!(!wal.exists());
+        fs::write(&wal, "[]\n").unwrap();
+        table.drop().unwrap();
+        assert!(!wal.exists());
+    }
+
+    #[test]
+    fn blob_values() {
+        let db = TestDB::new("test_blob_values");
+        let table = Table::new(&db.0, "files").unwrap();
+        table
+            .create(Schema::new(
+                vec!["name".into(), "data".into()],
+                vec![DataType::TEXT, DataType::BLOB(4)],
+            ))
+            .unwrap();
+        table
+            .insert(
+                SelectCols::All,
+                vec![row(&["a", "AAEC/w"]), row(&["b", "aGk="])],
+            )
+            .unwrap();
+        let res = table.insert(SelectCols::All, vec![row(&["c", "aGVsbG8="])]);
+        assert!(matches!(res, Err(TableError::TooLarge(col, 4, 5)) if col == "data"));
+        let res = table.insert(SelectCols::All, vec![row(&["c", "hello!"])]);
+        assert!(matches!(
+            res,
+            Err(TableError::TypeErr(DataTypesErr::InvalidBlob(_)))
+        ));
+
+        // Unpadded values match the stored padded form
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(eq("data", "AAEC/w")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["data"].as_deref(), Some("AAEC/w=="));
+
+        let res = table.select(
+            SelectCols::All,
+            Some(Condition::leaf("data", Operator::Gt, "aGk=")),
+            SelectOptions::default(),
+        );
+        assert!(matches!(res, Err(TableError::NotOrdered(col, op)) if col == "data" && op == ">"));
+        let res = table.count(Some(Condition::not(Condition::leaf(
+            "data",
+            Operator::Between,
+            ConditionValue::Range("A".into(), "B".into()),
+        ))));
+        assert!(matches!(res, Err(TableError::NotOrdered(_, _))));
+    }
+
+    #[test]
+    fn alter_to_enum_checks_existing_rows() {
+        let db = TestDB::new("test_alter_to_enum");
+        let table = users_table(&db);
+        let names =
+            |values: &[&str]| DataType::ENUM(values.iter().map(|v| v.to_string()).collect());
+
+        let res = table.alter("name", names(&["jone", "jane"]), false);
+        assert!(matches!(res, Err(TableError::InvalidRow(2, value)) if value == "doe"));
+        assert!(matches!(
+            table.alter("name", names(&[]), true),
+            Err(TableError::TypeErr(DataTypesErr::InvalidEnum(_)))
+        ));
+        assert_eq!(table.read_schema().unwrap().types[0], DataType::TEXT);
+
+        table
+            .alter("name", names(&["jone", "jane", "doe"]), false)
+            .unwrap();
+        assert!(matches!(
+            table.insert(SelectCols::All, vec![row(&["max", "40"])]),
+            Err(TableError::TypeErr(DataTypesErr::InvalidEnum(_)))
+        ));
+
+        let empty = Table::new(&db.0, "empty").unwrap();
+        assert!(matches!(
+            empty.create(Schema::new(vec!["kind".into()], vec![names(&[])])),
+            Err(TableError::TypeErr(DataTypesErr::InvalidEnum(_)))
+        ));
+    }
+
+    #[test]
+    fn alter_converts_existing_rows() {
+        let db = TestDB::new("test_alter_converts_existing_rows");
+        let table = users_table(&db);
+        table.alter("age", DataType::FLOAT, false).unwrap();
+        table
+            .insert(SelectCols::All, vec![row(&["7", "41.0"])])
+            .unwrap();
+        table.alter("age", DataType::INT, false).unwrap();
+        let entries = table.read().unwrap();
+        assert_eq!(entries[3]["age"].as_deref(), Some("41"));
+
+        table.alter("name", DataType::INT, true).unwrap();
+        let names = table
+            .read()
+            .unwrap()
+            .into_iter()
+            .map(|e| e["name"].clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["0", "0", "0", "7"]);
+    }
+
+    #[test]
+    fn not_null_rejects_empty_string() {
+        let schema = Schema {
+            nullable: vec![false, true],
+            ..Schema::new(
+                vec!["name".into(), "bio".into()],
+                vec![DataType::TEXT, DataType::TEXT],
+            )
+        };
+
+        let empty = String::new();
+        assert!(matches!(
+            schema.validate("name", Some(&empty)),
+            Err(TableError::NullViolation(col, _)) if col == "name"
+        ));
+        assert!(schema.validate("bio", Some(&empty)).is_ok());
+    }
+
+    #[test]
+    fn read_schema_without_nullable() {
+        let db = TestDB::new("test_read_schema_without_nullable");
+        let table = users_table(&db);
+        std::fs::write(
+            get_schema_path(&table),
+            r#"{ "cols": ["name", "age"], "types": ["TEXT", "INT"] }"#,
+        )
+        .unwrap();
+
+        let schema = table.read_schema().unwrap();
+        assert_eq!(schema.nullable, vec![true, true]);
+        assert_eq!(schema.defaults, vec![None, None]);
+        assert_eq!(schema.primary_key, None);
+    }
+
+    #[test]
+    fn update_matching_rows() {
+        let db = TestDB::new("test_update_matching_rows");
+        let table = users_table(&db);
+
+        let updated = table
+            .update(set(&[("age", "31")]), Some(eq("age", "30")))
+            .unwrap();
+        assert_eq!(updated, 2);
+
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(eq("age", "31")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| e["name"].as_deref() != Some("jone")));
+    }
+
+    #[test]
+    fn update_all_rows_without_condition() {
+        let db = TestDB::new("test_update_all_rows");
+        let table = users_table(&db);
+
+        let updated = table.update(set(&[("age", "1")]), None).unwrap();
+        assert_eq!(updated, 3);
+
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(eq("age", "1")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn update_unknown_col() {
+        let db = TestDB::new("test_update_unknown_col");
+        let table = users_table(&db);
+
+        let res = table.update(set(&[("email", "a@b.c")]), None);
+        assert!(matches!(res, Err(TableError::ColNotFound(col)) if col == "email"));
+    }
+
+    #[test]
+    fn update_with_invalid_value() {
+        let db = TestDB::new("test_update_invalid_value");
+        let table = users_table(&db);
+
+        let res = table.update(set(&[("age", "old")]), Some(eq("name", "jone")));
+        assert!(matches!(
+            res,
+            Err(TableError::TypeErr(DataTypesErr::InvalidInt(_)))
+        ));
+
+        // Nothing should be written when the validation fails
+        let entries = table
+            .select(
+                SelectCols::All,
+                Some(eq("name", "jone")),
+                SelectOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entries[0]["age"].as_deref(), Some("20"));
+    }
+
+    #[test]
+    fn update_without_matches() {
+        let db = TestDB::new("test_update_without_matches");
+        let table = users_table(&db);
+
+        let updated = table
+            .update(set(&[("age", "40")]), Some(eq("name", "nobody")))
+            .unwrap();
+        assert_eq!(updated, 0);
+    }
 }
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{
+    query_parser::{Condition, SelectCols},
+    table::{Cascade, Schema, Table, TableEntries, TableError},
+};
+
+#[derive(Debug, Error)]
+pub enum TransactionError {
+    #[error("Savepoint not found")]
+    SavepointNotFound(String),
+}
+
+type TableResult<T> = Result<T, TableError>;
+
+/// The changes not written yet, cloned for every savepoint.
+#[derive(Clone)]
+struct Pending {
+    entries: TableEntries,
+    seq: i64,
+    cascades: Vec<Cascade>,
+}
+
+/// A savepoint created by [`Transaction::savepoint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavepointHandle {
+    pub name: String,
+}
+
+/// A group of writes to a table applied together.
+///
+/// The rows are read once when the transaction starts and every `insert`, `update` and `delete`
+/// works on that in-memory copy. Nothing touches the disk until [`Transaction::commit`], which
+/// writes the table once. Dropping the transaction without committing discards the changes.
+///
+/// Savepoints snapshot the pending changes so the transaction can go back to them with
+/// [`Transaction::rollback_to`] without losing the earlier work.
+///
+/// Changes made to the table by others while the transaction is open are overwritten on commit.
+pub struct Transaction<'a> {
+    table: &'a Table<'a>,
+    schema: Schema,
+    pending: Pending,
+    savepoints: Vec<(String, Pending)>,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn new(table: &'a Table<'a>) -> TableResult<Self> {
+        Ok(Self {
+            table,
+            schema: table.read_schema()?,
+            pending: Pending {
+                entries: table.read()?,
+                seq: table.read_seq()?,
+                cascades: Vec::new(),
+            },
+            savepoints: Vec::new(),
+        })
+    }
+
+    /// Same as [`Table::insert`], a rejected insert leaves the pending rows untouched.
+    pub fn insert(
+        &mut self,
+        cols: SelectCols,
+        values: Vec<Vec<Option<String>>>,
+    ) -> TableResult<()> {
+        self.table.insert_entries(
+            &self.schema,
+            &mut self.pending.entries,
+            &mut self.pending.seq,
+            cols,
+            values,
+        )?;
+        Ok(())
+    }
+
+    /// Same as [`Table::update`], a rejected update leaves the pending rows untouched.
+    pub fn update(
+        &mut self,
+        set: HashMap<String, Option<String>>,
+        condition: Option<Condition>,
+    ) -> TableResult<usize> {
+        let mut entries = self.pending.entries.clone();
+        let updated = self
+            .table
+            .update_entries(&self.schema, &mut entries, set, condition)?;
+        self.pending.entries = entries;
+        Ok(updated)
+    }
+
+    /// Same as [`Table::delete`], the deletes cascading to other tables run on commit.
+    pub fn delete(&mut self, condition: Condition) -> TableResult<()> {
+        let (entries, cascades) =
+            self.table
+                .delete_entries(&self.schema, self.pending.entries.clone(), condition)?;
+        self.pending.entries = entries;
+        self.pending.cascades.extend(cascades);
+        Ok(())
+    }
+
+    /// The rows as they will be written on commit.
+    pub fn entries(&self) -> &TableEntries {
+        &self.pending.entries
+    }
+
+    /// Snapshot the pending changes under `name`. Savepoints nest, a name used twice refers
+    /// to the latest one.
+    pub fn savepoint(&mut self, name: &str) -> SavepointHandle {
+        self.savepoints
+            .push((name.to_string(), self.pending.clone()));
+        SavepointHandle { name: name.into() }
+    }
+
+    /// Go back to the changes at the savepoint `name`. The savepoint is kept, the ones created
+    /// after it are released.
+    pub fn rollback_to(&mut self, name: &str) -> Result<(), TransactionError> {
+        let pos = self.savepoint_pos(name)?;
+        self.savepoints.truncate(pos + 1);
+        self.pending = self.savepoints[pos].1.clone();
+        Ok(())
+    }
+
+    /// Forget the savepoint `name` and the ones created after it, the changes are kept.
+    pub fn release_savepoint(&mut self, name: &str) -> Result<(), TransactionError> {
+        let pos = self.savepoint_pos(name)?;
+        self.savepoints.truncate(pos);
+        Ok(())
+    }
+
+    fn savepoint_pos(&self, name: &str) -> Result<usize, TransactionError> {
+        self.savepoints
+            .iter()
+            .rposition(|(n, _)| n == name)
+            .ok_or_else(|| TransactionError::SavepointNotFound(name.to_string()))
+    }
+
+    /// Write the pending rows to the table.
+    pub fn commit(self) -> TableResult<()> {
+        let pending = self.pending;
+        if pending.seq != self.table.read_seq()? {
+            self.table.write_seq(pending.seq)?;
+        }
+        self.table.write(&pending.entries)?;
+        self.table.run_cascades(pending.cascades)
+    }
+
+    /// Discard the pending changes, same as dropping the transaction.
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        query_parser::SelectCols,
+        table::{
+            tests::{eq, keyed_table, posts_table, row, set, users_table, TestDB},
+            TableError,
+        },
+    };
+
+    use super::{Transaction, TransactionError};
+
+    #[test]
+    fn commit_writes_pending_changes() {
+        let db = TestDB::new("test_transaction_commit");
+        let table = users_table(&db);
+
+        let mut tx = Transaction::new(&table).unwrap();
+        tx.insert(SelectCols::All, vec![row(&["max", "40"])])
+            .unwrap();
+        assert_eq!(
+            tx.update(set(&[("age", "31")]), Some(eq("age", "30")))
+                .unwrap(),
+            2
+        );
+        tx.delete(eq("name", "jone")).unwrap();
+        assert_eq!(tx.entries().len(), 3);
+        assert_eq!(table.count(None).unwrap(), 3);
+        assert_eq!(table.count(Some(eq("age", "31"))).unwrap(), 0);
+
+        tx.commit().unwrap();
+        assert_eq!(table.count(None).unwrap(), 3);
+        assert_eq!(table.count(Some(eq("age", "31"))).unwrap(), 2);
+        assert_eq!(table.count(Some(eq("name", "jone"))).unwrap(), 0);
+    }
+
+    #[test]
+    fn drop_discards_pending_changes() {
+        let db = TestDB::new("test_transaction_rollback");
+        let table = users_table(&db);
+
+        {
+            let mut tx = Transaction::new(&table).unwrap();
+            tx.delete(eq("name", "jone")).unwrap();
+        }
+        let mut tx = Transaction::new(&table).unwrap();
+        tx.insert(SelectCols::All, vec![row(&["max", "40"])])
+            .unwrap();
+        tx.rollback();
+        assert_eq!(table.count(None).unwrap(), 3);
+
+        // A rejected write leaves the earlier ones in place
+        let mut tx = Transaction::new(&table).unwrap();
+        tx.update(set(&[("age", "31")]), Some(eq("name", "jane")))
+            .unwrap();
+        let res = tx.update(set(&[("age", "old")]), None);
+        assert!(matches!(res, Err(TableError::TypeErr(_))));
+        tx.commit().unwrap();
+        assert_eq!(table.count(Some(eq("age", "31"))).unwrap(), 1);
+    }
+
+    #[test]
+    fn cascades_run_on_commit() {
+        let db = TestDB::new("test_transaction_cascade");
+        let keyed = keyed_table(&db);
+        let posts = posts_table(&db, true);
+
+        let mut tx = Transaction::new(&keyed).unwrap();
+        tx.delete(eq("id", "1")).unwrap();
+        assert_eq!(posts.count(None).unwrap(), 2);
+
+        tx.commit().unwrap();
+        assert_eq!(keyed.count(None).unwrap(), 1);
+        assert_eq!(posts.count(None).unwrap(), 1);
+    }
+
+    #[test]
+    fn savepoints() {
+        let db = TestDB::new("test_transaction_savepoints");
+        let table = users_table(&db);
+        let names = |tx: &Transaction| {
+            let mut names = tx
+                .entries()
+                .iter()
+                .map(|e| e["name"].clone().unwrap())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let mut tx = Transaction::new(&table).unwrap();
+        tx.delete(eq("name", "jone")).unwrap();
+        let handle = tx.savepoint("a");
+        assert_eq!(handle.name, "a");
+        tx.delete(eq("name", "jane")).unwrap();
+        tx.savepoint("b");
+        tx.delete(eq("name", "doe")).unwrap();
+
+        tx.rollback_to("b").unwrap();
+        assert_eq!(names(&tx), vec!["doe"]);
+        tx.rollback_to("a").unwrap();
+        assert_eq!(names(&tx), vec!["doe", "jane"]);
+        // Rolling back to `a` released `b`
+        assert!(matches!(
+            tx.rollback_to("b"),
+            Err(TransactionError::SavepointNotFound(name)) if name == "b"
+        ));
+
+        tx.release_savepoint("a").unwrap();
+        assert!(tx.release_savepoint("a").is_err());
+        assert_eq!(names(&tx), vec!["doe", "jane"]);
+
+        tx.commit().unwrap();
+        assert_eq!(table.count(None).unwrap(), 2);
+    }
+}
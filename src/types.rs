@@ -1,10 +1,15 @@
-use std::num::ParseIntError;
+use std::{cmp::Ordering, num::ParseIntError};
 
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use uuid::Uuid;
 
-use crate::regex::{RE_ENUM, RE_ENUM_VALUES, RE_VARCHAR};
+use crate::{
+    regex::{RE_BLOB, RE_DECIMAL, RE_ENUM, RE_RANGE, RE_VARCHAR},
+    utils::{base64_decode, base64_decoded_len, base64_encode, get_quoted_values},
+};
 
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum DataTypesErr {
@@ -22,28 +27,171 @@ pub enum DataTypesErr {
     InvalidBool(String),
     #[error("Invalid string")]
     InvalidStr(String),
+    #[error("Invalid date")]
+    InvalidDate(String),
+    #[error("Invalid timestamp")]
+    InvalidTimestamp(String),
+    #[error("Invalid UUID")]
+    InvalidUuid(String),
+    #[error("Invalid JSON")]
+    InvalidJson(String),
+    #[error("Invalid blob")]
+    InvalidBlob(String),
+    #[error("Unknown type `{0}`, expected one of {}", DataType::names().join(", "))]
+    UnknownType(String),
 }
 
+/// A value that can't be coerced to the type of its column, see [`DataType::coerce`].
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Can't coerce `{value}` to {dtype}: {reason}")]
+pub struct CoercionError {
+    pub value: String,
+    pub dtype: String,
+    pub reason: String,
+}
+
+/// A stored value converted to the type of its column, see [`DataType::to_value`].
+///
+/// It serializes to the matching JSON type, e.g. `Int(3)` to `3` and `Null` to `null`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// Values of the same type are ordered, integers and floats are compared as numbers. `NULL`
+/// isn't ordered, not even with itself.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Int(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Text(a), Value::Text(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    /// The string a value is stored as, `None` for `NULL`.
+    pub fn to_raw(&self) -> Option<String> {
+        match self {
+            Value::Null => None,
+            Value::Bool(b) => Some(b.to_string()),
+            Value::Int(i) => Some(i.to_string()),
+            Value::Float(f) => Some(f.to_string()),
+            Value::Text(s) => Some(s.clone()),
+        }
+    }
+
+    /// The bytes of a `BLOB` value, `None` for `NULL` or a value that isn't base64.
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            Value::Text(s) => base64_decode(s),
+            _ => None,
+        }
+    }
+}
+
+/// The format `DATE` values are stored in.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The format `DATETIME` values are stored in.
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A `DATE`, `DATETIME` or `TIMESTAMP` value that is replaced by the current time when it's
+/// stored, also written `NOW()`.
+pub const NOW: &str = "NOW";
+
+/// A `UUID` value that is replaced by a new random (v4) UUID when it's stored, also written
+/// `GEN_RANDOM_UUID()`.
+pub const GEN_RANDOM_UUID: &str = "GEN_RANDOM_UUID";
+
+/// The most bytes a `BLOB` column without a size holds.
+pub const BLOB_SIZE: usize = 65_535;
+
+/// The types written as a bare name, the parameterized ones are matched by their regex in
+/// [`DataType::parse`]. A name listed twice is an alias, [`DataType::sql_name`] uses the first.
+const NAMED_TYPES: &[(&str, DataType)] = &[
+    ("INTEGER", DataType::INTEGER),
+    // Integers are 64-bit whatever their name
+    ("BIGINT", DataType::INTEGER),
+    ("INT", DataType::INT),
+    ("SERIAL", DataType::SERIAL),
+    ("FLOAT", DataType::FLOAT),
+    ("DOUBLE", DataType::DOUBLE),
+    ("DEC", DataType::DEC),
+    ("TEXT", DataType::TEXT),
+    ("BOOLEAN", DataType::BOOLEAN),
+    ("BOOL", DataType::BOOL),
+    ("DATE", DataType::DATE),
+    ("DATETIME", DataType::DATETIME),
+    ("TIMESTAMP", DataType::TIMESTAMP),
+    ("UUID", DataType::UUID),
+    ("JSON", DataType::JSON),
+];
+
+/// The parameterized types as they're listed in [`DataTypesErr::UnknownType`].
+const PARAMETERIZED_TYPES: &[&str] = &[
+    "VARCHAR(size)",
+    "RANGE(min, max)",
+    "DECIMAL(places)",
+    "ENUM(values)",
+    "BLOB(size)",
+];
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
 pub enum DataType {
     // Numeric datatypes
     INTEGER,
     INT,
+    /// An `INT` filled from the table sequence on insert
+    SERIAL,
+    /// An `INT` between the optional inclusive bounds `(min, max)`
+    RANGE(Option<i64>, Option<i64>),
     FLOAT,
+    DOUBLE,
     DEC,
+    /// A `FLOAT` rounded to the given number of decimal places when stored
+    DECIMAL(u8),
     // String datatypes
     TEXT,
     VARCHAR(usize),
     ENUM(Vec<String>),
     BOOLEAN,
     BOOL,
+    /// A `YYYY-MM-DD` date
+    DATE,
+    /// A `YYYY-MM-DD HH:MM:SS` date and time, without a time zone
+    DATETIME,
+    /// An RFC 3339 timestamp, stored with the offset it was given in
+    TIMESTAMP,
+    /// A UUID, stored lowercase and hyphenated
+    UUID,
+    /// A JSON document, stored as it was given
+    JSON,
+    /// Binary data of at most the given number of bytes, stored as padded base64
+    BLOB(usize),
 }
 
 impl DataType {
+    /// Parse a type as written in SQL, in any case, like `int`, `BIGINT` or `VARCHAR(32)`.
+    ///
+    /// Fails with [`DataTypesErr::UnknownType`] listing the supported types if `datatype`
+    /// isn't one of them.
     pub fn parse(datatype: &str) -> Result<Self, DataTypesErr> {
         let re_varchar = Regex::new(RE_VARCHAR).unwrap();
         let re_enum = Regex::new(RE_ENUM).unwrap();
-        let re_enum_values = Regex::new(RE_ENUM_VALUES).unwrap();
+        let re_range = Regex::new(RE_RANGE).unwrap();
+        let re_decimal = Regex::new(RE_DECIMAL).unwrap();
+        let re_blob = Regex::new(RE_BLOB).unwrap();
         let dt = datatype.trim();
 
         if let Some(caps) = re_varchar.captures(dt) {
@@ -58,79 +206,446 @@ impl DataType {
             return Ok(DataType::VARCHAR(size));
         }
 
+        if let Some(caps) = re_range.captures(dt) {
+            let bound = |name| match caps.name(name) {
+                Some(bound) => bound
+                    .as_str()
+                    .parse::<i64>()
+                    .map(Some)
+                    .map_err(|_| DataTypesErr::InvalidType(dt.into())),
+                None => Ok(None),
+            };
+            let (min, max) = (bound("min")?, bound("max")?);
+            if min.zip(max).is_some_and(|(min, max)| min > max) {
+                return Err(DataTypesErr::InvalidType(dt.into()));
+            }
+
+            return Ok(DataType::RANGE(min, max));
+        }
+
+        if let Some(caps) = re_decimal.captures(dt) {
+            return match caps["places"].parse::<u8>() {
+                Ok(places) => Ok(DataType::DECIMAL(places)),
+                Err(_) => Err(DataTypesErr::InvalidType(dt.into())),
+            };
+        }
+
+        if let Some(caps) = re_blob.captures(dt) {
+            return match caps.name("size") {
+                Some(size) => size
+                    .as_str()
+                    .parse::<usize>()
+                    .map(DataType::BLOB)
+                    .map_err(|_| DataTypesErr::InvalidType(dt.into())),
+                None => Ok(DataType::BLOB(BLOB_SIZE)),
+            };
+        }
+
         if let Some(caps) = re_enum.captures(dt) {
-            let values = re_enum_values
-                .captures_iter(&caps["values"])
-                .map(|caps| caps["value"].trim().to_string())
-                .filter(|v| !v.is_empty())
-                .collect::<Vec<_>>();
-            return Ok(DataType::ENUM(values));
-        }
-
-        let dt = dt.to_uppercase();
-        let dt = match dt {
-            _ if DataType::INTEGER.as_string() == dt => DataType::INTEGER,
-            _ if DataType::INT.as_string() == dt => DataType::INT,
-            _ if DataType::FLOAT.as_string() == dt => DataType::FLOAT,
-            _ if DataType::DEC.as_string() == dt => DataType::DEC,
-            _ if DataType::TEXT.as_string() == dt => DataType::TEXT,
-            _ if DataType::BOOLEAN.as_string() == dt => DataType::BOOLEAN,
-            _ if DataType::BOOL.as_string() == dt => DataType::BOOL,
-
-            _ => return Err(DataTypesErr::InvalidType(datatype.trim().into())),
-        };
+            let dt = DataType::ENUM(get_quoted_values(&caps["values"]));
+            dt.validate()?;
+            return Ok(dt);
+        }
+
+        NAMED_TYPES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(dt))
+            .map(|(_, dtype)| dtype.clone())
+            .ok_or_else(|| DataTypesErr::UnknownType(dt.into()))
+    }
+
+    /// The supported types, as listed in [`DataTypesErr::UnknownType`].
+    pub fn names() -> Vec<&'static str> {
+        NAMED_TYPES
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(PARAMETERIZED_TYPES.iter().copied())
+            .collect()
+    }
 
-        return Ok(dt);
+    /// The type as written in SQL, e.g. by `SHOW CREATE TABLE`. [`DataType::parse`] reads it
+    /// back as the same type.
+    pub fn sql_name(&self) -> String {
+        match self {
+            DataType::RANGE(min, max) => {
+                let bound = |b: &Option<i64>| b.map(|b| b.to_string()).unwrap_or_default();
+                format!("RANGE({}, {})", bound(min), bound(max))
+            }
+            DataType::ENUM(values) => {
+                let values = values
+                    .iter()
+                    .map(|v| format!("'{}'", v))
+                    .collect::<Vec<_>>();
+                format!("ENUM({})", values.join(", "))
+            }
+            DataType::VARCHAR(size) => format!("VARCHAR({})", size),
+            DataType::DECIMAL(places) => format!("DECIMAL({})", places),
+            DataType::BLOB(size) => format!("BLOB({})", size),
+            dtype => NAMED_TYPES
+                .iter()
+                .find(|(_, named)| named == dtype)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_else(|| format!("{:?}", dtype)),
+        }
     }
 
+    #[deprecated(note = "use `DataType::sql_name`")]
     pub fn as_string(&self) -> String {
-        format!("{:?}", self)
+        self.sql_name()
     }
 
     pub fn is_valid(&self, raw: &str) -> Result<(), DataTypesErr> {
-        return match self {
-            DataType::INTEGER | DataType::INT if raw.parse::<i64>().is_err() => Err(
-                DataTypesErr::InvalidInt(format!("'{}' is not a valid {:?}", raw, self)),
-            ),
-            DataType::FLOAT | DataType::DEC if raw.parse::<f64>().is_err() => Err(
+        match self {
+            _ if self.is_integer() && raw.parse::<i64>().is_err() => Err(DataTypesErr::InvalidInt(
+                format!("'{}' is not a valid {}", raw, self.sql_name()),
+            )),
+            DataType::RANGE(min, max)
+                if min.is_some_and(|min| raw.parse::<i64>().ok() < Some(min))
+                    || max.is_some_and(|max| raw.parse::<i64>().ok() > Some(max)) =>
+            {
+                Err(DataTypesErr::InvalidInt(format!(
+                    "'{}' is out of {}",
+                    raw,
+                    self.sql_name()
+                )))
+            }
+            _ if self.is_numeric() && !self.is_integer() && parse_float(raw).is_none() => Err(
                 DataTypesErr::InvalidFloat(format!("'{}' is not a valid {:?}", raw, self)),
             ),
-            DataType::VARCHAR(max_len) if &raw.len() > max_len => Err(DataTypesErr::InvalidStr(
-                format!("Max length exceed of `{}`. Max len = {}", raw, max_len),
-            )),
+            // Characters rather than bytes, so multibyte text gets the same limit
+            DataType::VARCHAR(max_len) if raw.chars().count() > *max_len => {
+                Err(DataTypesErr::InvalidStr(format!(
+                    "Max length exceed of `{}`. Max len = {}",
+                    raw, max_len
+                )))
+            }
             DataType::ENUM(values) if values.iter().position(|v| v == raw).is_none() => {
                 Err(DataTypesErr::InvalidEnum(format!(
                     "`{}` is not valid enum. must be one of these {:?}",
                     raw, values
                 )))
             }
-            DataType::BOOLEAN | DataType::BOOL if raw.parse::<bool>().is_err() => Err(
+            DataType::BOOLEAN | DataType::BOOL if parse_bool(raw).is_none() => Err(
                 DataTypesErr::InvalidBool(format!("`{}` is not a valid boolean", raw)),
             ),
+            DataType::DATE if !is_call(raw, NOW) && parse_date(raw).is_none() => {
+                Err(DataTypesErr::InvalidDate(format!(
+                    "`{}` is not a valid date, expected YYYY-MM-DD",
+                    raw
+                )))
+            }
+            DataType::DATETIME if !is_call(raw, NOW) && parse_datetime(raw).is_none() => {
+                Err(DataTypesErr::InvalidTimestamp(format!(
+                    "`{}` is not a valid DATETIME, expected YYYY-MM-DD HH:MM:SS",
+                    raw
+                )))
+            }
+            DataType::TIMESTAMP if !is_call(raw, NOW) && parse_timestamp(raw).is_none() => {
+                Err(DataTypesErr::InvalidTimestamp(format!(
+                    "`{}` is not a valid RFC 3339 timestamp",
+                    raw
+                )))
+            }
+            DataType::UUID if !is_call(raw, GEN_RANDOM_UUID) && Uuid::try_parse(raw).is_err() => {
+                Err(DataTypesErr::InvalidUuid(format!(
+                    "`{}` is not a valid UUID",
+                    raw
+                )))
+            }
+            DataType::JSON if serde_json::from_str::<serde_json::Value>(raw).is_err() => Err(
+                DataTypesErr::InvalidJson(format!("`{}` is not a valid JSON document", raw)),
+            ),
+            // The size is checked before decoding so a huge value isn't decoded for nothing
+            DataType::BLOB(max_size) if base64_decoded_len(raw) > *max_size => {
+                Err(DataTypesErr::InvalidBlob(format!(
+                    "Blob of {} bytes exceeds the max size of {} bytes",
+                    base64_decoded_len(raw),
+                    max_size
+                )))
+            }
+            DataType::BLOB(_) if base64_decode(raw).is_none() => Err(DataTypesErr::InvalidBlob(
+                format!("`{}` is not valid base64", raw),
+            )),
             _ => Ok(()),
+        }
+    }
+
+    /// Convert a raw value to the canonical form of this type, e.g. `"3.0"` to `3` for an
+    /// `INT` or `"TRUE"` to `true` for a `BOOL`.
+    pub fn convert(&self, raw: &str) -> Result<String, DataTypesErr> {
+        let converted = match self {
+            _ if self.is_integer() => match raw.parse::<f64>() {
+                Ok(f) if raw.parse::<i64>().is_err() && f.fract() == 0.0 => format!("{}", f as i64),
+                _ => raw.to_string(),
+            },
+            _ => self.normalize(raw),
+        };
+        self.is_valid(&converted)?;
+
+        Ok(converted)
+    }
+
+    /// Coerce a written value towards the form this type stores, for tables that opt in with
+    /// [`Table::set_coerce`](crate::table::Table::set_coerce).
+    ///
+    /// Whitespace around the value is trimmed, except for text types where it's part of the
+    /// value. Integers lose leading zeros and `+` signs, and floats without a fractional part
+    /// like `42.0` or `1e3` are accepted. Floats are written in their shortest form, and
+    /// booleans accept `1` / `0` and any case. Fails only for the numeric and boolean rules, the
+    /// result still has to pass [`DataType::is_valid`].
+    pub fn coerce(&self, raw: &str) -> Result<String, CoercionError> {
+        let fail = |reason: &str| CoercionError {
+            value: raw.to_string(),
+            dtype: self.sql_name(),
+            reason: reason.to_string(),
         };
+        let trimmed = raw.trim();
+
+        match self {
+            DataType::TEXT | DataType::VARCHAR(_) | DataType::ENUM(_) | DataType::JSON => {
+                Ok(raw.to_string())
+            }
+            _ if self.is_integer() => match (trimmed.parse::<i64>(), parse_float(trimmed)) {
+                (Ok(i), _) => Ok(i.to_string()),
+                // Beyond 2^63 the float can't be an `i64`
+                (_, Some(f)) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+                    Ok((f as i64).to_string())
+                }
+                (_, Some(_)) => Err(fail("not a whole number")),
+                (_, None) => Err(fail("not a number")),
+            },
+            _ if self.is_numeric() => parse_float(trimmed)
+                .map(|f| f.to_string())
+                .ok_or_else(|| fail("not a finite number")),
+            DataType::BOOLEAN | DataType::BOOL => parse_bool(trimmed)
+                .map(|b| b.to_string())
+                .ok_or_else(|| fail("not true, false, 1 or 0")),
+            _ => Ok(trimmed.to_string()),
+        }
+    }
+
+    /// Check the type itself rather than a value, an `ENUM` needs at least one value.
+    pub fn validate(&self) -> Result<(), DataTypesErr> {
+        match self {
+            DataType::ENUM(values) if values.is_empty() => Err(DataTypesErr::InvalidEnum(
+                "ENUM needs at least one allowed value".into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The typed form of a stored value, a value that doesn't fit the type (e.g. written before
+    /// the column type was changed) is kept as [`Value::Text`].
+    pub fn to_value(&self, raw: Option<&str>) -> Value {
+        let Some(raw) = raw else {
+            return Value::Null;
+        };
+        let value = match self {
+            _ if self.is_integer() => raw.parse().ok().map(Value::Int),
+            _ if self.is_numeric() => parse_float(raw).map(Value::Float),
+            DataType::BOOLEAN | DataType::BOOL => parse_bool(raw).map(Value::Bool),
+            _ => None,
+        };
+        value.unwrap_or_else(|| Value::Text(raw.to_string()))
+    }
+
+    /// Whether `NOW` is a valid value of this type.
+    pub fn is_time(&self) -> bool {
+        matches!(
+            self,
+            DataType::DATE | DataType::DATETIME | DataType::TIMESTAMP
+        )
+    }
+
+    /// Whether different strings can hold the same value, e.g. `TRUE` and `true`, so `=`
+    /// compares them with [`DataType::compare`].
+    pub fn has_aliases(&self) -> bool {
+        matches!(
+            self,
+            DataType::BOOLEAN | DataType::BOOL | DataType::UUID | DataType::BLOB(_)
+        )
+    }
+
+    /// Whether values of this type can be compared with `<`, `>` or `BETWEEN`, binary data
+    /// can't.
+    pub fn is_ordered(&self) -> bool {
+        !matches!(self, DataType::BLOB(_))
+    }
+
+    /// Whether storing `raw` generates a new value, i.e. `GEN_RANDOM_UUID` for a `UUID`.
+    pub fn is_generated(&self, raw: &str) -> bool {
+        *self == DataType::UUID && is_call(raw, GEN_RANDOM_UUID)
+    }
+
+    pub fn is_bool(&self) -> bool {
+        matches!(self, DataType::BOOLEAN | DataType::BOOL)
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer()
+            || matches!(
+                self,
+                DataType::FLOAT | DataType::DOUBLE | DataType::DEC | DataType::DECIMAL(_)
+            )
+    }
+
+    /// The form a valid value is stored in: booleans are `true` or `false`, `DECIMAL`s rounded,
+    /// dates zero padded, UUIDs hyphenated and blobs padded. `NOW` is replaced by the current local date or
+    /// time, or UTC time for a `TIMESTAMP`, and `GEN_RANDOM_UUID` by a new UUID.
+    pub fn normalize(&self, raw: &str) -> String {
+        match self {
+            DataType::BOOLEAN | DataType::BOOL => match parse_bool(raw) {
+                Some(b) => b.to_string(),
+                None => raw.to_string(),
+            },
+            DataType::DATE if is_call(raw, NOW) => {
+                Local::now().date_naive().format(DATE_FORMAT).to_string()
+            }
+            DataType::DATE => match parse_date(raw) {
+                Some(date) => date.format(DATE_FORMAT).to_string(),
+                None => raw.to_string(),
+            },
+            DataType::DATETIME if is_call(raw, NOW) => Local::now()
+                .naive_local()
+                .format(DATETIME_FORMAT)
+                .to_string(),
+            DataType::DATETIME => match parse_datetime(raw) {
+                Some(time) => time.format(DATETIME_FORMAT).to_string(),
+                None => raw.to_string(),
+            },
+            DataType::TIMESTAMP if is_call(raw, NOW) => {
+                Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+            }
+            DataType::UUID if is_call(raw, GEN_RANDOM_UUID) => {
+                Uuid::new_v4().hyphenated().to_string()
+            }
+            DataType::UUID => match Uuid::try_parse(raw) {
+                Ok(uuid) => uuid.hyphenated().to_string(),
+                Err(_) => raw.to_string(),
+            },
+            DataType::BLOB(_) => match base64_decode(raw) {
+                Some(bytes) => base64_encode(&bytes),
+                None => raw.to_string(),
+            },
+            _ => self.round(raw),
+        }
+    }
+
+    /// Round a `DECIMAL` value to its decimal places, other values are returned as they are.
+    pub fn round(&self, raw: &str) -> String {
+        match (self, raw.parse::<f64>()) {
+            (DataType::DECIMAL(places), Ok(value)) => format!("{:.*}", *places as usize, value),
+            _ => raw.to_string(),
+        }
+    }
+
+    /// Whether values of this type are stored as `i64`.
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            DataType::INTEGER | DataType::INT | DataType::SERIAL | DataType::RANGE(..)
+        )
+    }
+
+    /// Compare two raw values of this type. Numeric types are compared as numbers, dates and
+    /// timestamps chronologically and the rest lexicographically. Returns `None` if a value can't be parsed as this type.
+    pub fn compare(&self, a: &str, b: &str) -> Option<Ordering> {
+        match self {
+            _ if self.is_numeric() => match (self.to_value(Some(a)), self.to_value(Some(b))) {
+                (Value::Text(_), _) | (_, Value::Text(_)) => None,
+                (a, b) => a.partial_cmp(&b),
+            },
+            DataType::DATE => Some(parse_date(a)?.cmp(&parse_date(b)?)),
+            DataType::DATETIME => Some(parse_datetime(a)?.cmp(&parse_datetime(b)?)),
+            DataType::TIMESTAMP => Some(parse_timestamp(a)?.cmp(&parse_timestamp(b)?)),
+            DataType::UUID => Some(Uuid::try_parse(a).ok()?.cmp(&Uuid::try_parse(b).ok()?)),
+            DataType::BOOLEAN | DataType::BOOL => Some(parse_bool(a)?.cmp(&parse_bool(b)?)),
+            DataType::BLOB(_) => Some(base64_decode(a)?.cmp(&base64_decode(b)?)),
+            _ => Some(a.cmp(b)),
+        }
     }
 
     pub fn default(&self) -> String {
         let res = match self {
-            DataType::INTEGER | DataType::INT => "0",
-            DataType::FLOAT | DataType::DEC => "0.0",
+            // 0 unless it's out of the range, then the closest bound
+            DataType::RANGE(min, max) => {
+                return 0
+                    .max(min.unwrap_or(0))
+                    .min(max.unwrap_or(i64::MAX))
+                    .to_string()
+            }
+            DataType::INTEGER | DataType::INT | DataType::SERIAL => "0",
+            DataType::DECIMAL(_) => return self.round("0"),
+            DataType::FLOAT | DataType::DOUBLE | DataType::DEC => "0.0",
             DataType::TEXT | DataType::VARCHAR(_) => "",
             DataType::ENUM(val) => val[0].as_str(),
             DataType::BOOLEAN | DataType::BOOL => "false",
+            DataType::DATE => "1970-01-01",
+            DataType::DATETIME => "1970-01-01 00:00:00",
+            DataType::TIMESTAMP => "1970-01-01T00:00:00Z",
+            DataType::UUID => "00000000-0000-0000-0000-000000000000",
+            DataType::JSON => "{}",
+            DataType::BLOB(_) => "",
         };
 
         res.to_string()
     }
 }
 
+/// Whether `raw` is the function `name`, in any case and with or without `()`.
+fn is_call(raw: &str, name: &str) -> bool {
+    let raw = raw.trim();
+    let raw = match raw.strip_suffix(')') {
+        Some(call) => match call.trim_end().strip_suffix('(') {
+            Some(call) => call.trim_end(),
+            None => return false,
+        },
+        None => raw,
+    };
+    raw.eq_ignore_ascii_case(name)
+}
+
+/// A finite float, like `.5`, `-1.25` or `1e10`. `NaN` and infinities parse as `f64` but can't
+/// be compared, so they aren't valid values.
+fn parse_float(raw: &str) -> Option<f64> {
+    raw.parse::<f64>().ok().filter(|f| f.is_finite())
+}
+
+/// `true` or `false` in any case, or `1` and `0`.
+fn parse_bool(raw: &str) -> Option<bool> {
+    match raw.to_lowercase().as_str() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_date(raw: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(raw, DATE_FORMAT).ok()
+}
+
+fn parse_datetime(raw: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(raw, DATETIME_FORMAT).ok()
+}
+
+fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(raw)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
 #[cfg(test)]
 mod tests {
+    use std::cmp::Ordering;
+
+    use chrono::Local;
+
     use crate::types::DataTypesErr;
 
-    use super::DataType;
+    use super::{CoercionError, DataType, Value, BLOB_SIZE, DATE_FORMAT, GEN_RANDOM_UUID};
 
     #[test]
+    #[allow(deprecated)]
     fn should_convert_datatypes_as_str() {
         assert_eq!(DataType::BOOL.as_string(), "BOOL");
         assert_eq!(DataType::INTEGER.as_string(), "INTEGER");
@@ -155,17 +670,64 @@ mod tests {
         assert_eq!(dt.unwrap(), DataType::BOOL)
     }
 
+    #[test]
+    fn parse_as_serial() {
+        let dt = DataType::parse("serial").unwrap();
+        assert_eq!(dt, DataType::SERIAL);
+        assert!(dt.is_numeric());
+        assert!(dt.is_valid("a").is_err());
+    }
+
     #[test]
     fn parse_invalid_type() {
         let dt = DataType::parse(" Cool ");
         assert!(dt.is_err());
-        assert_eq!(dt, Err(DataTypesErr::InvalidType("Cool".into())));
+        assert_eq!(dt, Err(DataTypesErr::UnknownType("Cool".into())));
+        let message = dt.unwrap_err().to_string();
+        assert!(message.starts_with("Unknown type `Cool`, expected one of INTEGER, BIGINT, INT"));
+        assert!(message.ends_with("ENUM(values), BLOB(size)"));
+    }
+
+    #[test]
+    fn sql_names_parse_back() {
+        assert_eq!(DataType::parse("bigint").unwrap(), DataType::INTEGER);
+        assert_eq!(DataType::INTEGER.sql_name(), "INTEGER");
+        assert_eq!(DataType::DECIMAL(2).sql_name(), "DECIMAL(2)");
+
+        let types = [
+            DataType::INTEGER,
+            DataType::INT,
+            DataType::SERIAL,
+            DataType::RANGE(Some(-1), None),
+            DataType::FLOAT,
+            DataType::DOUBLE,
+            DataType::DEC,
+            DataType::DECIMAL(2),
+            DataType::TEXT,
+            DataType::VARCHAR(12),
+            DataType::ENUM(vec!["a b".into(), "c".into()]),
+            DataType::BOOLEAN,
+            DataType::BOOL,
+            DataType::DATE,
+            DataType::DATETIME,
+            DataType::TIMESTAMP,
+            DataType::UUID,
+            DataType::JSON,
+            DataType::BLOB(16),
+        ];
+        for dtype in types {
+            assert_eq!(DataType::parse(&dtype.sql_name()), Ok(dtype.clone()));
+            assert_eq!(DataType::parse(&dtype.sql_name().to_lowercase()), Ok(dtype));
+        }
     }
 
     #[test]
     fn parse_varchar_with_size() {
         let dt = DataType::parse("varchar(12)").unwrap();
         assert_eq!(dt, DataType::VARCHAR(12));
+        assert_eq!(serde_json::to_string(&dt).unwrap(), r#"{"VARCHAR":12}"#);
+        assert!(dt.is_valid("ñññññññññññø").is_ok());
+        assert!(dt.is_valid("ñññññññññññøø").is_err());
     }
 
     #[test]
@@ -180,10 +742,37 @@ mod tests {
         assert_eq!(dt, DataType::ENUM(vec!["1".into(), "2".into(), "3".into()]));
     }
 
+    #[test]
+    fn enum_values_are_kept_in_order() {
+        let dt = DataType::parse("ENUM('active','banned')").unwrap();
+        assert_eq!(dt, DataType::ENUM(vec!["active".into(), "banned".into()]));
+        assert_eq!(dt.default(), "active");
+        assert_eq!(
+            serde_json::to_string(&dt).unwrap(),
+            r#"{"ENUM":["active","banned"]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<DataType>(r#"{"ENUM":["active","banned"]}"#).unwrap(),
+            dt
+        );
+
+        // Values are plain strings, compared with their case
+        assert!(dt.is_valid("banned").is_ok());
+        assert!(matches!(
+            dt.is_valid("Banned"),
+            Err(DataTypesErr::InvalidEnum(_))
+        ));
+        assert_eq!(dt.compare("banned", "Banned"), Some(Ordering::Greater));
+    }
+
     #[test]
     fn parse_enum_values_with_single_quotes() {
         let dt = DataType::parse("ENUM('HUMAND', 'ALIEN')").unwrap();
         assert_eq!(dt, DataType::ENUM(vec!["HUMAND".into(), "ALIEN".into(),]));
+        assert!(matches!(
+            DataType::parse("ENUM( )"),
+            Err(DataTypesErr::InvalidEnum(_))
+        ));
     }
 
     #[test]
@@ -198,10 +787,320 @@ mod tests {
         assert_eq!(dt, DataType::ENUM(vec!["HUMAND".into(), "ALIEN".into(),]));
     }
 
+    #[test]
+    fn parse_range() {
+        let dt = DataType::parse("range(0, 150)").unwrap();
+        assert_eq!(dt, DataType::RANGE(Some(0), Some(150)));
+        assert_eq!(dt.sql_name(), "RANGE(0, 150)");
+        assert!(dt.is_integer());
+        assert!(dt.is_valid("150").is_ok());
+        assert_eq!(
+            dt.is_valid("151"),
+            Err(DataTypesErr::InvalidInt(
+                "'151' is out of RANGE(0, 150)".into()
+            ))
+        );
+        assert!(dt.is_valid("-1").is_err());
+        assert!(dt.is_valid("1.5").is_err());
+        assert_eq!(dt.compare("9", "10"), Some(Ordering::Less));
+
+        let dt = DataType::parse("RANGE(-10, )").unwrap();
+        assert_eq!(dt, DataType::RANGE(Some(-10), None));
+        assert!(dt.is_valid(&i64::MAX.to_string()).is_ok());
+        assert_eq!(dt.default(), "0");
+        assert_eq!(DataType::RANGE(Some(5), None).default(), "5");
+        assert_eq!(DataType::RANGE(None, Some(-5)).default(), "-5");
+
+        assert!(DataType::parse("RANGE(10, 1)").is_err());
+        assert!(DataType::parse("RANGE(1)").is_err());
+    }
+
+    #[test]
+    fn parse_decimal() {
+        let dt = DataType::parse("decimal(2)").unwrap();
+        assert_eq!(dt, DataType::DECIMAL(2));
+        assert_eq!(dt.sql_name(), "DECIMAL(2)");
+        assert!(dt.is_numeric());
+        assert!(dt.is_valid("1.005").is_ok());
+        assert!(matches!(
+            dt.is_valid("one"),
+            Err(DataTypesErr::InvalidFloat(_))
+        ));
+        assert_eq!(dt.round("1.256"), "1.26");
+        assert_eq!(dt.round("3"), "3.00");
+        assert_eq!(dt.convert("0.5").unwrap(), "0.50");
+        assert_eq!(dt.default(), "0.00");
+        assert_eq!(dt.compare("9.5", "10"), Some(Ordering::Less));
+        assert_eq!(DataType::DECIMAL(0).round("2.7"), "3");
+        assert_eq!(DataType::FLOAT.round("1.256"), "1.256");
+
+        assert!(DataType::parse("DECIMAL(300)").is_err());
+        assert!(DataType::parse("DECIMAL").is_err());
+    }
+
+    #[test]
+    fn parse_date() {
+        let dt = DataType::parse(" date ").unwrap();
+        assert_eq!(dt, DataType::DATE);
+        assert_eq!(dt.sql_name(), "DATE");
+        assert!(dt.is_valid("2024-02-29").is_ok());
+        for invalid in ["2023-02-29", "2024-13-01", "01/02/2024", "today"] {
+            assert!(matches!(
+                dt.is_valid(invalid),
+                Err(DataTypesErr::InvalidDate(_))
+            ));
+        }
+        assert_eq!(dt.convert("2024-2-1").unwrap(), "2024-02-01");
+        assert_eq!(dt.default(), "1970-01-01");
+        assert_eq!(
+            dt.normalize("now"),
+            Local::now().date_naive().format(DATE_FORMAT).to_string()
+        );
+        assert_eq!(
+            dt.compare("2024-02-01", "2023-12-15"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(dt.compare("2024-2-1", "2024-02-01"), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn parse_timestamp() {
+        let dt = DataType::parse("timestamp").unwrap();
+        assert_eq!(dt, DataType::TIMESTAMP);
+        assert!(dt.is_valid("2024-01-15T10:30:00Z").is_ok());
+        assert!(dt.is_valid("2024-01-15T10:30:00.5+02:00").is_ok());
+        for invalid in ["2024-01-15", "2024-01-15 10:30:00", "2024-01-15T25:00:00Z"] {
+            assert!(matches!(
+                dt.is_valid(invalid),
+                Err(DataTypesErr::InvalidTimestamp(_))
+            ));
+        }
+        // Kept with its offset but compared as an instant
+        assert_eq!(
+            dt.convert("2024-01-15T12:30:00+02:00").unwrap(),
+            "2024-01-15T12:30:00+02:00"
+        );
+        assert_eq!(
+            dt.compare("2024-01-15T12:30:00+02:00", "2024-01-15T10:30:00Z"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            dt.compare("2024-01-15T11:00:00+02:00", "2024-01-15T10:30:00Z"),
+            Some(Ordering::Less)
+        );
+        let now = dt.normalize("NOW");
+        assert!(now.ends_with('Z') && dt.is_valid(&now).is_ok());
+        assert_eq!(dt.default(), "1970-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn parse_datetime() {
+        let dt = DataType::parse("datetime").unwrap();
+        assert_eq!(dt, DataType::DATETIME);
+        assert!(dt.is_valid("2024-01-15 10:30:00").is_ok());
+        for invalid in [
+            "2024-01-15",
+            "2024-01-15T10:30:00",
+            "2024-01-15 25:61:00",
+            "2024-02-30 10:30:00",
+        ] {
+            assert!(matches!(
+                dt.is_valid(invalid),
+                Err(DataTypesErr::InvalidTimestamp(_))
+            ));
+        }
+        assert_eq!(
+            dt.convert("2024-1-5 9:05:00").unwrap(),
+            "2024-01-05 09:05:00"
+        );
+        assert_eq!(
+            dt.compare("2024-01-15 09:30:00", "2024-01-15 10:00:00"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(dt.compare("2024-01-15", "2024-01-15 10:00:00"), None);
+
+        for now in ["NOW", "now()", "Now( )"] {
+            assert!(dt.is_valid(now).is_ok());
+            assert!(dt.is_valid(&dt.normalize(now)).is_ok());
+        }
+        assert!(dt.is_valid("NOW(1)").is_err());
+        assert!(dt.is_valid("NOW)").is_err());
+        assert!(DataType::UUID.is_valid("gen_random_uuid()").is_ok());
+        assert_eq!(dt.default(), "1970-01-01 00:00:00");
+    }
+
+    #[test]
+    fn parse_uuid() {
+        let dt = DataType::parse("uuid").unwrap();
+        assert_eq!(dt, DataType::UUID);
+        let uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        assert_eq!(dt.convert(uuid).unwrap(), uuid);
+        assert_eq!(
+            dt.convert("67E5504410B1426F9247BB680E5FE0C8").unwrap(),
+            uuid
+        );
+        for invalid in ["67e55044-10b1-426f-9247", "not-a-uuid", ""] {
+            assert!(matches!(
+                dt.is_valid(invalid),
+                Err(DataTypesErr::InvalidUuid(_))
+            ));
+        }
+
+        let generated = dt.normalize("gen_random_uuid");
+        assert!(dt.is_valid(&generated).is_ok());
+        assert_ne!(generated, dt.normalize(GEN_RANDOM_UUID));
+        assert_eq!(
+            dt.compare(uuid, "67E5504410B1426F9247BB680E5FE0C8"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(dt.default(), "00000000-0000-0000-0000-000000000000");
+    }
+
+    #[test]
+    fn parse_json() {
+        let dt = DataType::parse("json").unwrap();
+        assert_eq!(dt, DataType::JSON);
+        for valid in [r#"{"a": {"b": [1, 2]}}"#, "[]", "3", r#""text""#, "null"] {
+            assert!(dt.is_valid(valid).is_ok(), "{}", valid);
+        }
+        for invalid in ["{", "{'a': 1}", "text", ""] {
+            assert!(matches!(
+                dt.is_valid(invalid),
+                Err(DataTypesErr::InvalidJson(_))
+            ));
+        }
+        // Kept as it was written
+        assert_eq!(dt.convert(r#"{ "a" : 1 }"#).unwrap(), r#"{ "a" : 1 }"#);
+        assert_eq!(dt.default(), "{}");
+    }
+
+    #[test]
+    fn coerce_values() {
+        let coerced = |dt: DataType, raw: &str| dt.coerce(raw).unwrap();
+        assert_eq!(coerced(DataType::INT, " 42 "), "42");
+        assert_eq!(coerced(DataType::INT, "007"), "7");
+        assert_eq!(coerced(DataType::INT, "+5"), "5");
+        assert_eq!(coerced(DataType::INT, "42.0"), "42");
+        assert_eq!(coerced(DataType::INT, "1e3"), "1000");
+        assert_eq!(coerced(DataType::FLOAT, " 1.50 "), "1.5");
+        assert_eq!(coerced(DataType::DOUBLE, "007.25"), "7.25");
+        assert_eq!(coerced(DataType::BOOL, " 1"), "true");
+        assert_eq!(coerced(DataType::BOOLEAN, "FALSE"), "false");
+        assert_eq!(coerced(DataType::DATE, " 2024-01-15 "), "2024-01-15");
+        // Whitespace is part of text
+        assert_eq!(coerced(DataType::TEXT, " jone "), " jone ");
+
+        assert_eq!(
+            DataType::INT.coerce("42.5"),
+            Err(CoercionError {
+                value: "42.5".into(),
+                dtype: "INT".into(),
+                reason: "not a whole number".into(),
+            })
+        );
+        assert!(DataType::INT.coerce("1e30").is_err());
+        assert!(DataType::INT.coerce("forty").is_err());
+        assert!(DataType::FLOAT.coerce("NaN").is_err());
+        assert!(DataType::BOOL.coerce("yes").is_err());
+    }
+
+    #[test]
+    fn parse_blob() {
+        assert_eq!(DataType::parse("blob").unwrap(), DataType::BLOB(BLOB_SIZE));
+        assert_eq!(DataType::parse("BLOB( 4 )").unwrap(), DataType::BLOB(4));
+        assert_eq!(DataType::BLOB(4).sql_name(), "BLOB(4)");
+
+        let dt = DataType::BLOB(4);
+        for valid in ["", "AAEC/w==", "AAEC/w", "AAECAw=="] {
+            assert!(dt.is_valid(valid).is_ok(), "{}", valid);
+        }
+        for invalid in ["AAEC/w=", "not base64", "AAECAwQ="] {
+            assert!(matches!(
+                dt.is_valid(invalid),
+                Err(DataTypesErr::InvalidBlob(_))
+            ));
+        }
+        // Stored and compared in the padded form
+        assert_eq!(dt.convert("AAEC/w").unwrap(), "AAEC/w==");
+        assert_eq!(dt.compare("AAEC/w", "AAEC/w=="), Some(Ordering::Equal));
+        assert!(!dt.is_ordered());
+        assert_eq!(
+            dt.to_value(Some("AAEC/w==")).to_bytes(),
+            Some(vec![0, 1, 2, 255])
+        );
+        assert_eq!(dt.default(), "");
+    }
+
+    #[test]
+    fn typed_values() {
+        assert_eq!(DataType::INT.to_value(Some("10")), Value::Int(10));
+        assert_eq!(
+            DataType::DECIMAL(2).to_value(Some("1.50")),
+            Value::Float(1.5)
+        );
+        assert_eq!(DataType::BOOL.to_value(Some("TRUE")), Value::Bool(true));
+        assert_eq!(
+            DataType::DATE.to_value(Some("2024-01-15")),
+            Value::Text("2024-01-15".into())
+        );
+        assert_eq!(
+            DataType::INT.to_value(Some("ten")),
+            Value::Text("ten".into())
+        );
+        assert_eq!(DataType::INT.to_value(None), Value::Null);
+
+        assert!(Value::Int(9) < Value::Int(10));
+        assert!(Value::Int(2) < Value::Float(2.5));
+        assert_eq!(Value::Null.partial_cmp(&Value::Null), None);
+        assert_eq!(Value::Int(1).partial_cmp(&Value::Text("1".into())), None);
+        assert_eq!(Value::Float(2.5).to_raw().as_deref(), Some("2.5"));
+        assert_eq!(Value::Null.to_raw(), None);
+        assert_eq!(
+            serde_json::to_string(&[Value::Int(1), Value::Bool(false), Value::Null]).unwrap(),
+            "[1,false,null]"
+        );
+    }
+
+    #[test]
+    fn parse_float_values() {
+        for dt in ["float", "double"] {
+            let dt = DataType::parse(dt).unwrap();
+            assert_eq!(dt.default(), "0.0");
+            for valid in [".5", "-1.25", "1e10", "2.5E-3", "7"] {
+                assert!(dt.is_valid(valid).is_ok(), "{}", valid);
+            }
+            for invalid in ["NaN", "inf", "-Infinity", "1e400", "1e", "1.2.3", ""] {
+                assert!(matches!(
+                    dt.is_valid(invalid),
+                    Err(DataTypesErr::InvalidFloat(_))
+                ));
+            }
+            assert_eq!(dt.compare(".5", "-1.25"), Some(Ordering::Greater));
+            assert_eq!(dt.compare("1e10", "999999999.5"), Some(Ordering::Greater));
+            assert_eq!(dt.compare("NaN", "1"), None);
+        }
+        assert_eq!(
+            DataType::DOUBLE.to_value(Some("NaN")),
+            Value::Text("NaN".into())
+        );
+    }
+
+    #[test]
+    fn compare_values() {
+        assert_eq!(DataType::INT.compare("9", "10"), Some(Ordering::Less));
+        assert_eq!(
+            DataType::FLOAT.compare("10.5", "9"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(DataType::TEXT.compare("9", "10"), Some(Ordering::Greater));
+        assert_eq!(DataType::INT.compare("nine", "10"), None);
+    }
+
     #[test]
     fn validate_datatypes() {
         let datatypes = [
             (DataType::BOOLEAN, "true"),
+            (DataType::BOOL, "False"),
             (DataType::FLOAT, "1.00"),
             (
                 DataType::ENUM(vec!["HUMAN".into(), "ALIAN".into()]),
@@ -247,4 +1146,21 @@ mod tests {
             .into_iter()
             .for_each(|(dtype, value, err_msg)| assert_eq!(dtype.is_valid(value), Err(err_msg)))
     }
+
+    #[test]
+    fn convert_values() {
+        assert_eq!(DataType::INT.convert("3.0").unwrap(), "3");
+        assert_eq!(DataType::INT.convert("12").unwrap(), "12");
+        assert!(DataType::INT.convert("3.5").is_err());
+        assert_eq!(DataType::FLOAT.convert("3").unwrap(), "3");
+        assert_eq!(DataType::BOOL.convert("TRUE").unwrap(), "true");
+        assert_eq!(DataType::BOOLEAN.convert("0").unwrap(), "false");
+        for invalid in ["yes", "", "2", "t"] {
+            assert!(matches!(
+                DataType::BOOL.convert(invalid),
+                Err(DataTypesErr::InvalidBool(_))
+            ));
+        }
+        assert_eq!(DataType::BOOL.compare("1", "True"), Some(Ordering::Equal));
+    }
 }
@@ -1,22 +1,24 @@
 use regex::Regex;
-use std::{
-    io::Write,
-    path::{Path, PathBuf},
-};
+use std::{cell::RefCell, collections::HashMap, io::Write, path::PathBuf};
 use tabwriter::TabWriter;
 
 use crate::{
     database::DB_DIR,
-    query_parser::SelectCols,
-    regex::RE_COMMA_SEPARATED_VALUES,
+    regex::{RE_COMMA_SEPARATED_VALUES, RE_ENUM_VALUES},
     table::{Table, TableEntries},
 };
 
-pub fn get_db_path(name: &str) -> PathBuf {
-    let base_dir = Path::new(DB_DIR);
-    let db_dir = base_dir.join(name);
+/// The directory holding every database, [`DB_DIR`] outside of tests. Tests get a directory of
+/// their own in the system temporary directory so they never touch real databases.
+pub fn get_base_dir() -> PathBuf {
+    match cfg!(test) {
+        true => std::env::temp_dir().join(format!("sql_tests_{}", std::process::id())),
+        false => PathBuf::from(DB_DIR),
+    }
+}
 
-    db_dir
+pub fn get_db_path(name: &str) -> PathBuf {
+    get_base_dir().join(name)
 }
 
 pub fn schema_file(file: &str) -> String {
@@ -27,6 +29,30 @@ pub fn table_file(file: &str) -> String {
     format!("{}.json", file)
 }
 
+pub fn index_file(file: &str, col: &str) -> String {
+    format!("{}.{}.index.json", file, col)
+}
+
+pub fn seq_file(file: &str) -> String {
+    format!("{}.seq", file)
+}
+
+pub fn version_file(file: &str) -> String {
+    format!("{}.version", file)
+}
+
+pub fn wal_file(file: &str) -> String {
+    format!("{}.wal", file)
+}
+
+pub fn view_file(file: &str) -> String {
+    format!("{}.view.json", file)
+}
+
+pub fn lock_file(file: &str) -> String {
+    format!("{}.lock", file)
+}
+
 pub fn get_schema_path(table: &Table) -> PathBuf {
     let db_dir = get_db_path(table.db);
     db_dir.join(schema_file(table.table_name))
@@ -37,19 +63,33 @@ pub fn get_table_path(table: &Table) -> PathBuf {
     db_dir.join(table_file(table.table_name))
 }
 
-pub fn get_cols(query: &str) -> SelectCols {
-    let query = query.trim();
+pub fn get_seq_path(table: &Table) -> PathBuf {
+    let db_dir = get_db_path(table.db);
+    db_dir.join(seq_file(table.table_name))
+}
+
+pub fn get_index_path(table: &Table, col: &str) -> PathBuf {
+    let db_dir = get_db_path(table.db);
+    db_dir.join(index_file(table.table_name, col))
+}
+
+pub fn get_version_path(table: &Table) -> PathBuf {
+    let db_dir = get_db_path(table.db);
+    db_dir.join(version_file(table.table_name))
+}
 
-    if query == "*" {
-        SelectCols::All
-    } else {
-        let cols = query
-            .split(",")
-            .map(|c| c.trim().to_string())
-            .collect::<Vec<_>>();
+pub fn get_wal_path(table: &Table) -> PathBuf {
+    let db_dir = get_db_path(table.db);
+    db_dir.join(wal_file(table.table_name))
+}
 
-        SelectCols::Cols(cols)
-    }
+pub fn get_view_path(db: &str, view_name: &str) -> PathBuf {
+    get_db_path(db).join(view_file(view_name))
+}
+
+pub fn get_lock_path(table: &Table) -> PathBuf {
+    let db_dir = get_db_path(table.db);
+    db_dir.join(lock_file(table.table_name))
 }
 
 pub fn get_comma_separated_values(query: &str) -> Vec<String> {
@@ -60,14 +100,185 @@ pub fn get_comma_separated_values(query: &str) -> Vec<String> {
         .collect::<Vec<_>>()
 }
 
+/// Extract a list of optionally quoted values like `'a', "b", c`.
+pub fn get_quoted_values(query: &str) -> Vec<String> {
+    let re = Regex::new(RE_ENUM_VALUES).unwrap();
+
+    re.captures_iter(query)
+        .map(|caps| caps["value"].trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>()
+}
+
+/// Match a value against a SQL `LIKE` pattern, where `%` matches any sequence of characters
+/// and `_` matches exactly one character.
+///
+/// The comparison is case-sensitive, case-insensitive matching should fold both sides first.
+pub fn like_match(value: &str, pattern: &str) -> bool {
+    let value = value.chars().collect::<Vec<_>>();
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let (mut v, mut p) = (0, 0);
+    // The position of the last `%` and the value position it currently stands for
+    let mut backtrack = None;
+
+    while v < value.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                backtrack = Some((p, v));
+                p += 1;
+            }
+            Some('_') => {
+                v += 1;
+                p += 1;
+            }
+            Some(&c) if c == value[v] => {
+                v += 1;
+                p += 1;
+            }
+            _ => match backtrack {
+                Some((wildcard, matched)) => {
+                    backtrack = Some((wildcard, matched + 1));
+                    p = wildcard + 1;
+                    v = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+/// The most patterns [`cached_regex`] keeps before starting over.
+const REGEX_CACHE_SIZE: usize = 64;
+
+thread_local! {
+    static REGEX_CACHE: RefCell<HashMap<String, Regex>> = RefCell::new(HashMap::new());
+}
+
+/// Compile a `REGEXP` pattern, patterns already compiled on this thread are reused so matching
+/// rows doesn't compile the pattern again for every row.
+pub fn cached_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    REGEX_CACHE.with(|cache| {
+        if let Some(re) = cache.borrow().get(pattern) {
+            return Ok(re.clone());
+        }
+
+        let re = Regex::new(pattern)?;
+        let mut cache = cache.borrow_mut();
+        if cache.len() >= REGEX_CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(pattern.to_string(), re.clone());
+        Ok(re)
+    })
+}
+
+/// Whether `value` matches the regex `pattern` anywhere, `None` if the pattern is invalid.
+pub fn regex_match(value: &str, pattern: &str) -> Option<bool> {
+    cached_regex(pattern).ok().map(|re| re.is_match(value))
+}
+
+/// The value at `path` in a JSON document, like `$.user.tags[0]`. `$` is the document, `.key`
+/// an object member and `[n]` an array item. `None` if the document isn't valid JSON or
+/// nothing is at the path.
+///
+/// Strings are returned without their quotes, other values as compact JSON.
+pub fn json_extract(document: &str, path: &str) -> Option<String> {
+    let document = serde_json::from_str::<serde_json::Value>(document).ok()?;
+    let mut rest = path.trim().strip_prefix('$')?;
+    let mut value = &document;
+
+    while !rest.is_empty() {
+        if let Some(member) = rest.strip_prefix('.') {
+            let end = member.find(['.', '[']).unwrap_or(member.len());
+            value = value.get(&member[..end])?;
+            rest = &member[end..];
+        } else if let Some(item) = rest.strip_prefix('[') {
+            let end = item.find(']')?;
+            value = value.get(item[..end].trim().parse::<usize>().ok()?)?;
+            rest = &item[end + 1..];
+        } else {
+            return None;
+        }
+    }
+
+    Some(match value {
+        serde_json::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    })
+}
+
+/// The standard base64 alphabet, used for `BLOB` values.
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as padded standard base64, the form `BLOB` values are stored in.
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | ((b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// The number of bytes a base64 value decodes to, without decoding it. Only meaningful if
+/// `raw` is valid base64.
+pub fn base64_decoded_len(raw: &str) -> usize {
+    raw.trim_end_matches('=').len() * 3 / 4
+}
+
+/// Decode standard base64, the padding can be left out. `None` if `raw` isn't base64 or has
+/// unused bits set, so every decoded value has a single padded form.
+pub fn base64_decode(raw: &str) -> Option<Vec<u8>> {
+    let raw = raw.as_bytes();
+    let data = match raw.iter().position(|&b| b == b'=') {
+        Some(pos) if raw.len().is_multiple_of(4) && raw.len() - pos <= 2 => raw[pos..]
+            .iter()
+            .all(|&b| b == b'=')
+            .then_some(&raw[..pos])?,
+        Some(_) => return None,
+        None => raw,
+    };
+    if data.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(data.len() / 4 * 3 + 2);
+    for chunk in data.chunks(4) {
+        let mut n = 0u32;
+        for (i, &b) in chunk.iter().enumerate() {
+            let digit = BASE64_ALPHABET.iter().position(|&a| a == b)? as u32;
+            n |= digit << (18 - 6 * i);
+        }
+        let len = chunk.len() * 6 / 8;
+        // The bits after the last byte have to be zero
+        if n & (0xFF_FFFF >> (8 * len)) != 0 {
+            return None;
+        }
+        bytes.extend((0..len).map(|i| (n >> (16 - 8 * i)) as u8));
+    }
+    Some(bytes)
+}
+
 pub fn display_entries(entries: TableEntries) {
     let mut tw = TabWriter::new(vec![]);
 
-    if let Some(entry) = entries.get(0) {
+    if let Some(entry) = entries.first() {
         let mut header = String::new();
 
         let mut sorted_cols = vec![];
-        entry.keys().into_iter().for_each(|k| {
+        entry.keys().for_each(|k| {
             sorted_cols.push(k);
             header.push_str(&format!("{k}\t"));
         });
@@ -77,7 +288,8 @@ pub fn display_entries(entries: TableEntries) {
         entries.iter().for_each(|row| {
             let mut row_str = String::new();
             sorted_cols.iter().for_each(|&k| {
-                let value = row.get(k).unwrap();
+                let value = row.get(k).cloned().flatten();
+                let value = value.as_deref().unwrap_or("NULL");
                 row_str.push_str(&format!("{value}\t"));
             });
 
@@ -90,3 +302,90 @@ pub fn display_entries(entries: TableEntries) {
         println!("{}", written);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        base64_decode, base64_encode, cached_regex, json_extract, like_match, regex_match,
+    };
+
+    #[test]
+    fn like_with_percent_wildcard() {
+        assert!(like_match("jone@example.com", "%@example.com"));
+        assert!(like_match("user_1", "user%"));
+        assert!(like_match("jone@example.com", "%example%"));
+        assert!(like_match("", "%"));
+        assert!(!like_match("jone@example.org", "%@example.com"));
+    }
+
+    #[test]
+    fn like_with_underscore_wildcard() {
+        assert!(like_match("cat", "c_t"));
+        assert!(like_match("user_1", "user__"));
+        assert!(!like_match("cart", "c_t"));
+        assert!(!like_match("ct", "c_t"));
+    }
+
+    #[test]
+    fn like_is_case_sensitive() {
+        assert!(!like_match("Jone", "jone"));
+        assert!(like_match("Jone", "J%"));
+    }
+
+    #[test]
+    fn regex_patterns_are_cached() {
+        assert_eq!(regex_match("user_42", r"^user_\d+$"), Some(true));
+        assert_eq!(regex_match("user_x", r"^user_\d+$"), Some(false));
+        assert_eq!(regex_match("anything", "(unclosed"), None);
+        assert!(cached_regex("(unclosed").is_err());
+
+        let re = cached_regex(r"^user_\d+$").unwrap();
+        assert_eq!(re.as_str(), r"^user_\d+$");
+    }
+
+    #[test]
+    fn extract_json_paths() {
+        let doc = r#"{"a": {"b": "x", "n": 1.5, "tags": ["red", {"c": true}]}, "e": null}"#;
+        assert_eq!(json_extract(doc, "$.a.b").as_deref(), Some("x"));
+        assert_eq!(json_extract(doc, "$.a.n").as_deref(), Some("1.5"));
+        assert_eq!(json_extract(doc, "$.a.tags[0]").as_deref(), Some("red"));
+        assert_eq!(json_extract(doc, "$.a.tags[1].c").as_deref(), Some("true"));
+        assert_eq!(json_extract(doc, "$.e").as_deref(), Some("null"));
+        assert_eq!(
+            json_extract(doc, "$.a.tags").as_deref(),
+            Some(r#"["red",{"c":true}]"#)
+        );
+
+        for missing in ["$.b", "$.a.b.c", "$.a.tags[2]", "$.a.tags[x]", "a.b", "$a"] {
+            assert_eq!(json_extract(doc, missing), None, "{}", missing);
+        }
+        assert_eq!(json_extract("not json", "$"), None);
+    }
+
+    #[test]
+    fn like_backtracks_on_partial_matches() {
+        assert!(like_match("abcabcabd", "%abd"));
+        assert!(like_match("mississippi", "m%iss%pi"));
+        assert!(!like_match("mississippi", "m%iss%pa"));
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob", &[0, 255, 128, 7]] {
+            assert_eq!(base64_decode(&base64_encode(bytes)).unwrap(), bytes);
+        }
+        assert_eq!(base64_encode(b"foob"), "Zm9vYg==");
+        assert_eq!(base64_decode("Zm9vYg"), Some(b"foob".to_vec()));
+
+        for invalid in [
+            "Zm9vY",
+            "Zm9vYg=",
+            "Zm9v\nYg==",
+            "Zm9vYh==",
+            "Zm=vYg==",
+            "Zm9v-g==",
+        ] {
+            assert_eq!(base64_decode(invalid), None, "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::{fs, io};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    database::{Database, DatabaseError},
+    query_parser::{Condition, OrderClause, SelectCols, SelectOptions},
+    table::{Table, TableEntries, TableError},
+    utils::get_view_path,
+};
+
+#[derive(Debug, Error)]
+pub enum ViewError {
+    #[error("Table Error")]
+    TableErr(#[from] TableError),
+    #[error("DB Error")]
+    DBErr(#[from] DatabaseError),
+    #[error("IO Error")]
+    IoErr(#[from] io::Error),
+    #[error("Invalid view definition")]
+    SerializationErr(#[from] serde_json::Error),
+    #[error("A table or view with this name already exists")]
+    ViewAlreadyExists(String),
+    #[error("View not found")]
+    ViewNotFound(String),
+    #[error("Table `{0}` the view selects from doesn't exist")]
+    SourceTableNotFound(String),
+}
+
+type ViewResult<T> = Result<T, ViewError>;
+
+/// What is stored in `<view>.view.json`.
+#[derive(Serialize, Deserialize)]
+struct Definition {
+    source_table: String,
+    cols: SelectCols,
+    condition: Option<Condition>,
+}
+
+/// A named select on a table, stored as `<view>.view.json` next to the tables.
+///
+/// A view holds no rows, every [`View::select`] runs the query against the current rows of the
+/// source table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct View<'a> {
+    pub db: &'a str,
+    pub view_name: &'a str,
+    pub source_table: String,
+    pub cols: SelectCols,
+    pub condition: Option<Condition>,
+}
+
+impl<'a> View<'a> {
+    pub fn new(
+        db: &'a str,
+        view_name: &'a str,
+        source_table: &str,
+        cols: SelectCols,
+        condition: Option<Condition>,
+    ) -> ViewResult<Self> {
+        Database::exists_or_err(db)?;
+        Ok(Self {
+            db,
+            view_name,
+            source_table: source_table.to_string(),
+            cols,
+            condition,
+        })
+    }
+
+    /// Load the view saved under `view_name` by [`View::create`].
+    pub fn open(db: &'a str, view_name: &'a str) -> ViewResult<Self> {
+        Database::exists_or_err(db)?;
+        let path = get_view_path(db, view_name);
+        if !path.exists() {
+            return Err(ViewError::ViewNotFound(view_name.to_string()));
+        }
+
+        let definition: Definition = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(Self {
+            db,
+            view_name,
+            source_table: definition.source_table,
+            cols: definition.cols,
+            condition: definition.condition,
+        })
+    }
+
+    /// Save the view definition. The columns of the view must be columns of the source table
+    /// and the query is run once first, so a view with an invalid condition isn't saved.
+    pub fn create(&self) -> ViewResult<()> {
+        if self.exists() || Table::new(self.db, self.view_name)?.exist() {
+            return Err(ViewError::ViewAlreadyExists(self.view_name.to_string()));
+        }
+
+        let table = Table::new(self.db, &self.source_table)?;
+        if !table.exist() {
+            return Err(ViewError::SourceTableNotFound(self.source_table.clone()));
+        }
+        let schema = table.read_schema()?;
+        let selected = match &self.cols {
+            SelectCols::All => vec![],
+            SelectCols::Cols(items) => items.iter().filter_map(|item| item.col()).collect(),
+        };
+        let keys = self.condition.iter().flat_map(|c| c.keys());
+        if let Some(col) = selected
+            .into_iter()
+            .chain(keys.map(|key| key.as_str()))
+            .find(|col| !schema.cols.iter().any(|c| c == col.trim()))
+        {
+            return Err(TableError::ColNotFound(col.to_string()).into());
+        }
+        self.select(None, Some(0), None)?;
+
+        let definition = Definition {
+            source_table: self.source_table.clone(),
+            cols: self.cols.clone(),
+            condition: self.condition.clone(),
+        };
+        // Write to a temporary file and move it in place, like the table rows
+        let path = get_view_path(self.db, self.view_name);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(&definition)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// The rows of the source table matching the view, sorted and paged like
+    /// [`Table::select`].
+    pub fn select(
+        &self,
+        order: Option<Vec<OrderClause>>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> ViewResult<TableEntries> {
+        let table = Table::new(self.db, &self.source_table)?;
+        if !table.exist() {
+            return Err(ViewError::SourceTableNotFound(self.source_table.clone()));
+        }
+
+        let options = SelectOptions {
+            limit,
+            offset,
+            order,
+            ..Default::default()
+        };
+        Ok(table.select(self.cols.clone(), self.condition.clone(), options)?)
+    }
+
+    pub fn drop(&self) -> ViewResult<()> {
+        if !self.exists() {
+            return Err(ViewError::ViewNotFound(self.view_name.to_string()));
+        }
+        fs::remove_file(get_view_path(self.db, self.view_name))?;
+        Ok(())
+    }
+
+    pub fn exists(&self) -> bool {
+        get_view_path(self.db, self.view_name).exists()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        database::Database,
+        query_parser::{Condition, Operator, OrderClause, SelectCols, SortDirection},
+        table::{
+            tests::{row, users_table, TestDB},
+            Table, TableError,
+        },
+    };
+
+    use super::{View, ViewError};
+
+    fn names(view: &View) -> Vec<String> {
+        let order = vec![OrderClause::new("name", SortDirection::Asc)];
+        view.select(Some(order), None, None)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry["name"].clone().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn view_selects_live_rows() {
+        let db = TestDB::new("test_view_select");
+        let table = users_table(&db);
+        let condition = Condition::leaf("age", Operator::GtEq, "30");
+        let view = View::new(
+            &db.0,
+            "adults",
+            "users",
+            SelectCols::parse("name").unwrap(),
+            Some(condition),
+        )
+        .unwrap();
+        view.create().unwrap();
+
+        let view = View::open(&db.0, "adults").unwrap();
+        assert_eq!(view.source_table, "users");
+        assert_eq!(names(&view), vec!["doe", "jane"]);
+        table
+            .insert(SelectCols::All, vec![row(&["max", "40"])])
+            .unwrap();
+        assert_eq!(names(&view), vec!["doe", "jane", "max"]);
+        assert_eq!(view.select(None, Some(1), Some(1)).unwrap().len(), 1);
+        assert_eq!(view.select(None, None, None).unwrap()[0].len(), 1);
+
+        assert_eq!(Database::list_tables(&db.0, false).unwrap(), vec!["users"]);
+        assert_eq!(
+            Database::list_tables(&db.0, true).unwrap(),
+            vec!["adults", "users"]
+        );
+        assert!(Database::incomplete_tables(&db.0).unwrap().is_empty());
+
+        view.drop().unwrap();
+        assert!(matches!(view.drop(), Err(ViewError::ViewNotFound(_))));
+        assert!(matches!(
+            View::open(&db.0, "adults"),
+            Err(ViewError::ViewNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn view_errors() {
+        let db = TestDB::new("test_view_errors");
+        let mut table = users_table(&db);
+        let view = |name, cols: &str| {
+            View::new(&db.0, name, "users", SelectCols::parse(cols).unwrap(), None).unwrap()
+        };
+
+        assert!(matches!(
+            view("emails", "email").create(),
+            Err(ViewError::TableErr(TableError::ColNotFound(_)))
+        ));
+        assert!(!view("emails", "email").exists());
+        let ages = View::new(
+            &db.0,
+            "ages",
+            "users",
+            SelectCols::All,
+            Some(Condition::leaf("years", Operator::Gt, "1")),
+        )
+        .unwrap();
+        assert!(matches!(
+            ages.create(),
+            Err(ViewError::TableErr(TableError::ColNotFound(col))) if col == "years"
+        ));
+        assert!(matches!(
+            view("users", "*").create(),
+            Err(ViewError::ViewAlreadyExists(_))
+        ));
+        view("everyone", "*").create().unwrap();
+        assert!(matches!(
+            view("everyone", "name").create(),
+            Err(ViewError::ViewAlreadyExists(_))
+        ));
+        let schema = table.read_schema().unwrap();
+        assert!(matches!(
+            Table::new(&db.0, "everyone").unwrap().create(schema),
+            Err(TableError::TableAlreadyExists(name)) if name == "everyone"
+        ));
+        assert!(matches!(
+            table.rename("everyone"),
+            Err(TableError::TableAlreadyExists(name)) if name == "everyone"
+        ));
+
+        table.drop().unwrap();
+        let everyone = View::open(&db.0, "everyone").unwrap();
+        assert!(matches!(
+            everyone.select(None, None, None),
+            Err(ViewError::SourceTableNotFound(table)) if table == "users"
+        ));
+    }
+}